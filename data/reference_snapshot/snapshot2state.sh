@@ -1,5 +1,6 @@
 #!/bin/bash
 
+# deprecated: use "whirlpool-replay import-snapshot" instead
 # usage: snapshot2state.sh <slot> <blockHeight> <blockTime> <snapshot.csv.gz> <program.so>
 
 SLOT=$1
@@ -0,0 +1,211 @@
+use std::collections::{BTreeSet, HashMap};
+
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use serde_derive::Serialize;
+use serde_json::Value;
+
+use crate::account_json::account_to_json;
+use crate::account_kind::{classify_account, AccountKind};
+use crate::replay_instruction::WritableAccountSnapshot;
+use crate::types::AccountMap;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccountChangeKind {
+  Created,
+  Updated,
+  Closed,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+  // flattened path (e.g. "sqrt_price", "ticks[12].liquidity_net", "reward_infos[0].amount_owed")
+  pub field: String,
+  // None if the account doesn't exist on that side
+  pub pre: Option<String>,
+  pub post: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDiff {
+  pub pubkey: String,
+  pub kind: AccountKind,
+  pub change: AccountChangeKind,
+  pub fields: Vec<FieldChange>,
+}
+
+impl WritableAccountSnapshot {
+  pub fn diff(&self) -> Vec<AccountDiff> {
+    return diff_accounts(&self.pre_snapshot, &self.post_snapshot);
+  }
+}
+
+// field level diff of the accounts changed between pre and post (sorted by pubkey).
+// unchanged accounts are not included.
+// accounts not owned by whirlpool program are compared as a whole "data" field (base64).
+pub fn diff_accounts(pre: &AccountMap, post: &AccountMap) -> Vec<AccountDiff> {
+  let pubkeys: BTreeSet<&String> = pre.keys().chain(post.keys()).collect();
+
+  let mut diffs = Vec::new();
+  for pubkey in pubkeys {
+    let pre_data = pre.get(pubkey);
+    let post_data = post.get(pubkey);
+    if pre_data == post_data {
+      continue;
+    }
+
+    let change = match (pre_data, post_data) {
+      (None, Some(_)) => AccountChangeKind::Created,
+      (Some(_), None) => AccountChangeKind::Closed,
+      _ => AccountChangeKind::Updated,
+    };
+
+    // closed account keeps its kind
+    let kind = classify_account(post_data.or(pre_data).unwrap());
+
+    let pre_fields = pre_data.map(|data| flatten_account(data)).unwrap_or_default();
+    let post_fields = post_data.map(|data| flatten_account(data)).unwrap_or_default();
+    diffs.push(AccountDiff {
+      pubkey: pubkey.clone(),
+      kind,
+      change,
+      fields: diff_fields(pre_fields, post_fields),
+    });
+  }
+
+  return diffs;
+}
+
+fn flatten_account(data: &[u8]) -> Vec<(String, String)> {
+  let mut fields = Vec::new();
+  match account_to_json(data) {
+    Some(json) => flatten_json(&json, String::new(), &mut fields),
+    None => fields.push(("data".to_string(), BASE64_STANDARD.encode(data))),
+  }
+  return fields;
+}
+
+fn flatten_json(value: &Value, path: String, fields: &mut Vec<(String, String)>) {
+  match value {
+    Value::Object(map) => {
+      for (key, value) in map {
+        let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+        flatten_json(value, path, fields);
+      }
+    }
+    Value::Array(values) => {
+      for (index, value) in values.iter().enumerate() {
+        flatten_json(value, format!("{}[{}]", path, index), fields);
+      }
+    }
+    Value::String(s) => fields.push((path, s.clone())),
+    _ => fields.push((path, value.to_string())),
+  }
+}
+
+fn diff_fields(pre: Vec<(String, String)>, post: Vec<(String, String)>) -> Vec<FieldChange> {
+  // both sides have the same layout except for created/closed accounts
+  let mut post_values: HashMap<String, String> = post.iter().cloned().collect();
+
+  let mut changes = Vec::new();
+  for (field, pre_value) in pre {
+    let post_value = post_values.remove(&field);
+    if post_value.as_ref() != Some(&pre_value) {
+      changes.push(FieldChange { field, pre: Some(pre_value), post: post_value });
+    }
+  }
+
+  // keep the order of post for the fields only in post
+  for (field, _) in post {
+    if let Some(post_value) = post_values.remove(&field) {
+      changes.push(FieldChange { field, pre: None, post: Some(post_value) });
+    }
+  }
+
+  return changes;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anchor_lang::AccountSerialize;
+  use solana_sdk::pubkey::Pubkey;
+  use whirlpool_base::state::PositionBundle;
+
+  fn position_bundle_data(mint: &Pubkey, bundle_indexes: &[usize]) -> Vec<u8> {
+    let mut position_bitmap = [0u8; 32];
+    for index in bundle_indexes {
+      position_bitmap[index / 8] |= 1 << (index % 8);
+    }
+
+    let mut data = Vec::new();
+    PositionBundle { position_bundle_mint: *mint, position_bitmap }.try_serialize(&mut data).unwrap();
+    return data;
+  }
+
+  fn field(field: &str, pre: Option<&str>, post: Option<&str>) -> FieldChange {
+    return FieldChange {
+      field: field.to_string(),
+      pre: pre.map(|value| value.to_string()),
+      post: post.map(|value| value.to_string()),
+    };
+  }
+
+  #[test]
+  fn test_diff_accounts() {
+    let mint = Pubkey::new_unique();
+    let mut pre = AccountMap::new();
+    let mut post = AccountMap::new();
+
+    // unchanged
+    pre.insert("a_unchanged".to_string(), position_bundle_data(&mint, &[0]));
+    post.insert("a_unchanged".to_string(), position_bundle_data(&mint, &[0]));
+    // bundled position 1 closed, 5 and 7 opened
+    pre.insert("b_updated".to_string(), position_bundle_data(&mint, &[1, 2]));
+    post.insert("b_updated".to_string(), position_bundle_data(&mint, &[2, 5, 7]));
+    // created and closed
+    post.insert("c_created".to_string(), position_bundle_data(&mint, &[]));
+    pre.insert("d_closed".to_string(), position_bundle_data(&mint, &[]));
+    // not a whirlpool account
+    pre.insert("e_unknown".to_string(), vec![1, 2, 3]);
+    post.insert("e_unknown".to_string(), vec![1, 2, 4]);
+
+    let diffs = diff_accounts(&pre, &post);
+    let pubkeys: Vec<&str> = diffs.iter().map(|diff| diff.pubkey.as_str()).collect();
+    assert_eq!(pubkeys, vec!["b_updated", "c_created", "d_closed", "e_unknown"]);
+
+    assert_eq!(diffs[0].kind, AccountKind::PositionBundle);
+    assert_eq!(diffs[0].change, AccountChangeKind::Updated);
+    assert_eq!(
+      diffs[0].fields,
+      vec![
+        field("position_bitmap[1]", Some("true"), Some("false")),
+        field("position_bitmap[5]", Some("false"), Some("true")),
+        field("position_bitmap[7]", Some("false"), Some("true")),
+      ]
+    );
+
+    // all fields only on one side
+    assert_eq!(diffs[1].kind, AccountKind::PositionBundle);
+    assert_eq!(diffs[1].change, AccountChangeKind::Created);
+    assert_eq!(diffs[1].fields.len(), 1 + 256);
+    assert!(diffs[1].fields.contains(&field("position_bundle_mint", None, Some(&mint.to_string()))));
+    assert!(diffs[1].fields.contains(&field("position_bitmap[255]", None, Some("false"))));
+
+    // closed account keeps its kind
+    assert_eq!(diffs[2].kind, AccountKind::PositionBundle);
+    assert_eq!(diffs[2].change, AccountChangeKind::Closed);
+    assert_eq!(diffs[2].fields.len(), 1 + 256);
+    assert!(diffs[2].fields.contains(&field("position_bundle_mint", Some(&mint.to_string()), None)));
+
+    assert_eq!(diffs[3].kind, AccountKind::Unknown);
+    assert_eq!(diffs[3].change, AccountChangeKind::Updated);
+    assert_eq!(diffs[3].fields, vec![field("data", Some("AQID"), Some("AQIE"))]);
+
+    // the same diff through the snapshot of a replayed instruction
+    let snapshot = WritableAccountSnapshot { pre_snapshot: pre, post_snapshot: post };
+    assert_eq!(snapshot.diff(), diffs);
+  }
+}
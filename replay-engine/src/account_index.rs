@@ -0,0 +1,313 @@
+use std::collections::{HashMap, HashSet};
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::account_kind::{classify_account, AccountKind};
+use crate::types::AccountMap;
+
+// offsets of the indexed fields (these fields never change after initialization)
+const POSITION_WHIRLPOOL_OFFSET: usize = 8;
+const POSITION_POSITION_MINT_OFFSET: usize = 40;
+const TICK_ARRAY_WHIRLPOOL_OFFSET: usize = 9956;
+const POSITION_BUNDLE_POSITION_BUNDLE_MINT_OFFSET: usize = 8;
+
+enum IndexEntry {
+  Position { whirlpool: String, position_mint: String },
+  TickArray { whirlpool: String },
+  PositionBundle { position_bundle_mint: String },
+}
+
+// AccountIndex provides lookups that would otherwise need a scan of the whole AccountMap.
+// ReplayEngine keeps it up to date on every write-back (including closed accounts).
+//
+// bundled positions have the mint of their position bundle as position_mint,
+// so positions are indexed by mint as a set.
+#[derive(Default)]
+pub struct AccountIndex {
+  entries: HashMap<String, IndexEntry>,
+  positions_by_whirlpool: HashMap<String, HashSet<String>>,
+  tick_arrays_by_whirlpool: HashMap<String, HashSet<String>>,
+  positions_by_mint: HashMap<String, HashSet<String>>,
+  position_bundle_by_mint: HashMap<String, String>,
+}
+
+impl AccountIndex {
+  pub fn build(accounts: &AccountMap) -> AccountIndex {
+    let mut index = AccountIndex::default();
+    for (pubkey, data) in accounts {
+      index.insert(pubkey, data);
+    }
+    return index;
+  }
+
+  pub fn get_positions_of(&self, whirlpool: &String) -> Vec<String> {
+    return get_sorted(self.positions_by_whirlpool.get(whirlpool));
+  }
+
+  pub fn get_tick_arrays_of(&self, whirlpool: &String) -> Vec<String> {
+    return get_sorted(self.tick_arrays_by_whirlpool.get(whirlpool));
+  }
+
+  // None for position bundle mint (use get_bundled_positions_of)
+  pub fn get_position_by_mint(&self, position_mint: &String) -> Option<String> {
+    if self.position_bundle_by_mint.contains_key(position_mint) {
+      return None;
+    }
+    return self
+      .positions_by_mint
+      .get(position_mint)
+      .and_then(|positions| positions.iter().next().cloned());
+  }
+
+  pub fn get_bundled_positions_of(&self, position_bundle: &String) -> Vec<String> {
+    return match self.entries.get(position_bundle) {
+      Some(IndexEntry::PositionBundle { position_bundle_mint }) => get_sorted(self.positions_by_mint.get(position_bundle_mint)),
+      _ => vec![],
+    };
+  }
+
+  // reflect a write-back (data is None if the account has been closed)
+  pub fn update(&mut self, pubkey: &String, data: Option<&Vec<u8>>) {
+    match data {
+      // indexed fields are immutable, so existing entry is kept as is
+      Some(_) if self.entries.contains_key(pubkey) => {}
+      Some(data) => self.insert(pubkey, data),
+      None => self.remove(pubkey),
+    }
+  }
+
+  fn insert(&mut self, pubkey: &String, data: &[u8]) {
+    let entry = match classify_account(data) {
+      AccountKind::Position if data.len() >= POSITION_POSITION_MINT_OFFSET + 32 => IndexEntry::Position {
+        whirlpool: read_pubkey(data, POSITION_WHIRLPOOL_OFFSET),
+        position_mint: read_pubkey(data, POSITION_POSITION_MINT_OFFSET),
+      },
+      AccountKind::TickArray if data.len() >= TICK_ARRAY_WHIRLPOOL_OFFSET + 32 => IndexEntry::TickArray {
+        whirlpool: read_pubkey(data, TICK_ARRAY_WHIRLPOOL_OFFSET),
+      },
+      AccountKind::PositionBundle if data.len() >= POSITION_BUNDLE_POSITION_BUNDLE_MINT_OFFSET + 32 => IndexEntry::PositionBundle {
+        position_bundle_mint: read_pubkey(data, POSITION_BUNDLE_POSITION_BUNDLE_MINT_OFFSET),
+      },
+      _ => return,
+    };
+
+    match &entry {
+      IndexEntry::Position { whirlpool, position_mint } => {
+        self.positions_by_whirlpool.entry(whirlpool.clone()).or_default().insert(pubkey.clone());
+        self.positions_by_mint.entry(position_mint.clone()).or_default().insert(pubkey.clone());
+      }
+      IndexEntry::TickArray { whirlpool } => {
+        self.tick_arrays_by_whirlpool.entry(whirlpool.clone()).or_default().insert(pubkey.clone());
+      }
+      IndexEntry::PositionBundle { position_bundle_mint } => {
+        self.position_bundle_by_mint.insert(position_bundle_mint.clone(), pubkey.clone());
+      }
+    }
+    self.entries.insert(pubkey.clone(), entry);
+  }
+
+  fn remove(&mut self, pubkey: &String) {
+    match self.entries.remove(pubkey) {
+      Some(IndexEntry::Position { whirlpool, position_mint }) => {
+        remove_from_set(&mut self.positions_by_whirlpool, &whirlpool, pubkey);
+        remove_from_set(&mut self.positions_by_mint, &position_mint, pubkey);
+      }
+      Some(IndexEntry::TickArray { whirlpool }) => {
+        remove_from_set(&mut self.tick_arrays_by_whirlpool, &whirlpool, pubkey);
+      }
+      Some(IndexEntry::PositionBundle { position_bundle_mint }) => {
+        self.position_bundle_by_mint.remove(&position_bundle_mint);
+      }
+      None => {}
+    }
+  }
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> String {
+  let bytes: [u8; 32] = data[offset..offset + 32].try_into().unwrap();
+  return Pubkey::new_from_array(bytes).to_string();
+}
+
+fn remove_from_set(map: &mut HashMap<String, HashSet<String>>, key: &String, pubkey: &String) {
+  if let Some(set) = map.get_mut(key) {
+    set.remove(pubkey);
+    if set.is_empty() {
+      map.remove(key);
+    }
+  }
+}
+
+fn get_sorted(set: Option<&HashSet<String>>) -> Vec<String> {
+  let mut pubkeys: Vec<String> = set.map(|set| set.iter().cloned().collect()).unwrap_or_default();
+  pubkeys.sort();
+  return pubkeys;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anchor_lang::{AccountSerialize, Discriminator};
+  use whirlpool_base::state::{Position, PositionBundle, TickArray};
+
+  use std::str::FromStr;
+
+  use crate::util::update_account_map;
+
+  const TICK_ARRAY_LEN: usize = 9988;
+
+  fn unique_pubkey() -> String {
+    return Pubkey::new_unique().to_string();
+  }
+
+  fn to_pubkey(pubkey: &String) -> Pubkey {
+    return Pubkey::from_str(pubkey).unwrap();
+  }
+
+  fn position_data(whirlpool: &String, position_mint: &String) -> Vec<u8> {
+    let position = Position {
+      whirlpool: to_pubkey(whirlpool),
+      position_mint: to_pubkey(position_mint),
+      liquidity: 1,
+      ..Position::default()
+    };
+    let mut data = Vec::new();
+    position.try_serialize(&mut data).unwrap();
+    return data;
+  }
+
+  fn tick_array_data(whirlpool: &String) -> Vec<u8> {
+    let mut data = vec![0u8; TICK_ARRAY_LEN];
+    data[0..8].copy_from_slice(&TickArray::DISCRIMINATOR);
+    data[TICK_ARRAY_WHIRLPOOL_OFFSET..TICK_ARRAY_WHIRLPOOL_OFFSET + 32].copy_from_slice(to_pubkey(whirlpool).as_ref());
+    return data;
+  }
+
+  fn position_bundle_data(position_bundle_mint: &String) -> Vec<u8> {
+    let position_bundle = PositionBundle {
+      position_bundle_mint: to_pubkey(position_bundle_mint),
+      position_bitmap: [0u8; 32],
+    };
+    let mut data = Vec::new();
+    position_bundle.try_serialize(&mut data).unwrap();
+    return data;
+  }
+
+  fn sorted(mut pubkeys: Vec<String>) -> Vec<String> {
+    pubkeys.sort();
+    return pubkeys;
+  }
+
+  #[test]
+  fn test_build() {
+    let whirlpool_one = unique_pubkey();
+    let whirlpool_two = unique_pubkey();
+    let position_mint = unique_pubkey();
+    let position_bundle_mint = unique_pubkey();
+
+    let position_one = unique_pubkey();
+    let position_two = unique_pubkey();
+    let tick_array = unique_pubkey();
+    let position_bundle = unique_pubkey();
+    let bundled_position_one = unique_pubkey();
+    let bundled_position_two = unique_pubkey();
+
+    let mut accounts = AccountMap::new();
+    accounts.insert(position_one.clone(), position_data(&whirlpool_one, &position_mint));
+    accounts.insert(position_two.clone(), position_data(&whirlpool_two, &unique_pubkey()));
+    accounts.insert(tick_array.clone(), tick_array_data(&whirlpool_one));
+    accounts.insert(position_bundle.clone(), position_bundle_data(&position_bundle_mint));
+    accounts.insert(bundled_position_one.clone(), position_data(&whirlpool_one, &position_bundle_mint));
+    accounts.insert(bundled_position_two.clone(), position_data(&whirlpool_two, &position_bundle_mint));
+    accounts.insert(unique_pubkey(), vec![0u8; 165]);
+
+    let index = AccountIndex::build(&accounts);
+
+    assert_eq!(index.get_positions_of(&whirlpool_one), sorted(vec![position_one.clone(), bundled_position_one.clone()]));
+    assert_eq!(index.get_positions_of(&whirlpool_two), sorted(vec![position_two.clone(), bundled_position_two.clone()]));
+    assert_eq!(index.get_tick_arrays_of(&whirlpool_one), vec![tick_array.clone()]);
+    assert!(index.get_tick_arrays_of(&whirlpool_two).is_empty());
+
+    assert_eq!(index.get_position_by_mint(&position_mint), Some(position_one.clone()));
+    assert_eq!(index.get_position_by_mint(&unique_pubkey()), None);
+    // bundled positions share the mint of the position bundle
+    assert_eq!(index.get_position_by_mint(&position_bundle_mint), None);
+
+    assert_eq!(
+      index.get_bundled_positions_of(&position_bundle),
+      sorted(vec![bundled_position_one.clone(), bundled_position_two.clone()])
+    );
+    assert!(index.get_bundled_positions_of(&position_one).is_empty());
+    assert!(index.get_bundled_positions_of(&unique_pubkey()).is_empty());
+  }
+
+  #[test]
+  fn test_update_on_write_back() {
+    let whirlpool_one = unique_pubkey();
+    let whirlpool_two = unique_pubkey();
+    let position_mint = unique_pubkey();
+    let position = unique_pubkey();
+    let tick_array = unique_pubkey();
+
+    let mut accounts = AccountMap::new();
+    accounts.insert(tick_array.clone(), tick_array_data(&whirlpool_one));
+    let mut index = AccountIndex::build(&accounts);
+
+    // position opened (tick array updated)
+    let mut pre_snapshot = AccountMap::new();
+    pre_snapshot.insert(tick_array.clone(), tick_array_data(&whirlpool_one));
+    let mut post_snapshot = pre_snapshot.clone();
+    post_snapshot.insert(position.clone(), position_data(&whirlpool_one, &position_mint));
+    update_account_map(&mut accounts, Some(&mut index), pre_snapshot, post_snapshot);
+
+    assert_eq!(index.get_positions_of(&whirlpool_one), vec![position.clone()]);
+    assert_eq!(index.get_position_by_mint(&position_mint), Some(position.clone()));
+    assert_eq!(index.get_tick_arrays_of(&whirlpool_one), vec![tick_array.clone()]);
+
+    // position closed
+    let mut pre_snapshot = AccountMap::new();
+    pre_snapshot.insert(position.clone(), accounts.get(&position).unwrap().clone());
+    update_account_map(&mut accounts, Some(&mut index), pre_snapshot, AccountMap::new());
+
+    assert!(!accounts.contains_key(&position));
+    assert!(index.get_positions_of(&whirlpool_one).is_empty());
+    assert_eq!(index.get_position_by_mint(&position_mint), None);
+
+    // reopened at the same pubkey on another whirlpool
+    let reopened_mint = unique_pubkey();
+    index.update(&position, Some(&position_data(&whirlpool_two, &reopened_mint)));
+    assert!(index.get_positions_of(&whirlpool_one).is_empty());
+    assert_eq!(index.get_positions_of(&whirlpool_two), vec![position.clone()]);
+    assert_eq!(index.get_position_by_mint(&reopened_mint), Some(position.clone()));
+
+    // closed via update(.., None)
+    index.update(&position, None);
+    assert!(index.get_positions_of(&whirlpool_two).is_empty());
+    assert_eq!(index.get_position_by_mint(&reopened_mint), None);
+
+    index.update(&tick_array, None);
+    assert!(index.get_tick_arrays_of(&whirlpool_one).is_empty());
+  }
+
+  #[test]
+  fn test_update_position_bundle() {
+    let whirlpool = unique_pubkey();
+    let position_bundle_mint = unique_pubkey();
+    let position_bundle = unique_pubkey();
+    let bundled_position = unique_pubkey();
+
+    let mut index = AccountIndex::default();
+    index.update(&position_bundle, Some(&position_bundle_data(&position_bundle_mint)));
+    index.update(&bundled_position, Some(&position_data(&whirlpool, &position_bundle_mint)));
+    assert_eq!(index.get_bundled_positions_of(&position_bundle), vec![bundled_position.clone()]);
+    assert_eq!(index.get_position_by_mint(&position_bundle_mint), None);
+
+    index.update(&bundled_position, None);
+    assert!(index.get_bundled_positions_of(&position_bundle).is_empty());
+
+    // after the bundle is deleted, its mint is no longer treated as a bundle mint
+    index.update(&position_bundle, None);
+    index.update(&bundled_position, Some(&position_data(&whirlpool, &position_bundle_mint)));
+    assert!(index.get_bundled_positions_of(&position_bundle).is_empty());
+    assert_eq!(index.get_position_by_mint(&position_bundle_mint), Some(bundled_position.clone()));
+  }
+}
@@ -0,0 +1,121 @@
+use anchor_lang::AccountDeserialize;
+use serde_json::{json, Value};
+use whirlpool_base::state::{FeeTier, Position, PositionBundle, TickArray, Whirlpool, WhirlpoolsConfig};
+
+use crate::account_kind::{classify_account, AccountKind};
+
+// render whirlpool program account as JSON.
+// u64, u128 and i128 are rendered as string (same as the transaction file), pubkey as base58.
+// None is returned if the account is not a whirlpool account or broken.
+pub fn account_to_json(data: &[u8]) -> Option<Value> {
+  let mut buf = data;
+  match classify_account(data) {
+    AccountKind::WhirlpoolsConfig => WhirlpoolsConfig::try_deserialize(&mut buf).ok().map(|config| whirlpools_config_to_json(&config)),
+    AccountKind::FeeTier => FeeTier::try_deserialize(&mut buf).ok().map(|fee_tier| fee_tier_to_json(&fee_tier)),
+    AccountKind::Whirlpool => Whirlpool::try_deserialize(&mut buf).ok().map(|whirlpool| whirlpool_to_json(&whirlpool)),
+    AccountKind::TickArray => TickArray::try_deserialize(&mut buf).ok().map(|tick_array| tick_array_to_json(&tick_array)),
+    AccountKind::Position => Position::try_deserialize(&mut buf).ok().map(|position| position_to_json(&position)),
+    AccountKind::PositionBundle => PositionBundle::try_deserialize(&mut buf).ok().map(|position_bundle| position_bundle_to_json(&position_bundle)),
+    AccountKind::Unknown => None,
+  }
+}
+
+pub fn whirlpools_config_to_json(config: &WhirlpoolsConfig) -> Value {
+  return json!({
+    "fee_authority": config.fee_authority.to_string(),
+    "collect_protocol_fees_authority": config.collect_protocol_fees_authority.to_string(),
+    "reward_emissions_super_authority": config.reward_emissions_super_authority.to_string(),
+    "default_protocol_fee_rate": config.default_protocol_fee_rate,
+  });
+}
+
+pub fn fee_tier_to_json(fee_tier: &FeeTier) -> Value {
+  return json!({
+    "whirlpools_config": fee_tier.whirlpools_config.to_string(),
+    "tick_spacing": fee_tier.tick_spacing,
+    "default_fee_rate": fee_tier.default_fee_rate,
+  });
+}
+
+pub fn whirlpool_to_json(whirlpool: &Whirlpool) -> Value {
+  let reward_infos: Vec<Value> = whirlpool.reward_infos.iter().map(|reward_info| json!({
+    "mint": reward_info.mint.to_string(),
+    "vault": reward_info.vault.to_string(),
+    "authority": reward_info.authority.to_string(),
+    "emissions_per_second_x64": reward_info.emissions_per_second_x64.to_string(),
+    "growth_global_x64": reward_info.growth_global_x64.to_string(),
+  })).collect();
+
+  return json!({
+    "whirlpools_config": whirlpool.whirlpools_config.to_string(),
+    "tick_spacing": whirlpool.tick_spacing,
+    "fee_rate": whirlpool.fee_rate,
+    "protocol_fee_rate": whirlpool.protocol_fee_rate,
+    "liquidity": whirlpool.liquidity.to_string(),
+    "sqrt_price": whirlpool.sqrt_price.to_string(),
+    "tick_current_index": whirlpool.tick_current_index,
+    "protocol_fee_owed_a": whirlpool.protocol_fee_owed_a.to_string(),
+    "protocol_fee_owed_b": whirlpool.protocol_fee_owed_b.to_string(),
+    "token_mint_a": whirlpool.token_mint_a.to_string(),
+    "token_vault_a": whirlpool.token_vault_a.to_string(),
+    "fee_growth_global_a": whirlpool.fee_growth_global_a.to_string(),
+    "token_mint_b": whirlpool.token_mint_b.to_string(),
+    "token_vault_b": whirlpool.token_vault_b.to_string(),
+    "fee_growth_global_b": whirlpool.fee_growth_global_b.to_string(),
+    "reward_last_updated_timestamp": whirlpool.reward_last_updated_timestamp.to_string(),
+    "reward_infos": reward_infos,
+  });
+}
+
+pub fn tick_array_to_json(tick_array: &TickArray) -> Value {
+  // TickArray is zero copy (packed), so fields are copied before use
+  let ticks: Vec<Value> = { tick_array.ticks }.iter().map(|tick| {
+    let reward_growths_outside: Vec<String> = { tick.reward_growths_outside }.iter().map(|growth| growth.to_string()).collect();
+    json!({
+      "initialized": { tick.initialized },
+      "liquidity_net": { tick.liquidity_net }.to_string(),
+      "liquidity_gross": { tick.liquidity_gross }.to_string(),
+      "fee_growth_outside_a": { tick.fee_growth_outside_a }.to_string(),
+      "fee_growth_outside_b": { tick.fee_growth_outside_b }.to_string(),
+      "reward_growths_outside": reward_growths_outside,
+    })
+  }).collect();
+
+  return json!({
+    "whirlpool": { tick_array.whirlpool }.to_string(),
+    "start_tick_index": { tick_array.start_tick_index },
+    "ticks": ticks,
+  });
+}
+
+pub fn position_to_json(position: &Position) -> Value {
+  let reward_infos: Vec<Value> = position.reward_infos.iter().map(|reward_info| json!({
+    "growth_inside_checkpoint": reward_info.growth_inside_checkpoint.to_string(),
+    "amount_owed": reward_info.amount_owed.to_string(),
+  })).collect();
+
+  return json!({
+    "whirlpool": position.whirlpool.to_string(),
+    "position_mint": position.position_mint.to_string(),
+    "liquidity": position.liquidity.to_string(),
+    "tick_lower_index": position.tick_lower_index,
+    "tick_upper_index": position.tick_upper_index,
+    "fee_growth_checkpoint_a": position.fee_growth_checkpoint_a.to_string(),
+    "fee_owed_a": position.fee_owed_a.to_string(),
+    "fee_growth_checkpoint_b": position.fee_growth_checkpoint_b.to_string(),
+    "fee_owed_b": position.fee_owed_b.to_string(),
+    "reward_infos": reward_infos,
+  });
+}
+
+pub fn position_bundle_to_json(position_bundle: &PositionBundle) -> Value {
+  // one boolean per bundle index (true if in use), so each opened or closed bundled position is a separate field change
+  let position_bitmap: Vec<bool> = (0..position_bundle.position_bitmap.len() * 8)
+    .map(|index| position_bundle.position_bitmap[index / 8] & (1 << (index % 8)) != 0)
+    .collect();
+
+  return json!({
+    "position_bundle_mint": position_bundle.position_bundle_mint.to_string(),
+    "position_bitmap": position_bitmap,
+  });
+}
@@ -0,0 +1,68 @@
+use anchor_lang::Discriminator;
+use serde_derive::Serialize;
+use whirlpool_base::state::{FeeTier, Position, PositionBundle, TickArray, Whirlpool, WhirlpoolsConfig};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize)]
+pub enum AccountKind {
+  WhirlpoolsConfig,
+  FeeTier,
+  Whirlpool,
+  TickArray,
+  Position,
+  PositionBundle,
+  Unknown,
+}
+
+// classify whirlpool program accounts by anchor discriminator.
+// AccountView, account diffs, the pool filter and the account index share this classifier.
+pub fn classify_account(data: &[u8]) -> AccountKind {
+  if data.len() < 8 {
+    return AccountKind::Unknown;
+  }
+
+  let discriminator = &data[0..8];
+  if discriminator == Whirlpool::DISCRIMINATOR {
+    AccountKind::Whirlpool
+  } else if discriminator == TickArray::DISCRIMINATOR {
+    AccountKind::TickArray
+  } else if discriminator == Position::DISCRIMINATOR {
+    AccountKind::Position
+  } else if discriminator == PositionBundle::DISCRIMINATOR {
+    AccountKind::PositionBundle
+  } else if discriminator == WhirlpoolsConfig::DISCRIMINATOR {
+    AccountKind::WhirlpoolsConfig
+  } else if discriminator == FeeTier::DISCRIMINATOR {
+    AccountKind::FeeTier
+  } else {
+    AccountKind::Unknown
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn account_data(discriminator: [u8; 8]) -> Vec<u8> {
+    let mut data = discriminator.to_vec();
+    data.extend_from_slice(&[0u8; 32]);
+    return data;
+  }
+
+  #[test]
+  fn test_classify_account() {
+    assert_eq!(classify_account(&account_data(WhirlpoolsConfig::DISCRIMINATOR)), AccountKind::WhirlpoolsConfig);
+    assert_eq!(classify_account(&account_data(FeeTier::DISCRIMINATOR)), AccountKind::FeeTier);
+    assert_eq!(classify_account(&account_data(Whirlpool::DISCRIMINATOR)), AccountKind::Whirlpool);
+    assert_eq!(classify_account(&account_data(TickArray::DISCRIMINATOR)), AccountKind::TickArray);
+    assert_eq!(classify_account(&account_data(Position::DISCRIMINATOR)), AccountKind::Position);
+    assert_eq!(classify_account(&account_data(PositionBundle::DISCRIMINATOR)), AccountKind::PositionBundle);
+
+    // discriminator only
+    assert_eq!(classify_account(&Whirlpool::DISCRIMINATOR), AccountKind::Whirlpool);
+
+    // other accounts (e.g. token accounts) and too short data
+    assert_eq!(classify_account(&account_data([0u8; 8])), AccountKind::Unknown);
+    assert_eq!(classify_account(&Whirlpool::DISCRIMINATOR[0..7]), AccountKind::Unknown);
+    assert_eq!(classify_account(&[]), AccountKind::Unknown);
+  }
+}
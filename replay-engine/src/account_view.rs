@@ -0,0 +1,208 @@
+use std::str::FromStr;
+
+use anchor_lang::AccountDeserialize;
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use whirlpool_base::state::{FeeTier, Position, PositionBundle, TickArray, Whirlpool, WhirlpoolsConfig};
+
+use crate::account_json::account_to_json;
+use crate::account_kind::{classify_account, AccountKind};
+use crate::errors::ErrorCode;
+use crate::types::AccountMap;
+
+// offsets of the whirlpool pubkey (right after the discriminator for Position)
+const POSITION_WHIRLPOOL_OFFSET: usize = 8;
+const TICK_ARRAY_WHIRLPOOL_OFFSET: usize = 9956;
+
+// AccountView provides typed access to the whirlpool program accounts in AccountMap.
+//
+//   let view = AccountView::new(replayer.get_accounts());
+//   for (pubkey, whirlpool) in view.whirlpools() { ... }
+//   let tick_arrays = view.tick_arrays_of(&whirlpool_pubkey);
+pub struct AccountView<'a> {
+  accounts: &'a AccountMap,
+}
+
+impl<'a> AccountView<'a> {
+  pub fn new(accounts: &'a AccountMap) -> AccountView<'a> {
+    return AccountView { accounts };
+  }
+
+  pub fn get_accounts(&self) -> &'a AccountMap {
+    return self.accounts;
+  }
+
+  pub fn get_kind(&self, pubkey: &String) -> Option<AccountKind> {
+    return self.accounts.get(pubkey).map(|data| classify_account(data));
+  }
+
+  // render the account as JSON (None if the account is not found or not a whirlpool account)
+  pub fn get_json(&self, pubkey: &String) -> Option<Value> {
+    return self.accounts.get(pubkey).and_then(|data| account_to_json(data));
+  }
+
+  pub fn get_whirlpools_config(&self, pubkey: &String) -> Result<WhirlpoolsConfig, ErrorCode> {
+    return self.get_account(pubkey);
+  }
+
+  pub fn get_fee_tier(&self, pubkey: &String) -> Result<FeeTier, ErrorCode> {
+    return self.get_account(pubkey);
+  }
+
+  pub fn get_whirlpool(&self, pubkey: &String) -> Result<Whirlpool, ErrorCode> {
+    return self.get_account(pubkey);
+  }
+
+  pub fn get_tick_array(&self, pubkey: &String) -> Result<TickArray, ErrorCode> {
+    return self.get_account(pubkey);
+  }
+
+  pub fn get_position(&self, pubkey: &String) -> Result<Position, ErrorCode> {
+    return self.get_account(pubkey);
+  }
+
+  pub fn get_position_bundle(&self, pubkey: &String) -> Result<PositionBundle, ErrorCode> {
+    return self.get_account(pubkey);
+  }
+
+  pub fn whirlpools_configs(&self) -> impl Iterator<Item = (&'a String, WhirlpoolsConfig)> + 'a {
+    return self.accounts_of(AccountKind::WhirlpoolsConfig);
+  }
+
+  pub fn fee_tiers(&self) -> impl Iterator<Item = (&'a String, FeeTier)> + 'a {
+    return self.accounts_of(AccountKind::FeeTier);
+  }
+
+  pub fn whirlpools(&self) -> impl Iterator<Item = (&'a String, Whirlpool)> + 'a {
+    return self.accounts_of(AccountKind::Whirlpool);
+  }
+
+  pub fn tick_arrays(&self) -> impl Iterator<Item = (&'a String, TickArray)> + 'a {
+    return self.accounts_of(AccountKind::TickArray);
+  }
+
+  pub fn positions(&self) -> impl Iterator<Item = (&'a String, Position)> + 'a {
+    return self.accounts_of(AccountKind::Position);
+  }
+
+  pub fn position_bundles(&self) -> impl Iterator<Item = (&'a String, PositionBundle)> + 'a {
+    return self.accounts_of(AccountKind::PositionBundle);
+  }
+
+  // tick arrays of the whirlpool (sorted by start_tick_index)
+  pub fn tick_arrays_of(&self, whirlpool: &String) -> Vec<(&'a String, TickArray)> {
+    let whirlpool = whirlpool.clone();
+    let mut tick_arrays: Vec<(&'a String, TickArray)> = self
+      .accounts_of_whirlpool(AccountKind::TickArray, TICK_ARRAY_WHIRLPOOL_OFFSET, whirlpool)
+      .collect();
+    tick_arrays.sort_by_key(|(_, tick_array)| { tick_array.start_tick_index });
+    return tick_arrays;
+  }
+
+  pub fn positions_of(&self, whirlpool: &String) -> Vec<(&'a String, Position)> {
+    return self
+      .accounts_of_whirlpool(AccountKind::Position, POSITION_WHIRLPOOL_OFFSET, whirlpool.clone())
+      .collect();
+  }
+
+  fn get_account<T: AccountDeserialize>(&self, pubkey: &String) -> Result<T, ErrorCode> {
+    let data = self
+      .accounts
+      .get(pubkey)
+      .ok_or_else(|| ErrorCode::AccountNotFound(pubkey.clone()))?;
+    return T::try_deserialize(&mut data.as_slice()).map_err(|_| ErrorCode::InvalidAccountData(pubkey.clone()));
+  }
+
+  fn accounts_of<T: AccountDeserialize + 'a>(&self, kind: AccountKind) -> impl Iterator<Item = (&'a String, T)> + 'a {
+    return self
+      .accounts
+      .iter()
+      .filter(move |(_, data)| classify_account(data) == kind)
+      .filter_map(|(pubkey, data)| T::try_deserialize(&mut data.as_slice()).ok().map(|account| (pubkey, account)));
+  }
+
+  // whirlpool pubkey is compared before deserialization to avoid deserializing all accounts
+  fn accounts_of_whirlpool<T: AccountDeserialize + 'a>(
+    &self,
+    kind: AccountKind,
+    offset: usize,
+    whirlpool: String,
+  ) -> impl Iterator<Item = (&'a String, T)> + 'a {
+    // invalid pubkey matches nothing
+    let whirlpool = Pubkey::from_str(&whirlpool).map(|pubkey| pubkey.to_bytes().to_vec()).unwrap_or_default();
+    return self
+      .accounts
+      .iter()
+      .filter(move |(_, data)| {
+        classify_account(data) == kind
+          && data.len() >= offset + 32
+          && data[offset..offset + 32] == whirlpool[..]
+      })
+      .filter_map(|(pubkey, data)| T::try_deserialize(&mut data.as_slice()).ok().map(|account| (pubkey, account)));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anchor_lang::{AccountSerialize, Discriminator};
+
+  const TICK_ARRAY_LEN: usize = 9988;
+
+  fn tick_array_data(whirlpool: &Pubkey, start_tick_index: i32) -> Vec<u8> {
+    let mut data = vec![0u8; TICK_ARRAY_LEN];
+    data[0..8].copy_from_slice(&TickArray::DISCRIMINATOR);
+    data[8..12].copy_from_slice(&start_tick_index.to_le_bytes());
+    data[TICK_ARRAY_WHIRLPOOL_OFFSET..TICK_ARRAY_WHIRLPOOL_OFFSET + 32].copy_from_slice(whirlpool.as_ref());
+    return data;
+  }
+
+  fn position_data(whirlpool: &Pubkey) -> Vec<u8> {
+    let mut data = Vec::new();
+    Position { whirlpool: *whirlpool, ..Position::default() }.try_serialize(&mut data).unwrap();
+    return data;
+  }
+
+  #[test]
+  fn test_accounts_of_whirlpool() {
+    let whirlpool = Pubkey::new_unique();
+    let other_whirlpool = Pubkey::new_unique();
+
+    let mut accounts = AccountMap::new();
+    let mut insert = |data: Vec<u8>| {
+      let pubkey = Pubkey::new_unique().to_string();
+      accounts.insert(pubkey.clone(), data);
+      return pubkey;
+    };
+    let tick_array_upper = insert(tick_array_data(&whirlpool, 5632));
+    let tick_array_lower = insert(tick_array_data(&whirlpool, -5632));
+    let tick_array_middle = insert(tick_array_data(&whirlpool, 0));
+    insert(tick_array_data(&other_whirlpool, 0));
+    let position = insert(position_data(&whirlpool));
+    insert(position_data(&other_whirlpool));
+    let unknown = insert(vec![0u8; 165]);
+
+    let view = AccountView::new(&accounts);
+
+    // sorted by start_tick_index
+    let tick_arrays: Vec<&String> = view.tick_arrays_of(&whirlpool.to_string()).into_iter().map(|(pubkey, _)| pubkey).collect();
+    assert_eq!(tick_arrays, vec![&tick_array_lower, &tick_array_middle, &tick_array_upper]);
+    assert_eq!(view.tick_arrays().count(), 4);
+
+    let positions: Vec<&String> = view.positions_of(&whirlpool.to_string()).into_iter().map(|(pubkey, _)| pubkey).collect();
+    assert_eq!(positions, vec![&position]);
+    assert_eq!(view.positions().count(), 2);
+
+    // invalid pubkey matches nothing
+    assert!(view.tick_arrays_of(&"invalid".to_string()).is_empty());
+
+    assert_eq!(view.get_kind(&position), Some(AccountKind::Position));
+    assert_eq!(view.get_kind(&unknown), Some(AccountKind::Unknown));
+    assert_eq!(view.get_kind(&Pubkey::new_unique().to_string()), None);
+    assert!(view.get_json(&unknown).is_none());
+
+    assert_eq!(view.get_position(&position).unwrap().whirlpool, whirlpool);
+    assert!(matches!(view.get_position(&tick_array_middle), Err(ErrorCode::InvalidAccountData(_))));
+    assert!(matches!(view.get_position(&Pubkey::new_unique().to_string()), Err(ErrorCode::AccountNotFound(_))));
+  }
+}
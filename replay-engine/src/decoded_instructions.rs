@@ -39,57 +39,77 @@ pub enum DecodedWhirlpoolInstruction {
   UpdateFeesAndRewards(DecodedUpdateFeesAndRewards),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum DecodedInstruction {
   ProgramDeployInstruction(DecodedProgramDeployInstruction),
   WhirlpoolInstruction(DecodedWhirlpoolInstruction),
 }
 
 pub fn from_json(ix: &String, json: &String) -> Result<DecodedInstruction, ErrorCode> {
-  fn from_str<'de, T>(json: &'de String) -> Result<T, ErrorCode>
-  where T: de::Deserialize<'de>,
-  {
-    serde_json::from_str(json).map_err(|_| ErrorCode::InvalidWhirlpoolInstructionJsonString)
+  return decode(ix, json);
+}
+
+// decode the payload already parsed (no need to serialize it into String again)
+pub fn from_json_value(ix: &String, json: &serde_json::Value) -> Result<DecodedInstruction, ErrorCode> {
+  return decode(ix, json);
+}
+
+// payload of instruction, JSON string or parsed JSON value
+trait InstructionPayload {
+  fn parse<T: de::DeserializeOwned>(&self) -> Result<T, ErrorCode>;
+}
+
+impl InstructionPayload for String {
+  fn parse<T: de::DeserializeOwned>(&self) -> Result<T, ErrorCode> {
+    serde_json::from_str(self).map_err(|_| ErrorCode::InvalidWhirlpoolInstructionJsonString)
+  }
+}
+
+impl InstructionPayload for serde_json::Value {
+  fn parse<T: de::DeserializeOwned>(&self) -> Result<T, ErrorCode> {
+    T::deserialize(self).map_err(|_| ErrorCode::InvalidWhirlpoolInstructionJsonString)
   }
+}
 
+fn decode<P: InstructionPayload>(ix: &String, json: &P) -> Result<DecodedInstruction, ErrorCode> {
   if ix.as_str() == "programDeploy" {
-    let ix = from_str::<DecodedProgramDeployInstruction>(&json)?;
+    let ix = json.parse::<DecodedProgramDeployInstruction>()?;
     return Ok(DecodedInstruction::ProgramDeployInstruction(ix));
   }
 
   let ix = match ix.as_str() {
-    "adminIncreaseLiquidity" => Ok(DecodedWhirlpoolInstruction::AdminIncreaseLiquidity(from_str(&json)?)),
-    "closeBundledPosition" => Ok(DecodedWhirlpoolInstruction::CloseBundledPosition(from_str(&json)?)),
-    "closePosition" => Ok(DecodedWhirlpoolInstruction::ClosePosition(from_str(&json)?)),
-    "collectFees" => Ok(DecodedWhirlpoolInstruction::CollectFees(from_str(&json)?)),
-    "collectProtocolFees" => Ok(DecodedWhirlpoolInstruction::CollectProtocolFees(from_str(&json)?)),
-    "collectReward" => Ok(DecodedWhirlpoolInstruction::CollectReward(from_str(&json)?)),
-    "decreaseLiquidity" => Ok(DecodedWhirlpoolInstruction::DecreaseLiquidity(from_str(&json)?)),
-    "deletePositionBundle" => Ok(DecodedWhirlpoolInstruction::DeletePositionBundle(from_str(&json)?)),
-    "increaseLiquidity" => Ok(DecodedWhirlpoolInstruction::IncreaseLiquidity(from_str(&json)?)),
-    "initializeConfig" => Ok(DecodedWhirlpoolInstruction::InitializeConfig(from_str(&json)?)),
-    "initializeFeeTier" => Ok(DecodedWhirlpoolInstruction::InitializeFeeTier(from_str(&json)?)),
-    "initializePool" => Ok(DecodedWhirlpoolInstruction::InitializePool(from_str(&json)?)),
-    "initializePositionBundle" => Ok(DecodedWhirlpoolInstruction::InitializePositionBundle(from_str(&json)?)),
-    "initializePositionBundleWithMetadata" => Ok(DecodedWhirlpoolInstruction::InitializePositionBundleWithMetadata(from_str(&json)?)),
-    "initializeReward" => Ok(DecodedWhirlpoolInstruction::InitializeReward(from_str(&json)?)),
-    "initializeTickArray" => Ok(DecodedWhirlpoolInstruction::InitializeTickArray(from_str(&json)?)),
-    "openBundledPosition" => Ok(DecodedWhirlpoolInstruction::OpenBundledPosition(from_str(&json)?)),
-    "openPosition" => Ok(DecodedWhirlpoolInstruction::OpenPosition(from_str(&json)?)),
-    "openPositionWithMetadata" => Ok(DecodedWhirlpoolInstruction::OpenPositionWithMetadata(from_str(&json)?)),
-    "setCollectProtocolFeesAuthority" => Ok(DecodedWhirlpoolInstruction::SetCollectProtocolFeesAuthority(from_str(&json)?)),
-    "setDefaultFeeRate" => Ok(DecodedWhirlpoolInstruction::SetDefaultFeeRate(from_str(&json)?)),
-    "setDefaultProtocolFeeRate" => Ok(DecodedWhirlpoolInstruction::SetDefaultProtocolFeeRate(from_str(&json)?)),
-    "setFeeAuthority" => Ok(DecodedWhirlpoolInstruction::SetFeeAuthority(from_str(&json)?)),
-    "setFeeRate" => Ok(DecodedWhirlpoolInstruction::SetFeeRate(from_str(&json)?)),
-    "setProtocolFeeRate" => Ok(DecodedWhirlpoolInstruction::SetProtocolFeeRate(from_str(&json)?)),
-    "setRewardAuthority" => Ok(DecodedWhirlpoolInstruction::SetRewardAuthority(from_str(&json)?)),
-    "setRewardAuthorityBySuperAuthority" => Ok(DecodedWhirlpoolInstruction::SetRewardAuthorityBySuperAuthority(from_str(&json)?)),
-    "setRewardEmissions" => Ok(DecodedWhirlpoolInstruction::SetRewardEmissions(from_str(&json)?)),
-    "setRewardEmissionsSuperAuthority" => Ok(DecodedWhirlpoolInstruction::SetRewardEmissionsSuperAuthority(from_str(&json)?)),
-    "swap" => Ok(DecodedWhirlpoolInstruction::Swap(from_str(&json)?)),
-    "twoHopSwap" => Ok(DecodedWhirlpoolInstruction::TwoHopSwap(from_str(&json)?)),
-    "updateFeesAndRewards" => Ok(DecodedWhirlpoolInstruction::UpdateFeesAndRewards(from_str(&json)?)),
+    "adminIncreaseLiquidity" => Ok(DecodedWhirlpoolInstruction::AdminIncreaseLiquidity(json.parse()?)),
+    "closeBundledPosition" => Ok(DecodedWhirlpoolInstruction::CloseBundledPosition(json.parse()?)),
+    "closePosition" => Ok(DecodedWhirlpoolInstruction::ClosePosition(json.parse()?)),
+    "collectFees" => Ok(DecodedWhirlpoolInstruction::CollectFees(json.parse()?)),
+    "collectProtocolFees" => Ok(DecodedWhirlpoolInstruction::CollectProtocolFees(json.parse()?)),
+    "collectReward" => Ok(DecodedWhirlpoolInstruction::CollectReward(json.parse()?)),
+    "decreaseLiquidity" => Ok(DecodedWhirlpoolInstruction::DecreaseLiquidity(json.parse()?)),
+    "deletePositionBundle" => Ok(DecodedWhirlpoolInstruction::DeletePositionBundle(json.parse()?)),
+    "increaseLiquidity" => Ok(DecodedWhirlpoolInstruction::IncreaseLiquidity(json.parse()?)),
+    "initializeConfig" => Ok(DecodedWhirlpoolInstruction::InitializeConfig(json.parse()?)),
+    "initializeFeeTier" => Ok(DecodedWhirlpoolInstruction::InitializeFeeTier(json.parse()?)),
+    "initializePool" => Ok(DecodedWhirlpoolInstruction::InitializePool(json.parse()?)),
+    "initializePositionBundle" => Ok(DecodedWhirlpoolInstruction::InitializePositionBundle(json.parse()?)),
+    "initializePositionBundleWithMetadata" => Ok(DecodedWhirlpoolInstruction::InitializePositionBundleWithMetadata(json.parse()?)),
+    "initializeReward" => Ok(DecodedWhirlpoolInstruction::InitializeReward(json.parse()?)),
+    "initializeTickArray" => Ok(DecodedWhirlpoolInstruction::InitializeTickArray(json.parse()?)),
+    "openBundledPosition" => Ok(DecodedWhirlpoolInstruction::OpenBundledPosition(json.parse()?)),
+    "openPosition" => Ok(DecodedWhirlpoolInstruction::OpenPosition(json.parse()?)),
+    "openPositionWithMetadata" => Ok(DecodedWhirlpoolInstruction::OpenPositionWithMetadata(json.parse()?)),
+    "setCollectProtocolFeesAuthority" => Ok(DecodedWhirlpoolInstruction::SetCollectProtocolFeesAuthority(json.parse()?)),
+    "setDefaultFeeRate" => Ok(DecodedWhirlpoolInstruction::SetDefaultFeeRate(json.parse()?)),
+    "setDefaultProtocolFeeRate" => Ok(DecodedWhirlpoolInstruction::SetDefaultProtocolFeeRate(json.parse()?)),
+    "setFeeAuthority" => Ok(DecodedWhirlpoolInstruction::SetFeeAuthority(json.parse()?)),
+    "setFeeRate" => Ok(DecodedWhirlpoolInstruction::SetFeeRate(json.parse()?)),
+    "setProtocolFeeRate" => Ok(DecodedWhirlpoolInstruction::SetProtocolFeeRate(json.parse()?)),
+    "setRewardAuthority" => Ok(DecodedWhirlpoolInstruction::SetRewardAuthority(json.parse()?)),
+    "setRewardAuthorityBySuperAuthority" => Ok(DecodedWhirlpoolInstruction::SetRewardAuthorityBySuperAuthority(json.parse()?)),
+    "setRewardEmissions" => Ok(DecodedWhirlpoolInstruction::SetRewardEmissions(json.parse()?)),
+    "setRewardEmissionsSuperAuthority" => Ok(DecodedWhirlpoolInstruction::SetRewardEmissionsSuperAuthority(json.parse()?)),
+    "swap" => Ok(DecodedWhirlpoolInstruction::Swap(json.parse()?)),
+    "twoHopSwap" => Ok(DecodedWhirlpoolInstruction::TwoHopSwap(json.parse()?)),
+    "updateFeesAndRewards" => Ok(DecodedWhirlpoolInstruction::UpdateFeesAndRewards(json.parse()?)),
     _ => Err(ErrorCode::UnknownWhirlpoolInstruction(ix.to_string())),
   };
 
@@ -603,6 +623,14 @@ where
     }
 }
 
+// u64 to string
+pub fn serialize_u64<S>(n: &u64, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&n.to_string())
+}
+
 // string to u128
 pub fn deserialize_u128<'de, D>(deserializer: D) -> Result<u128, D::Error>
 where
@@ -635,3 +663,29 @@ where
     let base64: String = BASE64_STANDARD.encode(data);
     serializer.serialize_str(&base64)
 }
+
+// base64 string or null to Option<Vec<u8>>
+pub fn deserialize_option_base64<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    let base64: Option<String> = de::Deserialize::deserialize(deserializer)?;
+    match base64 {
+        Some(base64) => match BASE64_STANDARD.decode(base64).ok() {
+            Some(data) => Ok(Some(data)),
+            None => Err(de::Error::custom("expected base64 string")),
+        },
+        None => Ok(None),
+    }
+}
+
+// Option<Vec<u8>> to base64 string or null
+pub fn serialize_option_base64<S>(data: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match data {
+        Some(data) => serializer.serialize_str(&BASE64_STANDARD.encode(data)),
+        None => serializer.serialize_none(),
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::replay_engine::ReplayFailureReport;
+
 #[derive(Error, Debug)]
 pub enum ErrorCode {
   #[error("invalid whirlpool instruction json string")]
@@ -8,4 +10,16 @@ pub enum ErrorCode {
   #[error("unknown whirlpool instruction detected: {0}")]
   UnknownWhirlpoolInstruction(String),
 
+  #[error("invalid pubkey string: {0}")]
+  InvalidPubkey(String),
+
+  #[error("account not found: {0}")]
+  AccountNotFound(String),
+
+  #[error("invalid account data: {0}")]
+  InvalidAccountData(String),
+
+  #[error("replayed instruction failed: {}", .0.status)]
+  ReplayedInstructionFailed(Box<ReplayFailureReport>),
+
 }
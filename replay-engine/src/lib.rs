@@ -1,6 +1,13 @@
+pub mod account_diff;
+pub mod account_index;
+pub mod account_json;
+pub mod account_kind;
+pub mod account_view;
 pub mod errors;
 pub mod types;
 pub mod decoded_instructions;
+pub mod pool_filter;
+pub mod program_versions;
 pub mod replay_engine;
 pub mod replay_environment;
 pub mod replay_instruction;
@@ -8,4 +15,4 @@ pub mod replay_instruction;
 mod replay_instructions;
 mod util;
 mod programs;
-mod pubkeys;
+pub mod pubkeys;
@@ -0,0 +1,448 @@
+use std::collections::HashSet;
+
+use anchor_lang::AccountDeserialize;
+use whirlpool_base::state::{Position, TickArray};
+
+use crate::account_kind::{classify_account, AccountKind};
+use crate::decoded_instructions::{DecodedSwap, DecodedTwoHopSwap, DecodedWhirlpoolInstruction};
+use crate::types::AccountMap;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FilteredInstruction {
+  // the instruction touches nothing in the selected pools
+  Skip,
+  Replay,
+  // the instruction should be replayed as the given instruction
+  // (TwoHopSwap with only one selected pool is narrowed to a Swap on that pool)
+  ReplayAs(DecodedWhirlpoolInstruction),
+  // the instruction is not replayed, but the position bundle should be updated to the given data
+  // (OpenBundledPosition and CloseBundledPosition on unselected pools flip a bit of the bundle bitmap)
+  UpdatePositionBundle { position_bundle: String, data: Vec<u8> },
+}
+
+// offset of position_bitmap: [u8; 32] (discriminator, position_bundle_mint)
+const POSITION_BUNDLE_POSITION_BITMAP_OFFSET: usize = 40;
+const POSITION_BUNDLE_POSITION_BITMAP_LEN: usize = 32;
+
+// PoolFilter keeps only the selected whirlpools and their dependent accounts.
+//
+// kept accounts:
+// - selected whirlpools, their tick arrays and positions (including bundled positions)
+// - all WhirlpoolsConfig, FeeTier and PositionBundle accounts (they are shared and few)
+//
+// positions and tick arrays created later are picked up because their instructions are replayed.
+// a position bundle may have positions on unselected pools, so only the bitmap update of
+// OpenBundledPosition and CloseBundledPosition on those pools is applied to keep the bundle consistent.
+pub struct PoolFilter {
+  whirlpools: HashSet<String>,
+}
+
+impl PoolFilter {
+  pub fn new(whirlpools: Vec<String>) -> PoolFilter {
+    return PoolFilter {
+      whirlpools: whirlpools.into_iter().collect(),
+    };
+  }
+
+  pub fn get_whirlpools(&self) -> &HashSet<String> {
+    return &self.whirlpools;
+  }
+
+  pub fn is_selected_whirlpool(&self, whirlpool: &String) -> bool {
+    return self.whirlpools.contains(whirlpool);
+  }
+
+  pub fn prune_accounts(&self, accounts: &mut AccountMap) {
+    accounts.retain(|pubkey, data| match classify_account(data) {
+      AccountKind::WhirlpoolsConfig | AccountKind::FeeTier | AccountKind::PositionBundle => true,
+      AccountKind::Whirlpool => self.whirlpools.contains(pubkey),
+      AccountKind::TickArray => match TickArray::try_deserialize(&mut data.as_slice()) {
+        Ok(tick_array) => self.whirlpools.contains(&{ tick_array.whirlpool }.to_string()),
+        Err(_) => false,
+      },
+      AccountKind::Position => match Position::try_deserialize(&mut data.as_slice()) {
+        Ok(position) => self.whirlpools.contains(&position.whirlpool.to_string()),
+        Err(_) => false,
+      },
+      AccountKind::Unknown => false,
+    });
+  }
+
+  // accounts should be pruned by this filter
+  pub fn filter_instruction(&self, ix: &DecodedWhirlpoolInstruction, accounts: &AccountMap) -> FilteredInstruction {
+    let selected = match ix {
+      // config, fee tier and position bundle are always kept
+      DecodedWhirlpoolInstruction::InitializeConfig(_) => true,
+      DecodedWhirlpoolInstruction::InitializeFeeTier(_) => true,
+      DecodedWhirlpoolInstruction::SetCollectProtocolFeesAuthority(_) => true,
+      DecodedWhirlpoolInstruction::SetDefaultFeeRate(_) => true,
+      DecodedWhirlpoolInstruction::SetDefaultProtocolFeeRate(_) => true,
+      DecodedWhirlpoolInstruction::SetFeeAuthority(_) => true,
+      DecodedWhirlpoolInstruction::SetRewardEmissionsSuperAuthority(_) => true,
+      DecodedWhirlpoolInstruction::InitializePositionBundle(_) => true,
+      DecodedWhirlpoolInstruction::InitializePositionBundleWithMetadata(_) => true,
+      DecodedWhirlpoolInstruction::DeletePositionBundle(_) => true,
+      // positions of unselected pools have been pruned
+      DecodedWhirlpoolInstruction::ClosePosition(ix) => accounts.contains_key(&ix.key_position),
+      DecodedWhirlpoolInstruction::CloseBundledPosition(ix) => {
+        if !accounts.contains_key(&ix.key_bundled_position) {
+          return update_position_bundle_bitmap(&ix.key_position_bundle, ix.data_bundle_index, false, accounts);
+        }
+        true
+      }
+      DecodedWhirlpoolInstruction::OpenBundledPosition(ix) => {
+        if !self.is_selected_whirlpool(&ix.key_whirlpool) {
+          return update_position_bundle_bitmap(&ix.key_position_bundle, ix.data_bundle_index, true, accounts);
+        }
+        true
+      }
+      // pool specific
+      DecodedWhirlpoolInstruction::AdminIncreaseLiquidity(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::CollectFees(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::CollectProtocolFees(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::CollectReward(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::DecreaseLiquidity(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::IncreaseLiquidity(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::InitializePool(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::InitializeReward(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::InitializeTickArray(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::OpenPosition(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::OpenPositionWithMetadata(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::SetFeeRate(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::SetProtocolFeeRate(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::SetRewardAuthority(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::SetRewardAuthorityBySuperAuthority(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::SetRewardEmissions(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::Swap(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::UpdateFeesAndRewards(ix) => self.is_selected_whirlpool(&ix.key_whirlpool),
+      DecodedWhirlpoolInstruction::TwoHopSwap(ix) => {
+        let one = self.is_selected_whirlpool(&ix.key_whirlpool_one);
+        let two = self.is_selected_whirlpool(&ix.key_whirlpool_two);
+        match (one, two) {
+          (true, true) => true,
+          (true, false) => return FilteredInstruction::ReplayAs(DecodedWhirlpoolInstruction::Swap(narrow_two_hop_swap_to_one(ix))),
+          (false, true) => return FilteredInstruction::ReplayAs(DecodedWhirlpoolInstruction::Swap(narrow_two_hop_swap_to_two(ix))),
+          (false, false) => false,
+        }
+      }
+    };
+
+    return if selected { FilteredInstruction::Replay } else { FilteredInstruction::Skip };
+  }
+}
+
+// set (open) or clear (close) the bit of bundle_index as the program does.
+// the instruction is skipped if the position bundle is not found.
+fn update_position_bundle_bitmap(position_bundle: &String, bundle_index: u16, open: bool, accounts: &AccountMap) -> FilteredInstruction {
+  let data = match accounts.get(position_bundle) {
+    Some(data) if data.len() >= POSITION_BUNDLE_POSITION_BITMAP_OFFSET + POSITION_BUNDLE_POSITION_BITMAP_LEN => data,
+    _ => return FilteredInstruction::Skip,
+  };
+  let byte_index = bundle_index as usize / 8;
+  if byte_index >= POSITION_BUNDLE_POSITION_BITMAP_LEN {
+    return FilteredInstruction::Skip;
+  }
+
+  let mut data = data.clone();
+  let mask = 1u8 << (bundle_index % 8);
+  if open {
+    data[POSITION_BUNDLE_POSITION_BITMAP_OFFSET + byte_index] |= mask;
+  } else {
+    data[POSITION_BUNDLE_POSITION_BITMAP_OFFSET + byte_index] &= !mask;
+  }
+  return FilteredInstruction::UpdatePositionBundle {
+    position_bundle: position_bundle.clone(),
+    data,
+  };
+}
+
+// TwoHopSwap is equivalent to two swaps with the intermediate amount.
+// exact input: one is executed with the specified amount, two with the output of one.
+// exact output: two is executed with the specified amount, one with the input of two.
+fn narrow_two_hop_swap_to_one(ix: &DecodedTwoHopSwap) -> DecodedSwap {
+  let amount = if ix.data_amount_specified_is_input { ix.data_amount } else { ix.transfer_amount_1 };
+  return DecodedSwap {
+    data_amount: amount,
+    data_other_amount_threshold: if ix.data_amount_specified_is_input { 0 } else { u64::MAX },
+    data_sqrt_price_limit: ix.data_sqrt_price_limit_one,
+    data_amount_specified_is_input: ix.data_amount_specified_is_input,
+    data_a_to_b: ix.data_a_to_b_one,
+    key_token_program: ix.key_token_program.clone(),
+    key_token_authority: ix.key_token_authority.clone(),
+    key_whirlpool: ix.key_whirlpool_one.clone(),
+    key_token_owner_account_a: ix.key_token_owner_account_one_a.clone(),
+    key_vault_a: ix.key_vault_one_a.clone(),
+    key_token_owner_account_b: ix.key_token_owner_account_one_b.clone(),
+    key_vault_b: ix.key_vault_one_b.clone(),
+    key_tick_array_0: ix.key_tick_array_one_0.clone(),
+    key_tick_array_1: ix.key_tick_array_one_1.clone(),
+    key_tick_array_2: ix.key_tick_array_one_2.clone(),
+    key_oracle: ix.key_oracle_one.clone(),
+    transfer_amount_0: ix.transfer_amount_0,
+    transfer_amount_1: ix.transfer_amount_1,
+  };
+}
+
+fn narrow_two_hop_swap_to_two(ix: &DecodedTwoHopSwap) -> DecodedSwap {
+  let amount = if ix.data_amount_specified_is_input { ix.transfer_amount_2 } else { ix.data_amount };
+  return DecodedSwap {
+    data_amount: amount,
+    data_other_amount_threshold: if ix.data_amount_specified_is_input { 0 } else { u64::MAX },
+    data_sqrt_price_limit: ix.data_sqrt_price_limit_two,
+    data_amount_specified_is_input: ix.data_amount_specified_is_input,
+    data_a_to_b: ix.data_a_to_b_two,
+    key_token_program: ix.key_token_program.clone(),
+    key_token_authority: ix.key_token_authority.clone(),
+    key_whirlpool: ix.key_whirlpool_two.clone(),
+    key_token_owner_account_a: ix.key_token_owner_account_two_a.clone(),
+    key_vault_a: ix.key_vault_two_a.clone(),
+    key_token_owner_account_b: ix.key_token_owner_account_two_b.clone(),
+    key_vault_b: ix.key_vault_two_b.clone(),
+    key_tick_array_0: ix.key_tick_array_two_0.clone(),
+    key_tick_array_1: ix.key_tick_array_two_1.clone(),
+    key_tick_array_2: ix.key_tick_array_two_2.clone(),
+    key_oracle: ix.key_oracle_two.clone(),
+    transfer_amount_0: ix.transfer_amount_2,
+    transfer_amount_1: ix.transfer_amount_3,
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anchor_lang::{AccountSerialize, Discriminator};
+  use solana_sdk::pubkey::Pubkey;
+  use whirlpool_base::state::{PositionBundle, Whirlpool};
+
+  use crate::decoded_instructions::{DecodedCloseBundledPosition, DecodedClosePosition, DecodedOpenBundledPosition};
+
+  const TICK_ARRAY_LEN: usize = 9988;
+  const TICK_ARRAY_WHIRLPOOL_OFFSET: usize = 9956;
+
+  fn serialize<T: AccountSerialize>(account: &T) -> Vec<u8> {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    return data;
+  }
+
+  fn tick_array_data(whirlpool: &Pubkey) -> Vec<u8> {
+    let mut data = vec![0u8; TICK_ARRAY_LEN];
+    data[0..8].copy_from_slice(&TickArray::DISCRIMINATOR);
+    data[TICK_ARRAY_WHIRLPOOL_OFFSET..TICK_ARRAY_WHIRLPOOL_OFFSET + 32].copy_from_slice(whirlpool.as_ref());
+    return data;
+  }
+
+  fn position_data(whirlpool: &Pubkey) -> Vec<u8> {
+    return serialize(&Position { whirlpool: *whirlpool, ..Position::default() });
+  }
+
+  fn two_hop_swap(whirlpool_one: &Pubkey, whirlpool_two: &Pubkey, amount_specified_is_input: bool) -> DecodedTwoHopSwap {
+    return DecodedTwoHopSwap {
+      data_amount: 1000,
+      data_other_amount_threshold: 1,
+      data_amount_specified_is_input: amount_specified_is_input,
+      data_a_to_b_one: true,
+      data_a_to_b_two: false,
+      data_sqrt_price_limit_one: 11,
+      data_sqrt_price_limit_two: 22,
+      key_token_program: "token_program".to_string(),
+      key_token_authority: "token_authority".to_string(),
+      key_whirlpool_one: whirlpool_one.to_string(),
+      key_whirlpool_two: whirlpool_two.to_string(),
+      key_token_owner_account_one_a: "owner_one_a".to_string(),
+      key_vault_one_a: "vault_one_a".to_string(),
+      key_token_owner_account_one_b: "owner_one_b".to_string(),
+      key_vault_one_b: "vault_one_b".to_string(),
+      key_token_owner_account_two_a: "owner_two_a".to_string(),
+      key_vault_two_a: "vault_two_a".to_string(),
+      key_token_owner_account_two_b: "owner_two_b".to_string(),
+      key_vault_two_b: "vault_two_b".to_string(),
+      key_tick_array_one_0: "tick_array_one_0".to_string(),
+      key_tick_array_one_1: "tick_array_one_1".to_string(),
+      key_tick_array_one_2: "tick_array_one_2".to_string(),
+      key_tick_array_two_0: "tick_array_two_0".to_string(),
+      key_tick_array_two_1: "tick_array_two_1".to_string(),
+      key_tick_array_two_2: "tick_array_two_2".to_string(),
+      key_oracle_one: "oracle_one".to_string(),
+      key_oracle_two: "oracle_two".to_string(),
+      // one: 1000 (in) -> 900 (out), two: 900 (in) -> 800 (out)
+      transfer_amount_0: 1000,
+      transfer_amount_1: 900,
+      transfer_amount_2: 900,
+      transfer_amount_3: 800,
+    };
+  }
+
+  #[test]
+  fn test_prune_accounts() {
+    let selected = Pubkey::new_unique();
+    let unselected = Pubkey::new_unique();
+    let filter = PoolFilter::new(vec![selected.to_string()]);
+
+    let mut accounts = AccountMap::new();
+    accounts.insert(selected.to_string(), serialize(&Whirlpool::default()));
+    accounts.insert(unselected.to_string(), serialize(&Whirlpool::default()));
+    accounts.insert("selected_tick_array".to_string(), tick_array_data(&selected));
+    accounts.insert("unselected_tick_array".to_string(), tick_array_data(&unselected));
+    accounts.insert("selected_position".to_string(), position_data(&selected));
+    accounts.insert("unselected_position".to_string(), position_data(&unselected));
+    accounts.insert("position_bundle".to_string(), serialize(&PositionBundle::default()));
+    accounts.insert("unknown".to_string(), vec![0u8; 16]);
+
+    filter.prune_accounts(&mut accounts);
+
+    let mut kept: Vec<String> = accounts.keys().cloned().collect();
+    kept.sort();
+    let mut expected = vec![
+      selected.to_string(),
+      "position_bundle".to_string(),
+      "selected_position".to_string(),
+      "selected_tick_array".to_string(),
+    ];
+    expected.sort();
+    assert_eq!(kept, expected);
+  }
+
+  #[test]
+  fn test_filter_instruction_skips_unselected_pools() {
+    let selected = Pubkey::new_unique();
+    let unselected = Pubkey::new_unique();
+    let filter = PoolFilter::new(vec![selected.to_string()]);
+    let mut accounts = AccountMap::new();
+    accounts.insert("selected_position".to_string(), position_data(&selected));
+
+    let swap = |whirlpool: &Pubkey| {
+      DecodedWhirlpoolInstruction::Swap(narrow_two_hop_swap_to_one(&two_hop_swap(whirlpool, whirlpool, true)))
+    };
+    assert_eq!(filter.filter_instruction(&swap(&selected), &accounts), FilteredInstruction::Replay);
+    assert_eq!(filter.filter_instruction(&swap(&unselected), &accounts), FilteredInstruction::Skip);
+
+    // the position of the unselected pool has been pruned
+    let close_position = |position: &str| {
+      DecodedWhirlpoolInstruction::ClosePosition(DecodedClosePosition {
+        key_position_authority: "authority".to_string(),
+        key_receiver: "receiver".to_string(),
+        key_position: position.to_string(),
+        key_position_mint: "position_mint".to_string(),
+        key_position_token_account: "position_token_account".to_string(),
+        key_token_program: "token_program".to_string(),
+      })
+    };
+    assert_eq!(filter.filter_instruction(&close_position("selected_position"), &accounts), FilteredInstruction::Replay);
+    assert_eq!(filter.filter_instruction(&close_position("unselected_position"), &accounts), FilteredInstruction::Skip);
+
+    let two_hop = DecodedWhirlpoolInstruction::TwoHopSwap(two_hop_swap(&unselected, &unselected, true));
+    assert_eq!(filter.filter_instruction(&two_hop, &accounts), FilteredInstruction::Skip);
+    let two_hop = DecodedWhirlpoolInstruction::TwoHopSwap(two_hop_swap(&selected, &selected, true));
+    assert_eq!(filter.filter_instruction(&two_hop, &accounts), FilteredInstruction::Replay);
+  }
+
+  #[test]
+  fn test_filter_instruction_narrows_two_hop_swap() {
+    let selected = Pubkey::new_unique();
+    let unselected = Pubkey::new_unique();
+    let filter = PoolFilter::new(vec![selected.to_string()]);
+    let accounts = AccountMap::new();
+
+    let narrowed = |ix: DecodedTwoHopSwap| match filter.filter_instruction(&DecodedWhirlpoolInstruction::TwoHopSwap(ix), &accounts) {
+      FilteredInstruction::ReplayAs(DecodedWhirlpoolInstruction::Swap(swap)) => swap,
+      filtered => panic!("not narrowed: {:?}", filtered),
+    };
+
+    // exact input: one is swapped with the specified amount
+    let swap = narrowed(two_hop_swap(&selected, &unselected, true));
+    assert_eq!(swap.key_whirlpool, selected.to_string());
+    assert_eq!(swap.data_amount, 1000);
+    assert!(swap.data_amount_specified_is_input);
+    assert_eq!(swap.data_other_amount_threshold, 0);
+    assert!(swap.data_a_to_b);
+    assert_eq!(swap.data_sqrt_price_limit, 11);
+    assert_eq!(swap.key_vault_a, "vault_one_a");
+    assert_eq!(swap.key_tick_array_0, "tick_array_one_0");
+    assert_eq!((swap.transfer_amount_0, swap.transfer_amount_1), (1000, 900));
+
+    // exact input: two is swapped with the output of one
+    let swap = narrowed(two_hop_swap(&unselected, &selected, true));
+    assert_eq!(swap.key_whirlpool, selected.to_string());
+    assert_eq!(swap.data_amount, 900);
+    assert!(!swap.data_a_to_b);
+    assert_eq!(swap.data_sqrt_price_limit, 22);
+    assert_eq!(swap.key_vault_b, "vault_two_b");
+    assert_eq!(swap.key_oracle, "oracle_two");
+    assert_eq!((swap.transfer_amount_0, swap.transfer_amount_1), (900, 800));
+
+    // exact output: one is swapped with the input of two
+    let swap = narrowed(two_hop_swap(&selected, &unselected, false));
+    assert_eq!(swap.data_amount, 900);
+    assert!(!swap.data_amount_specified_is_input);
+    assert_eq!(swap.data_other_amount_threshold, u64::MAX);
+
+    // exact output: two is swapped with the specified amount
+    let swap = narrowed(two_hop_swap(&unselected, &selected, false));
+    assert_eq!(swap.data_amount, 1000);
+    assert_eq!(swap.data_other_amount_threshold, u64::MAX);
+  }
+
+  #[test]
+  fn test_filter_instruction_updates_position_bundle_bitmap() {
+    let selected = Pubkey::new_unique();
+    let unselected = Pubkey::new_unique();
+    let filter = PoolFilter::new(vec![selected.to_string()]);
+    let mut accounts = AccountMap::new();
+    accounts.insert("position_bundle".to_string(), serialize(&PositionBundle::default()));
+
+    let open = |whirlpool: &Pubkey| {
+      DecodedWhirlpoolInstruction::OpenBundledPosition(DecodedOpenBundledPosition {
+        data_bundle_index: 9,
+        data_tick_lower_index: -128,
+        data_tick_upper_index: 128,
+        key_bundled_position: "bundled_position".to_string(),
+        key_position_bundle: "position_bundle".to_string(),
+        key_position_bundle_token_account: "position_bundle_token_account".to_string(),
+        key_position_bundle_authority: "authority".to_string(),
+        key_whirlpool: whirlpool.to_string(),
+        key_funder: "funder".to_string(),
+        key_system_program: "system_program".to_string(),
+        key_rent: "rent".to_string(),
+      })
+    };
+    let close = DecodedWhirlpoolInstruction::CloseBundledPosition(DecodedCloseBundledPosition {
+      data_bundle_index: 9,
+      key_bundled_position: "bundled_position".to_string(),
+      key_position_bundle: "position_bundle".to_string(),
+      key_position_bundle_token_account: "position_bundle_token_account".to_string(),
+      key_position_bundle_authority: "authority".to_string(),
+      key_receiver: "receiver".to_string(),
+    });
+
+    assert_eq!(filter.filter_instruction(&open(&selected), &accounts), FilteredInstruction::Replay);
+
+    // bundle index 9: bit 1 of the second byte
+    let opened = match filter.filter_instruction(&open(&unselected), &accounts) {
+      FilteredInstruction::UpdatePositionBundle { position_bundle, data } => {
+        assert_eq!(position_bundle, "position_bundle");
+        data
+      }
+      filtered => panic!("bitmap is not updated: {:?}", filtered),
+    };
+    let position_bundle = PositionBundle::try_deserialize(&mut opened.as_slice()).unwrap();
+    let mut expected_bitmap = [0u8; 32];
+    expected_bitmap[1] = 0b10;
+    assert_eq!(position_bundle.position_bitmap, expected_bitmap);
+    accounts.insert("position_bundle".to_string(), opened);
+
+    // the bundled position on the unselected pool doesn't exist
+    let closed = match filter.filter_instruction(&close, &accounts) {
+      FilteredInstruction::UpdatePositionBundle { data, .. } => data,
+      filtered => panic!("bitmap is not updated: {:?}", filtered),
+    };
+    assert_eq!(closed, serialize(&PositionBundle::default()));
+
+    // the bundled position exists (the pool is selected), so the instruction is replayed
+    accounts.insert("bundled_position".to_string(), position_data(&selected));
+    assert_eq!(filter.filter_instruction(&close, &accounts), FilteredInstruction::Replay);
+
+    accounts.remove("position_bundle");
+    assert_eq!(filter.filter_instruction(&open(&unselected), &accounts), FilteredInstruction::Skip);
+  }
+}
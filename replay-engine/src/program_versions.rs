@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use solana_sdk::hash::hash;
+
+// sha256 (base58) of the Whirlpool program data deployed on mainnet.
+// version is the deployment (yyyymmdd-slot) that matches the files in programs/whirlpool.
+// 20221221-168120167 rolled back to 20221204-164780893, so they share the same hash.
+const KNOWN_PROGRAM_VERSIONS: [(&str, &str); 11] = [
+  ("5XQz7ZRdgb1humojikgX67WMjzLJNNdTP26Dp3NuWXmF", "20220309-124152351"),
+  ("3WgwaX7VvDwnKJaTr4o5oaovMjSW22Drmf6RkByMGqZr", "20220316-125145977"),
+  ("E7He2cVcHQyUNxNgVWMEgbz2JHo3hCZgCEct3KkkBHjy", "20220323-126272055"),
+  ("6gzj6HD3iHT98FXoNpAX4LzMNcF1EbLTtFwV1CR3M7h1", "20220331-127483851"),
+  ("ALriEcnquFjYoF3wxKz3hQgMKzDEGsc3sNMMsPhTTTbA", "20220407-128594124"),
+  ("3ZXpvN6QnogfpPB5EnCsDGFhLB5y8DGkhZpvqjDLeBha", "20221204-164780893"),
+  ("9xkSDXcXaL9V1PmXNT2eVDwnx8Em5jd2wRSfc3AuMKob", "20221221-168116472"),
+  ("nRYUaMzBrfKNmRbSf6R2qDD33FkdQwoHUmhNLni5i5B", "20230127-174717190"),
+  ("HZENbwNnFSd3n3MS8L8tLB8P1mQ7Q5Ats5gq6AJuVAwu", "20230217-178191491"),
+  ("EoATGkE4VpZiTR5RQyMRLe3SCTecW792STa6qtNULWeq", "20230419-189278833"),
+  ("GX53kM5EGnVgCZ7eE7vqzK49ZxRuhY9VyFoV6meLhxAr", "20230901-214946722"),
+];
+
+pub fn get_program_data_hash(program_data: &[u8]) -> String {
+  return hash(program_data).to_string();
+}
+
+pub struct ProgramVersionRegistry {
+  versions: HashMap<String, String>,
+}
+
+impl Default for ProgramVersionRegistry {
+  fn default() -> ProgramVersionRegistry {
+    let versions = KNOWN_PROGRAM_VERSIONS
+      .iter()
+      .map(|(program_data_hash, version)| (program_data_hash.to_string(), version.to_string()))
+      .collect();
+    return ProgramVersionRegistry { versions };
+  }
+}
+
+impl ProgramVersionRegistry {
+  // registry with the known mainnet deployments
+  pub fn new() -> ProgramVersionRegistry {
+    return ProgramVersionRegistry::default();
+  }
+
+  // add (or overwrite) a version for deployments not known yet
+  pub fn register(&mut self, program_data_hash: String, version: String) {
+    self.versions.insert(program_data_hash, version);
+  }
+
+  pub fn get_version(&self, program_data_hash: &str) -> Option<&String> {
+    return self.versions.get(program_data_hash);
+  }
+}
@@ -7,13 +7,51 @@ use crate::programs;
 use crate::errors::ErrorCode;
 use crate::util;
 use crate::pubkeys;
+use crate::program_versions;
+use crate::pool_filter::PoolFilter;
+use crate::account_index::AccountIndex;
+
+use solana_sdk::transaction::TransactionError;
+
+// reports keep the pre snapshot, so only the first ones are kept (the first failure is usually the cause of the divergence)
+pub const DEFAULT_MAX_FAILURE_REPORTS: usize = 100;
+
+// How ReplayEngine reacts when a replayed instruction fails.
+// All recorded transactions succeeded on mainnet, so a failure means that the replay has diverged.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FailurePolicy {
+  // panic with the failure report
+  Panic,
+  // return ErrorCode::ReplayedInstructionFailed with the failure report
+  // (the failed instruction is not written back, and WhirlpoolReplayer skips it when replay is resumed)
+  ReturnError,
+  // keep the failure report (up to max_failure_reports) and continue replaying (the failed instruction is not written back)
+  RecordAndContinue,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayFailureReport {
+  pub slot: Slot,
+  pub instruction: DecodedWhirlpoolInstruction,
+  pub status: TransactionError,
+  pub log_messages: Vec<String>,
+  pub pre_snapshot: AccountMap,
+}
 
 pub struct ReplayEngine {
   slot: Slot,
   program_data: Vec<u8>,
+  program_data_hash: String,
   accounts: AccountMap,
+  // None until enabled (building the index needs a scan of all accounts)
+  account_index: Option<AccountIndex>,
   environment: ReplayEnvironment,
   replay_execution_counter: u64,
+  failure_policy: FailurePolicy,
+  failure_reports: Vec<ReplayFailureReport>,
+  max_failure_reports: usize,
+  // all failures including the ones without report
+  failure_count: u64,
 }
 
 impl ReplayEngine {
@@ -26,13 +64,20 @@ impl ReplayEngine {
   ) -> ReplayEngine {
     let slot = Slot { slot, block_height, block_time };
     let environment = ReplayEngine::build_environment(block_time, &program_data);
+    let program_data_hash = program_versions::get_program_data_hash(&program_data);
     let replay_execution_counter = 0u64;
     return ReplayEngine {
       slot,
       program_data,
+      program_data_hash,
       accounts,
+      account_index: None,
       environment,
       replay_execution_counter,
+      failure_policy: FailurePolicy::RecordAndContinue,
+      failure_reports: vec![],
+      max_failure_reports: DEFAULT_MAX_FAILURE_REPORTS,
+      failure_count: 0,
     };
   }
 
@@ -74,10 +119,67 @@ impl ReplayEngine {
     return &self.program_data;
   }
 
+  pub fn get_program_data_hash(&self) -> &String {
+    return &self.program_data_hash;
+  }
+
   pub fn get_accounts(&self) -> &AccountMap {
     return &self.accounts;
   }
 
+  pub fn enable_account_index(&mut self) {
+    if self.account_index.is_none() {
+      self.account_index = Some(AccountIndex::build(&self.accounts));
+    }
+  }
+
+  pub fn get_account_index(&self) -> Option<&AccountIndex> {
+    return self.account_index.as_ref();
+  }
+
+  pub fn get_failure_policy(&self) -> FailurePolicy {
+    return self.failure_policy;
+  }
+
+  pub fn set_failure_policy(&mut self, failure_policy: FailurePolicy) {
+    self.failure_policy = failure_policy;
+  }
+
+  // the first max_failure_reports failures (RecordAndContinue)
+  pub fn get_failure_reports(&self) -> &Vec<ReplayFailureReport> {
+    return &self.failure_reports;
+  }
+
+  // taking the reports makes room for the following failures (the failure count is kept)
+  pub fn take_failure_reports(&mut self) -> Vec<ReplayFailureReport> {
+    return std::mem::take(&mut self.failure_reports);
+  }
+
+  pub fn get_failure_count(&self) -> u64 {
+    return self.failure_count;
+  }
+
+  pub fn set_max_failure_reports(&mut self, max_failure_reports: usize) {
+    self.max_failure_reports = max_failure_reports;
+    self.failure_reports.truncate(max_failure_reports);
+  }
+
+  // drop accounts unrelated to the pools selected by the filter
+  pub fn prune_accounts(&mut self, pool_filter: &PoolFilter) {
+    pool_filter.prune_accounts(&mut self.accounts);
+    if self.account_index.is_some() {
+      self.account_index = Some(AccountIndex::build(&self.accounts));
+    }
+  }
+
+  // write an account without replaying an instruction (e.g. FilteredInstruction::UpdatePositionBundle)
+  pub fn update_account(&mut self, pubkey: &String, data: Vec<u8>) {
+    if let Some(account_index) = self.account_index.as_mut() {
+      account_index.update(pubkey, Some(&data));
+    }
+    self.accounts.insert(pubkey.clone(), data);
+  }
+
   pub fn update_slot(&mut self, slot: u64, block_height: u64, block_time: i64) {
     self.slot = Slot { slot, block_height, block_time };
     self.environment.set_sysvar_clock_unix_timestamp(self.slot.block_time);
@@ -85,6 +187,7 @@ impl ReplayEngine {
 
   pub fn update_program_data(&mut self, program_data: Vec<u8>) {
     self.program_data = program_data;
+    self.program_data_hash = program_versions::get_program_data_hash(&self.program_data);
     self.environment = ReplayEngine::build_environment(self.slot.block_time, &self.program_data);
     self.replay_execution_counter = 0u64;
   }
@@ -110,13 +213,41 @@ impl ReplayEngine {
         // https://docs.rs/solana-transaction-status/latest/src/solana_transaction_status/lib.rs.html#812-817
         let meta = result.transaction_status.tx_with_meta.get_status_meta().unwrap();
 
-        if meta.status.is_ok() {
-          // write back
-          util::update_account_map(
-            &mut self.accounts,
-            result.snapshot.pre_snapshot.clone(),
-            result.snapshot.post_snapshot.clone()
-          );
+        match meta.status {
+          Ok(_) => {
+            // write back
+            util::update_account_map(
+              &mut self.accounts,
+              self.account_index.as_mut(),
+              result.snapshot.pre_snapshot.clone(),
+              result.snapshot.post_snapshot.clone()
+            );
+          },
+          Err(status) => {
+            // no write back
+            let report = ReplayFailureReport {
+              slot: self.slot,
+              instruction: ix.clone(),
+              status,
+              log_messages: meta.log_messages.unwrap_or_default(),
+              pre_snapshot: result.snapshot.pre_snapshot.clone(),
+            };
+
+            match self.failure_policy {
+              FailurePolicy::Panic => {
+                panic!("replayed instruction failed: {:?}", report);
+              },
+              FailurePolicy::ReturnError => {
+                return Err(ErrorCode::ReplayedInstructionFailed(Box::new(report)));
+              },
+              FailurePolicy::RecordAndContinue => {
+                self.failure_count += 1;
+                if self.failure_reports.len() < self.max_failure_reports {
+                  self.failure_reports.push(report);
+                }
+              },
+            }
+          },
         }
 
         return Ok(result);
@@ -127,3 +258,115 @@ impl ReplayEngine {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anchor_lang::AccountSerialize;
+  use solana_sdk::pubkey::Pubkey;
+  use std::str::FromStr;
+  use whirlpool_base::state::{Position, Whirlpool};
+
+  use crate::decoded_instructions::DecodedOpenPosition;
+
+  const WHIRLPOOL_PROGRAM: &[u8] = include_bytes!("programs/whirlpool/whirlpool-20230419-189278833.so");
+
+  fn unique_pubkey() -> String {
+    return Pubkey::new_unique().to_string();
+  }
+
+  fn position_data(whirlpool: &String) -> Vec<u8> {
+    let position = Position {
+      whirlpool: Pubkey::from_str(whirlpool).unwrap(),
+      position_mint: Pubkey::new_unique(),
+      ..Position::default()
+    };
+    let mut data = Vec::new();
+    position.try_serialize(&mut data).unwrap();
+    return data;
+  }
+
+  #[test]
+  fn test_failed_open_position_is_recorded() {
+    let whirlpool_pubkey = unique_pubkey();
+    let whirlpool = Whirlpool { tick_spacing: 64, ..Whirlpool::default() };
+    let mut whirlpool_data = Vec::new();
+    whirlpool.try_serialize(&mut whirlpool_data).unwrap();
+
+    let mut accounts = AccountMap::new();
+    accounts.insert(whirlpool_pubkey.clone(), whirlpool_data);
+    let mut engine = ReplayEngine::new(189278833, 172000000, 1681891200, WHIRLPOOL_PROGRAM.to_vec(), accounts);
+    assert_eq!(engine.get_failure_policy(), FailurePolicy::RecordAndContinue);
+
+    // tick indexes are not multiples of tick_spacing, so the instruction fails and the position is not created
+    let position_pubkey = unique_pubkey();
+    let ix = DecodedWhirlpoolInstruction::OpenPosition(DecodedOpenPosition {
+      data_tick_lower_index: 1,
+      data_tick_upper_index: 2,
+      key_funder: unique_pubkey(),
+      key_owner: unique_pubkey(),
+      key_position: position_pubkey.clone(),
+      key_position_mint: unique_pubkey(),
+      key_position_token_account: unique_pubkey(),
+      key_whirlpool: whirlpool_pubkey.clone(),
+      key_token_program: pubkeys::SPL_TOKEN_PROGRAM_ID.to_string(),
+      key_system_program: pubkeys::SYSTEM_PROGRAM_ID.to_string(),
+      key_rent: solana_sdk::sysvar::rent::id().to_string(),
+      key_associated_token_program: pubkeys::SPL_ASSOCIATED_TOKEN_ACCOUNT_PROGRAM_ID.to_string(),
+    });
+
+    let result = engine.replay_instruction(&ix).unwrap();
+    assert!(!result.snapshot.post_snapshot.contains_key(&position_pubkey));
+
+    let reports = engine.get_failure_reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].instruction, ix);
+    assert!(reports[0].pre_snapshot.contains_key(&whirlpool_pubkey));
+
+    // nothing is written back
+    assert!(!engine.get_accounts().contains_key(&position_pubkey));
+
+    // failures beyond the limit are only counted
+    engine.set_max_failure_reports(2);
+    engine.replay_instruction(&ix).unwrap();
+    engine.replay_instruction(&ix).unwrap();
+    assert_eq!(engine.get_failure_reports().len(), 2);
+    assert_eq!(engine.get_failure_count(), 3);
+
+    assert_eq!(engine.take_failure_reports().len(), 2);
+    engine.replay_instruction(&ix).unwrap();
+    assert_eq!(engine.get_failure_reports().len(), 1);
+    assert_eq!(engine.get_failure_count(), 4);
+  }
+
+  #[test]
+  fn test_prune_accounts_rebuilds_account_index() {
+    let selected_whirlpool = unique_pubkey();
+    let unselected_whirlpool = unique_pubkey();
+    let selected_position = unique_pubkey();
+    let unselected_position = unique_pubkey();
+
+    let mut whirlpool_data = Vec::new();
+    Whirlpool::default().try_serialize(&mut whirlpool_data).unwrap();
+    let mut accounts = AccountMap::new();
+    accounts.insert(selected_whirlpool.clone(), whirlpool_data.clone());
+    accounts.insert(unselected_whirlpool.clone(), whirlpool_data);
+    accounts.insert(selected_position.clone(), position_data(&selected_whirlpool));
+    accounts.insert(unselected_position.clone(), position_data(&unselected_whirlpool));
+
+    let mut engine = ReplayEngine::new(189278833, 172000000, 1681891200, WHIRLPOOL_PROGRAM.to_vec(), accounts);
+    engine.enable_account_index();
+    assert_eq!(engine.get_account_index().unwrap().get_positions_of(&unselected_whirlpool), vec![unselected_position.clone()]);
+
+    engine.prune_accounts(&PoolFilter::new(vec![selected_whirlpool.clone()]));
+    assert!(!engine.get_accounts().contains_key(&unselected_position));
+    let index = engine.get_account_index().unwrap();
+    assert!(index.get_positions_of(&unselected_whirlpool).is_empty());
+    assert_eq!(index.get_positions_of(&selected_whirlpool), vec![selected_position.clone()]);
+
+    // the rebuilt index is still maintained
+    let new_position = unique_pubkey();
+    engine.update_account(&new_position, position_data(&selected_whirlpool));
+    assert_eq!(engine.get_account_index().unwrap().get_positions_of(&selected_whirlpool).len(), 2);
+  }
+}
@@ -1,10 +1,8 @@
 use solana_transaction_status::ConfirmedTransactionWithStatusMeta;
 
-use std::str::FromStr;
-
 use crate::errors::ErrorCode;
 use crate::{decoded_instructions::DecodedWhirlpoolInstruction, types::AccountMap};
-use solana_sdk::{pubkey::Pubkey, transaction::Transaction, instruction::{Instruction, AccountMeta}, message::Message};
+use solana_sdk::{transaction::Transaction, instruction::{Instruction, AccountMeta}, message::Message};
 use solana_sdk::signer::Signer;
 
 use anchor_lang::{InstructionData, ToAccountMetas};
@@ -14,6 +12,7 @@ use crate::replay_environment;
 use crate::replay_environment::ReplayEnvironment;
 
 use crate::pubkeys;
+use crate::util;
 
 #[derive(Clone)]
 pub struct WritableAccountSnapshot {
@@ -40,41 +39,41 @@ pub fn replay_whirlpool_instruction(
 ) -> Result<ReplayInstructionResult, ErrorCode> {
   match instruction {
     // major instructions
-    DecodedWhirlpoolInstruction::Swap(decoded) => Ok(replay_instructions::swap::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::TwoHopSwap(decoded) => Ok(replay_instructions::two_hop_swap::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::UpdateFeesAndRewards(decoded) => Ok(replay_instructions::update_fees_and_rewards::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::CollectFees(decoded) => Ok(replay_instructions::collect_fees::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::CollectReward(decoded) => Ok(replay_instructions::collect_reward::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::CollectProtocolFees(decoded) => Ok(replay_instructions::collect_protocol_fees::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::IncreaseLiquidity(decoded) => Ok(replay_instructions::increase_liquidity::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::DecreaseLiquidity(decoded) => Ok(replay_instructions::decrease_liquidity::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::OpenPosition(decoded) => Ok(replay_instructions::open_position::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::OpenPositionWithMetadata(decoded) => Ok(replay_instructions::open_position_with_metadata::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::ClosePosition(decoded) => Ok(replay_instructions::close_position::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::OpenBundledPosition(decoded) => Ok(replay_instructions::open_bundled_position::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::CloseBundledPosition(decoded) => Ok(replay_instructions::close_bundled_position::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::InitializeTickArray(decoded) => Ok(replay_instructions::initialize_tick_array::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
+    DecodedWhirlpoolInstruction::Swap(decoded) => replay_instructions::swap::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::TwoHopSwap(decoded) => replay_instructions::two_hop_swap::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::UpdateFeesAndRewards(decoded) => replay_instructions::update_fees_and_rewards::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::CollectFees(decoded) => replay_instructions::collect_fees::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::CollectReward(decoded) => replay_instructions::collect_reward::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::CollectProtocolFees(decoded) => replay_instructions::collect_protocol_fees::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::IncreaseLiquidity(decoded) => replay_instructions::increase_liquidity::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::DecreaseLiquidity(decoded) => replay_instructions::decrease_liquidity::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::OpenPosition(decoded) => replay_instructions::open_position::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::OpenPositionWithMetadata(decoded) => replay_instructions::open_position_with_metadata::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::ClosePosition(decoded) => replay_instructions::close_position::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::OpenBundledPosition(decoded) => replay_instructions::open_bundled_position::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::CloseBundledPosition(decoded) => replay_instructions::close_bundled_position::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::InitializeTickArray(decoded) => replay_instructions::initialize_tick_array::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
     // minor instructions
-    DecodedWhirlpoolInstruction::InitializePool(decoded) => Ok(replay_instructions::initialize_pool::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::InitializeReward(decoded) => Ok(replay_instructions::initialize_reward::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::SetRewardEmissions(decoded) => Ok(replay_instructions::set_reward_emissions::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::InitializePositionBundle(decoded) => Ok(replay_instructions::initialize_position_bundle::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::InitializePositionBundleWithMetadata(decoded) => Ok(replay_instructions::initialize_position_bundle_with_metadata::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::DeletePositionBundle(decoded) => Ok(replay_instructions::delete_position_bundle::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
+    DecodedWhirlpoolInstruction::InitializePool(decoded) => replay_instructions::initialize_pool::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::InitializeReward(decoded) => replay_instructions::initialize_reward::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::SetRewardEmissions(decoded) => replay_instructions::set_reward_emissions::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::InitializePositionBundle(decoded) => replay_instructions::initialize_position_bundle::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::InitializePositionBundleWithMetadata(decoded) => replay_instructions::initialize_position_bundle_with_metadata::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::DeletePositionBundle(decoded) => replay_instructions::delete_position_bundle::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
     // very rare instructions
-    DecodedWhirlpoolInstruction::InitializeFeeTier(decoded) => Ok(replay_instructions::initialize_fee_tier::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::SetFeeRate(decoded) => Ok(replay_instructions::set_fee_rate::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::InitializeConfig(decoded) => Ok(replay_instructions::initialize_config::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::SetCollectProtocolFeesAuthority(decoded) => Ok(replay_instructions::set_collect_protocol_fees_authority::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::SetDefaultFeeRate(decoded) => Ok(replay_instructions::set_default_fee_rate::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::SetDefaultProtocolFeeRate(decoded) => Ok(replay_instructions::set_default_protocol_fee_rate::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::SetFeeAuthority(decoded) => Ok(replay_instructions::set_fee_authority::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::SetProtocolFeeRate(decoded) => Ok(replay_instructions::set_protocol_fee_rate::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::SetRewardAuthority(decoded) => Ok(replay_instructions::set_reward_authority::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::SetRewardAuthorityBySuperAuthority(decoded) => Ok(replay_instructions::set_reward_authority_by_super_authority::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
-    DecodedWhirlpoolInstruction::SetRewardEmissionsSuperAuthority(decoded) => Ok(replay_instructions::set_reward_emissions_super_authority::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
+    DecodedWhirlpoolInstruction::InitializeFeeTier(decoded) => replay_instructions::initialize_fee_tier::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::SetFeeRate(decoded) => replay_instructions::set_fee_rate::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::InitializeConfig(decoded) => replay_instructions::initialize_config::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::SetCollectProtocolFeesAuthority(decoded) => replay_instructions::set_collect_protocol_fees_authority::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::SetDefaultFeeRate(decoded) => replay_instructions::set_default_fee_rate::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::SetDefaultProtocolFeeRate(decoded) => replay_instructions::set_default_protocol_fee_rate::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::SetFeeAuthority(decoded) => replay_instructions::set_fee_authority::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::SetProtocolFeeRate(decoded) => replay_instructions::set_protocol_fee_rate::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::SetRewardAuthority(decoded) => replay_instructions::set_reward_authority::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::SetRewardAuthorityBySuperAuthority(decoded) => replay_instructions::set_reward_authority_by_super_authority::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
+    DecodedWhirlpoolInstruction::SetRewardEmissionsSuperAuthority(decoded) => replay_instructions::set_reward_emissions_super_authority::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
     // temporary patch instructions
-    DecodedWhirlpoolInstruction::AdminIncreaseLiquidity(decoded) => Ok(replay_instructions::admin_increase_liquidity::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map })),
+    DecodedWhirlpoolInstruction::AdminIncreaseLiquidity(decoded) => replay_instructions::admin_increase_liquidity::replay(ReplayInstructionParams { replayer, decoded_instruction: &decoded, account_map }),
     //_ => {
     //  Err(ErrorCode::UnknownWhirlpoolInstruction("not implemented yet".to_string()))
     //}
@@ -84,24 +83,26 @@ pub fn replay_whirlpool_instruction(
 
 
 impl ReplayEnvironment {
-  pub fn set_whirlpool_account(&mut self, pubkey: &String, account_map: &AccountMap) {
+  pub fn set_whirlpool_account(&mut self, pubkey: &String, account_map: &AccountMap) -> Result<(), ErrorCode> {
     self.set_account_with_data(
-      Pubkey::from_str(pubkey).unwrap(),
+      util::pubkey(pubkey)?,
       pubkeys::ORCA_WHIRLPOOL_PROGRAM_ID,
-      account_map.get(pubkey).unwrap(),
+      util::get_account_data(pubkey, account_map)?,
       false
     );
+    Ok(())
   }
 
   pub fn set_funder_account(
     &mut self,
     pubkey: &String,
-  ) {
+  ) -> Result<(), ErrorCode> {
     self.set_account_with_lamports(
-      solana_program::pubkey::Pubkey::from_str(pubkey.as_str()).unwrap(),
+      util::pubkey(pubkey)?,
       pubkeys::SYSTEM_PROGRAM_ID,
       10_000_000_000, // 10 SOL
     );
+    Ok(())
   }
   
   pub fn build_whirlpool_replay_transaction(
@@ -137,15 +138,39 @@ impl ReplayEnvironment {
   pub fn take_snapshot(
     &self,
     pubkeys: &[&String],
-  ) -> AccountMap {
+  ) -> Result<AccountMap, ErrorCode> {
     let mut snapshot = AccountMap::new();
   
     for pubkey_string in pubkeys {
-      let account = self.get_account(Pubkey::from_str(pubkey_string).unwrap()).unwrap();
+      let account = self.get_account(util::pubkey(pubkey_string)?)
+        .ok_or_else(|| ErrorCode::AccountNotFound((*pubkey_string).clone()))?;
       snapshot.insert((*pubkey_string).clone(), account.data);
     }
   
-    return snapshot;
+    return Ok(snapshot);
+  }
+
+  // snapshot after the transaction.
+  // if the transaction has failed, the accounts to be created don't exist, so missing accounts are skipped
+  // (nothing is written back, and the failure is handled by FailurePolicy).
+  pub fn take_post_snapshot(
+    &self,
+    replay_result: &ConfirmedTransactionWithStatusMeta,
+    pubkeys: &[&String],
+  ) -> Result<AccountMap, ErrorCode> {
+    let failed = replay_result.tx_with_meta.get_status_meta().map_or(false, |meta| meta.status.is_err());
+    if !failed {
+      return self.take_snapshot(pubkeys);
+    }
+
+    let mut snapshot = AccountMap::new();
+    for pubkey_string in pubkeys {
+      if let Some(account) = self.get_account(util::pubkey(pubkey_string)?) {
+        snapshot.insert((*pubkey_string).clone(), account.data);
+      }
+    }
+
+    return Ok(snapshot);
   }
 
 }
\ No newline at end of file
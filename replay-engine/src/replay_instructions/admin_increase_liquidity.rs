@@ -1,4 +1,5 @@
 use crate::decoded_instructions;
+use crate::errors::ErrorCode;
 use crate::replay_instruction::{ReplayInstructionParams, ReplayInstructionResult, WritableAccountSnapshot};
 use crate::util::pubkey; // abbr
 
@@ -30,15 +31,15 @@ impl ToAccountMetas for AdminIncreaseLiquidityInstructionAccounts {
   }
 }
 
-pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedAdminIncreaseLiquidity>) -> ReplayInstructionResult {
+pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedAdminIncreaseLiquidity>) -> Result<ReplayInstructionResult, ErrorCode> {
   let replayer = req.replayer;
   let ix = req.decoded_instruction;
   let account_map = req.account_map;
 
   // whirlpools_config
-  replayer.set_whirlpool_account(&ix.key_whirlpools_config, account_map);
+  replayer.set_whirlpool_account(&ix.key_whirlpools_config, account_map)?;
   // whirlpool
-  replayer.set_whirlpool_account(&ix.key_whirlpool, account_map);
+  replayer.set_whirlpool_account(&ix.key_whirlpool, account_map)?;
   // authority
     
   let tx = replayer.build_whirlpool_replay_transaction(
@@ -46,29 +47,29 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedAdminInc
       liquidity: ix.data_liquidity,
     },
     AdminIncreaseLiquidityInstructionAccounts {
-      whirlpools_config: pubkey(&ix.key_whirlpools_config),
-      whirlpool: pubkey(&ix.key_whirlpool),
-      authority: pubkey(&ix.key_authority),
+      whirlpools_config: pubkey(&ix.key_whirlpools_config)?,
+      whirlpool: pubkey(&ix.key_whirlpool)?,
+      authority: pubkey(&ix.key_authority)?,
     },
   );
 
   let pre_snapshot = replayer.take_snapshot(&[
     &ix.key_whirlpools_config,
     &ix.key_whirlpool,
-  ]);
+  ])?;
   
   let replay_result = replayer.execute_transaction(tx);
 
-  let post_snapshot = replayer.take_snapshot(&[
+  let post_snapshot = replayer.take_post_snapshot(&replay_result, &[
     &ix.key_whirlpools_config,
     &ix.key_whirlpool,
-  ]);
+  ])?;
 
-  return ReplayInstructionResult {
+  return Ok(ReplayInstructionResult {
     transaction_status: replay_result,
     snapshot: WritableAccountSnapshot {
       pre_snapshot,
       post_snapshot,
     }
-  }
+  });
 }
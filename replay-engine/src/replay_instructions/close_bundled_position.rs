@@ -2,27 +2,28 @@ use whirlpool_base::accounts as whirlpool_ix_accounts;
 use whirlpool_base::instruction as whirlpool_ix_args;
 
 use crate::decoded_instructions;
+use crate::errors::ErrorCode;
 use crate::replay_instruction::{ReplayInstructionParams, ReplayInstructionResult, WritableAccountSnapshot};
 use crate::util;
 use crate::util::pubkey; // abbr
 
-pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedCloseBundledPosition>) -> ReplayInstructionResult {
+pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedCloseBundledPosition>) -> Result<ReplayInstructionResult, ErrorCode> {
   let replayer = req.replayer;
   let ix = req.decoded_instruction;
   let account_map = req.account_map;
 
-  let position_bundle_data = util::get_position_bundle_data(&ix.key_position_bundle, account_map);
+  let position_bundle_data = util::get_position_bundle_data(&ix.key_position_bundle, account_map)?;
   let position_bundle_mint = position_bundle_data.position_bundle_mint;
 
   // bundled_position
-  replayer.set_whirlpool_account(&ix.key_bundled_position, account_map);
+  replayer.set_whirlpool_account(&ix.key_bundled_position, account_map)?;
   // position_bundle
-  replayer.set_whirlpool_account(&ix.key_position_bundle, account_map);
+  replayer.set_whirlpool_account(&ix.key_position_bundle, account_map)?;
   // position_bundle_token_account
   replayer.set_token_account(
-    pubkey(&ix.key_position_bundle_token_account),
+    pubkey(&ix.key_position_bundle_token_account)?,
     position_bundle_mint,
-    pubkey(&ix.key_position_bundle_authority),
+    pubkey(&ix.key_position_bundle_authority)?,
     1u64
   );
   // position_bundle_authority
@@ -33,31 +34,31 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedCloseBun
       bundle_index: ix.data_bundle_index,
     },
     whirlpool_ix_accounts::CloseBundledPosition {
-      bundled_position: pubkey(&ix.key_bundled_position),
-      position_bundle: pubkey(&ix.key_position_bundle),
-      position_bundle_token_account: pubkey(&ix.key_position_bundle_token_account),
-      position_bundle_authority: pubkey(&ix.key_position_bundle_authority),
-      receiver: pubkey(&ix.key_receiver),
+      bundled_position: pubkey(&ix.key_bundled_position)?,
+      position_bundle: pubkey(&ix.key_position_bundle)?,
+      position_bundle_token_account: pubkey(&ix.key_position_bundle_token_account)?,
+      position_bundle_authority: pubkey(&ix.key_position_bundle_authority)?,
+      receiver: pubkey(&ix.key_receiver)?,
     },
   );
 
   let pre_snapshot = replayer.take_snapshot(&[
     &ix.key_bundled_position,
     &ix.key_position_bundle,
-  ]);
+  ])?;
   
   let replay_result = replayer.execute_transaction(tx);
 
-  let post_snapshot = replayer.take_snapshot(&[
+  let post_snapshot = replayer.take_post_snapshot(&replay_result, &[
     // closed
     &ix.key_position_bundle,
-  ]);
+  ])?;
 
-  return ReplayInstructionResult {
+  return Ok(ReplayInstructionResult {
     transaction_status: replay_result,
     snapshot: WritableAccountSnapshot {
       pre_snapshot,
       post_snapshot,
     }
-  }
+  });
 }
@@ -2,26 +2,27 @@ use whirlpool_base::accounts as whirlpool_ix_accounts;
 use whirlpool_base::instruction as whirlpool_ix_args;
 
 use crate::decoded_instructions;
+use crate::errors::ErrorCode;
 use crate::replay_instruction::{ReplayInstructionParams, ReplayInstructionResult, WritableAccountSnapshot};
 use crate::util;
 use crate::util::pubkey; // abbr
 
-pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedClosePosition>) -> ReplayInstructionResult {
+pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedClosePosition>) -> Result<ReplayInstructionResult, ErrorCode> {
   let replayer = req.replayer;
   let ix = req.decoded_instruction;
   let account_map = req.account_map;
 
-  let position_data = util::get_position_data(&ix.key_position, account_map);
+  let position_data = util::get_position_data(&ix.key_position, account_map)?;
   let position_mint = position_data.position_mint;
 
   // position_authority
   // receiver
   // position
-  replayer.set_whirlpool_account(&ix.key_position, account_map);
+  replayer.set_whirlpool_account(&ix.key_position, account_map)?;
   // position_mint
   //builder.add_token_mint(
   replayer.set_token_mint(
-    pubkey(&ix.key_position_mint),
+    pubkey(&ix.key_position_mint)?,
     None,
     1u64,
     0u8,
@@ -29,9 +30,9 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedClosePos
   );
   // position_token_amount
   replayer.set_token_account(
-    pubkey(&ix.key_position_token_account),
+    pubkey(&ix.key_position_token_account)?,
     position_mint,
-    pubkey(&ix.key_position_authority),
+    pubkey(&ix.key_position_authority)?,
     1u64
   );
   // token_program
@@ -40,30 +41,30 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedClosePos
     whirlpool_ix_args::ClosePosition {
     },
     whirlpool_ix_accounts::ClosePosition {
-      position_authority: pubkey(&ix.key_position_authority),
-      receiver: pubkey(&ix.key_receiver),
-      position: pubkey(&ix.key_position),
-      position_mint: pubkey(&ix.key_position_mint),
-      position_token_account: pubkey(&ix.key_position_token_account),
-      token_program: pubkey(&ix.key_token_program),
+      position_authority: pubkey(&ix.key_position_authority)?,
+      receiver: pubkey(&ix.key_receiver)?,
+      position: pubkey(&ix.key_position)?,
+      position_mint: pubkey(&ix.key_position_mint)?,
+      position_token_account: pubkey(&ix.key_position_token_account)?,
+      token_program: pubkey(&ix.key_token_program)?,
     },
   );
 
   let pre_snapshot = replayer.take_snapshot(&[
     &ix.key_position,
-  ]);
+  ])?;
   
   let replay_result = replayer.execute_transaction(tx);
 
-  let post_snapshot = replayer.take_snapshot(&[
+  let post_snapshot = replayer.take_post_snapshot(&replay_result, &[
     // closed
-  ]);
+  ])?;
 
-  return ReplayInstructionResult {
+  return Ok(ReplayInstructionResult {
     transaction_status: replay_result,
     snapshot: WritableAccountSnapshot {
       pre_snapshot,
       post_snapshot,
     }
-  }
+  });
 }
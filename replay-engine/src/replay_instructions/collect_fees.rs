@@ -2,63 +2,64 @@ use whirlpool_base::accounts as whirlpool_ix_accounts;
 use whirlpool_base::instruction as whirlpool_ix_args;
 
 use crate::decoded_instructions;
+use crate::errors::ErrorCode;
 use crate::replay_instruction::{ReplayInstructionParams, ReplayInstructionResult, WritableAccountSnapshot};
 use crate::util;
 use crate::util::pubkey; // abbr
 
-pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedCollectFees>) -> ReplayInstructionResult {
+pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedCollectFees>) -> Result<ReplayInstructionResult, ErrorCode> {
   let replayer = req.replayer;
   let ix = req.decoded_instruction;
   let account_map = req.account_map;
 
-  let whirlpool_data = util::get_whirlpool_data(&ix.key_whirlpool, account_map);
+  let whirlpool_data = util::get_whirlpool_data(&ix.key_whirlpool, account_map)?;
   let mint_a = whirlpool_data.token_mint_a;
   let mint_b = whirlpool_data.token_mint_b;
 
-  let position_data = util::get_position_data(&ix.key_position, account_map);
+  let position_data = util::get_position_data(&ix.key_position, account_map)?;
   let position_mint = position_data.position_mint;
 
   let amount_a = ix.transfer_amount_0;
   let amount_b = ix.transfer_amount_1;
 
   // whirlpool
-  replayer.set_whirlpool_account(&ix.key_whirlpool, account_map);
+  replayer.set_whirlpool_account(&ix.key_whirlpool, account_map)?;
   // position_authority
   // position
-  replayer.set_whirlpool_account(&ix.key_position, account_map);
+  replayer.set_whirlpool_account(&ix.key_position, account_map)?;
   // position_token_amount
   replayer.set_token_account(
-    pubkey(&ix.key_position_token_account),
+    pubkey(&ix.key_position_token_account)?,
     position_mint,
-    pubkey(&ix.key_position_authority),
+    pubkey(&ix.key_position_authority)?,
     1u64
   );
   // token_owner_account_a
   replayer.set_token_account(
-    pubkey(&ix.key_token_owner_account_a),
+    pubkey(&ix.key_token_owner_account_a)?,
     mint_a,
-    pubkey(&ix.key_position_authority),
+    pubkey(&ix.key_position_authority)?,
     0u64
   );
   // token_vault_a
   replayer.set_token_account(
-    pubkey(&ix.key_token_vault_a),
+    pubkey(&ix.key_token_vault_a)?,
     mint_a,
-    pubkey(&ix.key_whirlpool),
+    pubkey(&ix.key_whirlpool)?,
     amount_a
   );
   // token_owner_account_b
   replayer.set_token_account(
-    pubkey(&ix.key_token_owner_account_b),
+    pubkey(&ix.key_token_owner_account_b)?,
     mint_b,
-    pubkey(&ix.key_position_authority),
+    pubkey(&ix.key_position_authority)?,
     0u64
   );
   // token_vault_b
   replayer.set_token_account(
-    pubkey(&ix.key_token_vault_b),
+    pubkey(&ix.key_token_vault_b)?,
     mint_b,
-    pubkey(&ix.key_whirlpool),
+    pubkey(&ix.key_whirlpool)?,
     amount_b
   );
   // token_program
@@ -67,35 +68,35 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedCollectF
     whirlpool_ix_args::CollectFees {
     },
     whirlpool_ix_accounts::CollectFees {
-      whirlpool: pubkey(&ix.key_whirlpool),
-      position_authority: pubkey(&ix.key_position_authority),
-      position: pubkey(&ix.key_position),
-      position_token_account: pubkey(&ix.key_position_token_account),
-      token_owner_account_a: pubkey(&ix.key_token_owner_account_a),
-      token_vault_a: pubkey(&ix.key_token_vault_a),
-      token_owner_account_b: pubkey(&ix.key_token_owner_account_b),
-      token_vault_b: pubkey(&ix.key_token_vault_b),
-      token_program: pubkey(&ix.key_token_program),
+      whirlpool: pubkey(&ix.key_whirlpool)?,
+      position_authority: pubkey(&ix.key_position_authority)?,
+      position: pubkey(&ix.key_position)?,
+      position_token_account: pubkey(&ix.key_position_token_account)?,
+      token_owner_account_a: pubkey(&ix.key_token_owner_account_a)?,
+      token_vault_a: pubkey(&ix.key_token_vault_a)?,
+      token_owner_account_b: pubkey(&ix.key_token_owner_account_b)?,
+      token_vault_b: pubkey(&ix.key_token_vault_b)?,
+      token_program: pubkey(&ix.key_token_program)?,
     },
   );
 
   let pre_snapshot = replayer.take_snapshot(&[
     &ix.key_whirlpool,
     &ix.key_position,
-  ]);
+  ])?;
   
   let replay_result = replayer.execute_transaction(tx);
 
-  let post_snapshot = replayer.take_snapshot(&[
+  let post_snapshot = replayer.take_post_snapshot(&replay_result, &[
     &ix.key_whirlpool,
     &ix.key_position,
-  ]);
+  ])?;
 
-  return ReplayInstructionResult {
+  return Ok(ReplayInstructionResult {
     transaction_status: replay_result,
     snapshot: WritableAccountSnapshot {
       pre_snapshot,
       post_snapshot,
     }
-  }
+  });
 }
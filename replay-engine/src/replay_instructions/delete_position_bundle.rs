@@ -2,23 +2,24 @@ use whirlpool_base::accounts as whirlpool_ix_accounts;
 use whirlpool_base::instruction as whirlpool_ix_args;
 
 use crate::decoded_instructions;
+use crate::errors::ErrorCode;
 use crate::replay_instruction::{ReplayInstructionParams, ReplayInstructionResult, WritableAccountSnapshot};
 use crate::util;
 use crate::util::pubkey; // abbr
 
-pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedDeletePositionBundle>) -> ReplayInstructionResult {
+pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedDeletePositionBundle>) -> Result<ReplayInstructionResult, ErrorCode> {
   let replayer = req.replayer;
   let ix = req.decoded_instruction;
   let account_map = req.account_map;
 
-  let position_bundle_data = util::get_position_bundle_data(&ix.key_position_bundle, account_map);
+  let position_bundle_data = util::get_position_bundle_data(&ix.key_position_bundle, account_map)?;
   let position_bundle_mint = position_bundle_data.position_bundle_mint;
 
   // position_bundle
-  replayer.set_whirlpool_account(&ix.key_position_bundle, account_map);
+  replayer.set_whirlpool_account(&ix.key_position_bundle, account_map)?;
   // position_bundle_mint
   replayer.set_token_mint(
-    pubkey(&ix.key_position_bundle_mint),
+    pubkey(&ix.key_position_bundle_mint)?,
     None,
     1u64,
     0u8,
@@ -26,9 +27,9 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedDeletePo
   );
   // position_bundle_token_account
   replayer.set_token_account(
-    pubkey(&ix.key_position_bundle_token_account),
+    pubkey(&ix.key_position_bundle_token_account)?,
     position_bundle_mint,
-    pubkey(&ix.key_position_bundle_owner),
+    pubkey(&ix.key_position_bundle_owner)?,
     1u64
   );
   // position_bundle_owner
@@ -39,30 +40,30 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedDeletePo
     whirlpool_ix_args::DeletePositionBundle {
     },
     whirlpool_ix_accounts::DeletePositionBundle {
-      position_bundle: pubkey(&ix.key_position_bundle),
-      position_bundle_mint: pubkey(&ix.key_position_bundle_mint),
-      position_bundle_token_account: pubkey(&ix.key_position_bundle_token_account),
-      position_bundle_owner: pubkey(&ix.key_position_bundle_owner),
-      receiver: pubkey(&ix.key_receiver),
-      token_program: pubkey(&ix.key_token_program),
+      position_bundle: pubkey(&ix.key_position_bundle)?,
+      position_bundle_mint: pubkey(&ix.key_position_bundle_mint)?,
+      position_bundle_token_account: pubkey(&ix.key_position_bundle_token_account)?,
+      position_bundle_owner: pubkey(&ix.key_position_bundle_owner)?,
+      receiver: pubkey(&ix.key_receiver)?,
+      token_program: pubkey(&ix.key_token_program)?,
     },
   );
 
   let pre_snapshot = replayer.take_snapshot(&[
     &ix.key_position_bundle,
-  ]);
+  ])?;
   
   let replay_result = replayer.execute_transaction(tx);
 
-  let post_snapshot = replayer.take_snapshot(&[
+  let post_snapshot = replayer.take_post_snapshot(&replay_result, &[
     // closed
-  ]);
+  ])?;
 
-  return ReplayInstructionResult {
+  return Ok(ReplayInstructionResult {
     transaction_status: replay_result,
     snapshot: WritableAccountSnapshot {
       pre_snapshot,
       post_snapshot,
     }
-  }
+  });
 }
@@ -2,70 +2,71 @@ use whirlpool_base::accounts as whirlpool_ix_accounts;
 use whirlpool_base::instruction as whirlpool_ix_args;
 
 use crate::decoded_instructions;
+use crate::errors::ErrorCode;
 use crate::replay_instruction::{ReplayInstructionParams, ReplayInstructionResult, WritableAccountSnapshot};
 use crate::util;
 use crate::util::pubkey; // abbr
 
-pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedIncreaseLiquidity>) -> ReplayInstructionResult {
+pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedIncreaseLiquidity>) -> Result<ReplayInstructionResult, ErrorCode> {
   let replayer = req.replayer;
   let ix = req.decoded_instruction;
   let account_map = req.account_map;
 
-  let whirlpool_data = util::get_whirlpool_data(&ix.key_whirlpool, account_map);
+  let whirlpool_data = util::get_whirlpool_data(&ix.key_whirlpool, account_map)?;
   let mint_a = whirlpool_data.token_mint_a;
   let mint_b = whirlpool_data.token_mint_b;
 
-  let position_data = util::get_position_data(&ix.key_position, account_map);
+  let position_data = util::get_position_data(&ix.key_position, account_map)?;
   let position_mint = position_data.position_mint;
 
   let amount_a = ix.transfer_amount_0;
   let amount_b = ix.transfer_amount_1;
 
   // whirlpool
-  replayer.set_whirlpool_account(&ix.key_whirlpool, account_map);
+  replayer.set_whirlpool_account(&ix.key_whirlpool, account_map)?;
   // token_program
   // position_authority
   // position
-  replayer.set_whirlpool_account(&ix.key_position, account_map);
+  replayer.set_whirlpool_account(&ix.key_position, account_map)?;
   // position_token_amount
   replayer.set_token_account(
-    pubkey(&ix.key_position_token_account),
+    pubkey(&ix.key_position_token_account)?,
     position_mint,
-    pubkey(&ix.key_position_authority),
+    pubkey(&ix.key_position_authority)?,
     1u64
   );
   // token_owner_account_a
   replayer.set_token_account(
-    pubkey(&ix.key_token_owner_account_a),
+    pubkey(&ix.key_token_owner_account_a)?,
     mint_a,
-    pubkey(&ix.key_position_authority),
+    pubkey(&ix.key_position_authority)?,
     amount_a
   );
   // token_owner_account_b
   replayer.set_token_account(
-    pubkey(&ix.key_token_owner_account_b),
+    pubkey(&ix.key_token_owner_account_b)?,
     mint_b,
-    pubkey(&ix.key_position_authority),
+    pubkey(&ix.key_position_authority)?,
     amount_b
   );
   // token_vault_a
   replayer.set_token_account(
-    pubkey(&ix.key_token_vault_a),
+    pubkey(&ix.key_token_vault_a)?,
     mint_a,
-    pubkey(&ix.key_whirlpool),
+    pubkey(&ix.key_whirlpool)?,
     0u64
   );
   // token_vault_b
   replayer.set_token_account(
-    pubkey(&ix.key_token_vault_b),
+    pubkey(&ix.key_token_vault_b)?,
     mint_b,
-    pubkey(&ix.key_whirlpool),
+    pubkey(&ix.key_whirlpool)?,
     0u64
   );
   // tick_array_lower
-  replayer.set_whirlpool_account(&ix.key_tick_array_lower, account_map);
+  replayer.set_whirlpool_account(&ix.key_tick_array_lower, account_map)?;
   // tick_array_upper
-  replayer.set_whirlpool_account(&ix.key_tick_array_upper, account_map);
+  replayer.set_whirlpool_account(&ix.key_tick_array_upper, account_map)?;
 
   let tx = replayer.build_whirlpool_replay_transaction(
     whirlpool_ix_args::IncreaseLiquidity {
@@ -74,17 +75,17 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedIncrease
       token_max_b: ix.data_token_amount_max_b,
     },
     whirlpool_ix_accounts::ModifyLiquidity {
-      whirlpool: pubkey(&ix.key_whirlpool),
-      token_program: pubkey(&ix.key_token_program),
-      position_authority: pubkey(&ix.key_position_authority),
-      position: pubkey(&ix.key_position),
-      position_token_account: pubkey(&ix.key_position_token_account),
-      token_owner_account_a: pubkey(&ix.key_token_owner_account_a),
-      token_owner_account_b: pubkey(&ix.key_token_owner_account_b),
-      token_vault_a: pubkey(&ix.key_token_vault_a),
-      token_vault_b: pubkey(&ix.key_token_vault_b),
-      tick_array_lower: pubkey(&ix.key_tick_array_lower),
-      tick_array_upper: pubkey(&ix.key_tick_array_upper),
+      whirlpool: pubkey(&ix.key_whirlpool)?,
+      token_program: pubkey(&ix.key_token_program)?,
+      position_authority: pubkey(&ix.key_position_authority)?,
+      position: pubkey(&ix.key_position)?,
+      position_token_account: pubkey(&ix.key_position_token_account)?,
+      token_owner_account_a: pubkey(&ix.key_token_owner_account_a)?,
+      token_owner_account_b: pubkey(&ix.key_token_owner_account_b)?,
+      token_vault_a: pubkey(&ix.key_token_vault_a)?,
+      token_vault_b: pubkey(&ix.key_token_vault_b)?,
+      tick_array_lower: pubkey(&ix.key_tick_array_lower)?,
+      tick_array_upper: pubkey(&ix.key_tick_array_upper)?,
     },
   );
 
@@ -93,22 +94,22 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedIncrease
     &ix.key_position,
     &ix.key_tick_array_lower,
     &ix.key_tick_array_upper,
-  ]);
+  ])?;
   
   let replay_result = replayer.execute_transaction(tx);
 
-  let post_snapshot = replayer.take_snapshot(&[
+  let post_snapshot = replayer.take_post_snapshot(&replay_result, &[
     &ix.key_whirlpool,
     &ix.key_position,
     &ix.key_tick_array_lower,
     &ix.key_tick_array_upper,
-  ]);
+  ])?;
 
-  return ReplayInstructionResult {
+  return Ok(ReplayInstructionResult {
     transaction_status: replay_result,
     snapshot: WritableAccountSnapshot {
       pre_snapshot,
       post_snapshot,
     }
-  }
+  });
 }
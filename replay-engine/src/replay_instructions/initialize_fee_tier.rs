@@ -2,19 +2,20 @@ use whirlpool_base::accounts as whirlpool_ix_accounts;
 use whirlpool_base::instruction as whirlpool_ix_args;
 
 use crate::decoded_instructions;
+use crate::errors::ErrorCode;
 use crate::replay_instruction::{ReplayInstructionParams, ReplayInstructionResult, WritableAccountSnapshot};
 use crate::util::pubkey; // abbr
 
-pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedInitializeFeeTier>) -> ReplayInstructionResult {
+pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedInitializeFeeTier>) -> Result<ReplayInstructionResult, ErrorCode> {
   let replayer = req.replayer;
   let ix = req.decoded_instruction;
   let account_map = req.account_map;
 
   // config
-  replayer.set_whirlpool_account(&ix.key_whirlpools_config, account_map);
+  replayer.set_whirlpool_account(&ix.key_whirlpools_config, account_map)?;
   // fee_tier
   // funder
-  replayer.set_funder_account(&ix.key_funder);
+  replayer.set_funder_account(&ix.key_funder)?;
   // fee_authority
   // system_program
 
@@ -24,30 +25,30 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedInitiali
       default_fee_rate: ix.data_default_fee_rate,
     },
     whirlpool_ix_accounts::InitializeFeeTier {
-      config: pubkey(&ix.key_whirlpools_config),
-      fee_tier: pubkey(&ix.key_fee_tier),
-      funder: pubkey(&ix.key_funder),
-      fee_authority: pubkey(&ix.key_fee_authority),
-      system_program: pubkey(&ix.key_system_program),
+      config: pubkey(&ix.key_whirlpools_config)?,
+      fee_tier: pubkey(&ix.key_fee_tier)?,
+      funder: pubkey(&ix.key_funder)?,
+      fee_authority: pubkey(&ix.key_fee_authority)?,
+      system_program: pubkey(&ix.key_system_program)?,
     },
   );
 
   let pre_snapshot = replayer.take_snapshot(&[
     &ix.key_whirlpools_config,
-  ]);
+  ])?;
   
   let replay_result = replayer.execute_transaction(tx);
 
-  let post_snapshot = replayer.take_snapshot(&[
+  let post_snapshot = replayer.take_post_snapshot(&replay_result, &[
     &ix.key_whirlpools_config,
     &ix.key_fee_tier, // created
-  ]);
+  ])?;
 
-  return ReplayInstructionResult {
+  return Ok(ReplayInstructionResult {
     transaction_status: replay_result,
     snapshot: WritableAccountSnapshot {
       pre_snapshot,
       post_snapshot,
     }
-  }
+  });
 }
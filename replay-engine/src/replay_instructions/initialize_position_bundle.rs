@@ -2,10 +2,11 @@ use whirlpool_base::accounts as whirlpool_ix_accounts;
 use whirlpool_base::instruction as whirlpool_ix_args;
 
 use crate::decoded_instructions;
+use crate::errors::ErrorCode;
 use crate::replay_instruction::{ReplayInstructionParams, ReplayInstructionResult, WritableAccountSnapshot};
 use crate::util::pubkey; // abbr
 
-pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedInitializePositionBundle>) -> ReplayInstructionResult {
+pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedInitializePositionBundle>) -> Result<ReplayInstructionResult, ErrorCode> {
   let replayer = req.replayer;
   let ix = req.decoded_instruction;
   let _account_map = req.account_map;
@@ -15,7 +16,7 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedInitiali
   // position_bundle_token_account
   // position_bundle_owner
   // funder
-  replayer.set_funder_account(&ix.key_funder);
+  replayer.set_funder_account(&ix.key_funder)?;
   // token_program
   // system_program
   // rent
@@ -25,32 +26,32 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedInitiali
     whirlpool_ix_args::InitializePositionBundle {
     },
     whirlpool_ix_accounts::InitializePositionBundle {
-      position_bundle: pubkey(&ix.key_position_bundle),
-      position_bundle_mint: pubkey(&ix.key_position_bundle_mint),
-      position_bundle_token_account: pubkey(&ix.key_position_bundle_token_account),
-      position_bundle_owner: pubkey(&ix.key_position_bundle_owner),
-      funder: pubkey(&ix.key_funder),
-      token_program: pubkey(&ix.key_token_program),
-      system_program: pubkey(&ix.key_system_program),
-      rent: pubkey(&ix.key_rent),
-      associated_token_program: pubkey(&ix.key_associated_token_program),
+      position_bundle: pubkey(&ix.key_position_bundle)?,
+      position_bundle_mint: pubkey(&ix.key_position_bundle_mint)?,
+      position_bundle_token_account: pubkey(&ix.key_position_bundle_token_account)?,
+      position_bundle_owner: pubkey(&ix.key_position_bundle_owner)?,
+      funder: pubkey(&ix.key_funder)?,
+      token_program: pubkey(&ix.key_token_program)?,
+      system_program: pubkey(&ix.key_system_program)?,
+      rent: pubkey(&ix.key_rent)?,
+      associated_token_program: pubkey(&ix.key_associated_token_program)?,
     },
   );
 
   let pre_snapshot = replayer.take_snapshot(&[
-  ]);
+  ])?;
   
   let replay_result = replayer.execute_transaction(tx);
 
-  let post_snapshot = replayer.take_snapshot(&[
+  let post_snapshot = replayer.take_post_snapshot(&replay_result, &[
     &ix.key_position_bundle, // created
-  ]);
+  ])?;
 
-  return ReplayInstructionResult {
+  return Ok(ReplayInstructionResult {
     transaction_status: replay_result,
     snapshot: WritableAccountSnapshot {
       pre_snapshot,
       post_snapshot,
     }
-  }
+  });
 }
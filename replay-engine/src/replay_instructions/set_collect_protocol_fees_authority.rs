@@ -2,16 +2,17 @@ use whirlpool_base::accounts as whirlpool_ix_accounts;
 use whirlpool_base::instruction as whirlpool_ix_args;
 
 use crate::decoded_instructions;
+use crate::errors::ErrorCode;
 use crate::replay_instruction::{ReplayInstructionParams, ReplayInstructionResult, WritableAccountSnapshot};
 use crate::util::pubkey; // abbr
 
-pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedSetCollectProtocolFeesAuthority>) -> ReplayInstructionResult {
+pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedSetCollectProtocolFeesAuthority>) -> Result<ReplayInstructionResult, ErrorCode> {
   let replayer = req.replayer;
   let ix = req.decoded_instruction;
   let account_map = req.account_map;
 
   // whirlpools_config
-  replayer.set_whirlpool_account(&ix.key_whirlpools_config, account_map);
+  replayer.set_whirlpool_account(&ix.key_whirlpools_config, account_map)?;
   // collect_protocol_fees_authority
   // new_collect_protocol_fees_authority
 
@@ -19,27 +20,27 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedSetColle
     whirlpool_ix_args::SetCollectProtocolFeesAuthority {
     },
     whirlpool_ix_accounts::SetCollectProtocolFeesAuthority {
-      whirlpools_config: pubkey(&ix.key_whirlpools_config),
-      collect_protocol_fees_authority: pubkey(&ix.key_collect_protocol_fees_authority),
-      new_collect_protocol_fees_authority: pubkey(&ix.key_new_collect_protocol_fees_authority),
+      whirlpools_config: pubkey(&ix.key_whirlpools_config)?,
+      collect_protocol_fees_authority: pubkey(&ix.key_collect_protocol_fees_authority)?,
+      new_collect_protocol_fees_authority: pubkey(&ix.key_new_collect_protocol_fees_authority)?,
     },
   );
 
   let pre_snapshot = replayer.take_snapshot(&[
     &ix.key_whirlpools_config,
-  ]);
+  ])?;
   
   let replay_result = replayer.execute_transaction(tx);
 
-  let post_snapshot = replayer.take_snapshot(&[
+  let post_snapshot = replayer.take_post_snapshot(&replay_result, &[
     &ix.key_whirlpools_config,
-  ]);
+  ])?;
 
-  return ReplayInstructionResult {
+  return Ok(ReplayInstructionResult {
     transaction_status: replay_result,
     snapshot: WritableAccountSnapshot {
       pre_snapshot,
       post_snapshot,
     }
-  }
+  });
 }
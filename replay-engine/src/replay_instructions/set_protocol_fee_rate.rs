@@ -2,18 +2,19 @@ use whirlpool_base::accounts as whirlpool_ix_accounts;
 use whirlpool_base::instruction as whirlpool_ix_args;
 
 use crate::decoded_instructions;
+use crate::errors::ErrorCode;
 use crate::replay_instruction::{ReplayInstructionParams, ReplayInstructionResult, WritableAccountSnapshot};
 use crate::util::pubkey; // abbr
 
-pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedSetProtocolFeeRate>) -> ReplayInstructionResult {
+pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedSetProtocolFeeRate>) -> Result<ReplayInstructionResult, ErrorCode> {
   let replayer = req.replayer;
   let ix = req.decoded_instruction;
   let account_map = req.account_map;
 
   // whirlpools_config
-  replayer.set_whirlpool_account(&ix.key_whirlpools_config, account_map);
+  replayer.set_whirlpool_account(&ix.key_whirlpools_config, account_map)?;
   // whirlpool
-  replayer.set_whirlpool_account(&ix.key_whirlpool, account_map);
+  replayer.set_whirlpool_account(&ix.key_whirlpool, account_map)?;
   // fee_authority
     
   let tx = replayer.build_whirlpool_replay_transaction(
@@ -21,29 +22,29 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedSetProto
       protocol_fee_rate: ix.data_protocol_fee_rate,
     },
     whirlpool_ix_accounts::SetProtocolFeeRate {
-      whirlpools_config: pubkey(&ix.key_whirlpools_config),
-      whirlpool: pubkey(&ix.key_whirlpool),
-      fee_authority: pubkey(&ix.key_fee_authority),
+      whirlpools_config: pubkey(&ix.key_whirlpools_config)?,
+      whirlpool: pubkey(&ix.key_whirlpool)?,
+      fee_authority: pubkey(&ix.key_fee_authority)?,
     },
   );
 
   let pre_snapshot = replayer.take_snapshot(&[
     &ix.key_whirlpools_config,
     &ix.key_whirlpool,
-  ]);
+  ])?;
   
   let replay_result = replayer.execute_transaction(tx);
 
-  let post_snapshot = replayer.take_snapshot(&[
+  let post_snapshot = replayer.take_post_snapshot(&replay_result, &[
     &ix.key_whirlpools_config,
     &ix.key_whirlpool,
-  ]);
+  ])?;
 
-  return ReplayInstructionResult {
+  return Ok(ReplayInstructionResult {
     transaction_status: replay_result,
     snapshot: WritableAccountSnapshot {
       pre_snapshot,
       post_snapshot,
     }
-  }
+  });
 }
@@ -2,16 +2,17 @@ use whirlpool_base::accounts as whirlpool_ix_accounts;
 use whirlpool_base::instruction as whirlpool_ix_args;
 
 use crate::decoded_instructions;
+use crate::errors::ErrorCode;
 use crate::replay_instruction::{ReplayInstructionParams, ReplayInstructionResult, WritableAccountSnapshot};
 use crate::util;
 use crate::util::pubkey; // abbr
 
-pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedSwap>) -> ReplayInstructionResult {
+pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedSwap>) -> Result<ReplayInstructionResult, ErrorCode> {
   let replayer = req.replayer;
   let ix = req.decoded_instruction;
   let account_map = req.account_map;
 
-  let whirlpool_data = util::get_whirlpool_data(&ix.key_whirlpool, account_map);
+  let whirlpool_data = util::get_whirlpool_data(&ix.key_whirlpool, account_map)?;
   let mint_a = whirlpool_data.token_mint_a;
   let mint_b = whirlpool_data.token_mint_b;
 
@@ -23,41 +24,41 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedSwap>) -
   // token_program
   // token_authority
   // whirlpool
-  replayer.set_whirlpool_account(&ix.key_whirlpool, account_map);
+  replayer.set_whirlpool_account(&ix.key_whirlpool, account_map)?;
   // token_owner_account_a
   replayer.set_token_account(
-    pubkey(&ix.key_token_owner_account_a),
+    pubkey(&ix.key_token_owner_account_a)?,
     mint_a,
-    pubkey(&ix.key_token_authority),
+    pubkey(&ix.key_token_authority)?,
     if mint_a_is_input { input_amount } else { 0u64 }
   );
   // vault_a
   replayer.set_token_account(
-    pubkey(&ix.key_vault_a),
+    pubkey(&ix.key_vault_a)?,
     mint_a,
-    pubkey(&ix.key_whirlpool),
+    pubkey(&ix.key_whirlpool)?,
     if mint_a_is_input { 0u64 } else { output_amount }
   );
   // token_owner_account_b
   replayer.set_token_account(
-    pubkey(&ix.key_token_owner_account_b),
+    pubkey(&ix.key_token_owner_account_b)?,
     mint_b,
-    pubkey(&ix.key_token_authority),
+    pubkey(&ix.key_token_authority)?,
     if mint_b_is_input { input_amount } else { 0u64 }
   );
   // vault_b
   replayer.set_token_account(
-    pubkey(&ix.key_vault_b),
+    pubkey(&ix.key_vault_b)?,
     mint_b,
-    pubkey(&ix.key_whirlpool),
+    pubkey(&ix.key_whirlpool)?,
     if mint_b_is_input { 0u64 } else { output_amount }
   );
   // tick_array_0
-  replayer.set_whirlpool_account(&ix.key_tick_array_0, account_map);
+  replayer.set_whirlpool_account(&ix.key_tick_array_0, account_map)?;
   // tick_array_1
-  replayer.set_whirlpool_account(&ix.key_tick_array_1, account_map);
+  replayer.set_whirlpool_account(&ix.key_tick_array_1, account_map)?;
   // tick_array_2
-  replayer.set_whirlpool_account(&ix.key_tick_array_2, account_map);
+  replayer.set_whirlpool_account(&ix.key_tick_array_2, account_map)?;
   // oracle
 
   let tx = replayer.build_whirlpool_replay_transaction(
@@ -69,17 +70,17 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedSwap>) -
       a_to_b: ix.data_a_to_b,
     },
     whirlpool_ix_accounts::Swap {
-      token_program: pubkey(&ix.key_token_program),
-      token_authority: pubkey(&ix.key_token_authority),
-      whirlpool: pubkey(&ix.key_whirlpool),
-      token_owner_account_a: pubkey(&ix.key_token_owner_account_a),
-      token_vault_a: pubkey(&ix.key_vault_a),
-      token_owner_account_b: pubkey(&ix.key_token_owner_account_b),
-      token_vault_b: pubkey(&ix.key_vault_b),
-      tick_array_0: pubkey(&ix.key_tick_array_0),
-      tick_array_1: pubkey(&ix.key_tick_array_1),
-      tick_array_2: pubkey(&ix.key_tick_array_2),
-      oracle: pubkey(&ix.key_oracle),
+      token_program: pubkey(&ix.key_token_program)?,
+      token_authority: pubkey(&ix.key_token_authority)?,
+      whirlpool: pubkey(&ix.key_whirlpool)?,
+      token_owner_account_a: pubkey(&ix.key_token_owner_account_a)?,
+      token_vault_a: pubkey(&ix.key_vault_a)?,
+      token_owner_account_b: pubkey(&ix.key_token_owner_account_b)?,
+      token_vault_b: pubkey(&ix.key_vault_b)?,
+      tick_array_0: pubkey(&ix.key_tick_array_0)?,
+      tick_array_1: pubkey(&ix.key_tick_array_1)?,
+      tick_array_2: pubkey(&ix.key_tick_array_2)?,
+      oracle: pubkey(&ix.key_oracle)?,
     },
   );
 
@@ -88,24 +89,24 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedSwap>) -
     &ix.key_tick_array_0,
     &ix.key_tick_array_1,
     &ix.key_tick_array_2,
-  ]);
+  ])?;
   
   let replay_result = replayer.execute_transaction(tx);
 
-  let post_snapshot = replayer.take_snapshot(&[
+  let post_snapshot = replayer.take_post_snapshot(&replay_result, &[
     &ix.key_whirlpool,
     &ix.key_tick_array_0,
     &ix.key_tick_array_1,
     &ix.key_tick_array_2,
-  ]);
+  ])?;
 
-  return ReplayInstructionResult {
+  return Ok(ReplayInstructionResult {
     transaction_status: replay_result,
     snapshot: WritableAccountSnapshot {
       pre_snapshot,
       post_snapshot,
     }
-  }
+  });
 }
 
 
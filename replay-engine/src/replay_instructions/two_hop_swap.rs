@@ -2,17 +2,18 @@ use whirlpool_base::accounts as whirlpool_ix_accounts;
 use whirlpool_base::instruction as whirlpool_ix_args;
 
 use crate::decoded_instructions;
+use crate::errors::ErrorCode;
 use crate::replay_instruction::{ReplayInstructionParams, ReplayInstructionResult, WritableAccountSnapshot};
 use crate::util;
 use crate::util::pubkey; // abbr
 
-pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedTwoHopSwap>) -> ReplayInstructionResult {
+pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedTwoHopSwap>) -> Result<ReplayInstructionResult, ErrorCode> {
   let replayer = req.replayer;
   let ix = req.decoded_instruction;
   let account_map = req.account_map;
 
-  let whirlpool_one_data = util::get_whirlpool_data(&ix.key_whirlpool_one, account_map);
-  let whirlpool_two_data = util::get_whirlpool_data(&ix.key_whirlpool_two, account_map);
+  let whirlpool_one_data = util::get_whirlpool_data(&ix.key_whirlpool_one, account_map)?;
+  let whirlpool_two_data = util::get_whirlpool_data(&ix.key_whirlpool_two, account_map)?;
   let mint_one_a = whirlpool_one_data.token_mint_a;
   let mint_one_b = whirlpool_one_data.token_mint_b;
   let mint_two_a = whirlpool_two_data.token_mint_a;
@@ -30,77 +31,77 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedTwoHopSw
   // token_program
   // token_authority
   // whirlpool_one
-  replayer.set_whirlpool_account(&ix.key_whirlpool_one, account_map);
+  replayer.set_whirlpool_account(&ix.key_whirlpool_one, account_map)?;
   // whirlpool_two
-  replayer.set_whirlpool_account(&ix.key_whirlpool_two, account_map);
+  replayer.set_whirlpool_account(&ix.key_whirlpool_two, account_map)?;
   // token_owner_account_one_a
   replayer.set_token_account(
-    pubkey(&ix.key_token_owner_account_one_a),
+    pubkey(&ix.key_token_owner_account_one_a)?,
     mint_one_a,
-    pubkey(&ix.key_token_authority),
+    pubkey(&ix.key_token_authority)?,
     if ix.key_token_owner_account_one_a == input_token_owner_account { input_amount } else { 0u64 }
   );
   // vault_one_a
   replayer.set_token_account(
-    pubkey(&ix.key_vault_one_a),
+    pubkey(&ix.key_vault_one_a)?,
     mint_one_a,
-    pubkey(&ix.key_whirlpool_one),
+    pubkey(&ix.key_whirlpool_one)?,
     if mint_one_a == input_mint { 0u64 } else { intermediate_amount }
   );
   // token_owner_account_one_b
   replayer.set_token_account(
-    pubkey(&ix.key_token_owner_account_one_b),
+    pubkey(&ix.key_token_owner_account_one_b)?,
     mint_one_b,
-    pubkey(&ix.key_token_authority),
+    pubkey(&ix.key_token_authority)?,
     if ix.key_token_owner_account_one_b == input_token_owner_account { input_amount } else { 0u64 }
   );
   // vault_one_b
   replayer.set_token_account(
-    pubkey(&ix.key_vault_one_b),
+    pubkey(&ix.key_vault_one_b)?,
     mint_one_b,
-    pubkey(&ix.key_whirlpool_one),
+    pubkey(&ix.key_whirlpool_one)?,
     if mint_one_b == input_mint { 0u64 } else { intermediate_amount }
   );
   // token_owner_account_two_a
   replayer.set_token_account(
-    pubkey(&ix.key_token_owner_account_two_a),
+    pubkey(&ix.key_token_owner_account_two_a)?,
     mint_two_a,
-    pubkey(&ix.key_token_authority),
+    pubkey(&ix.key_token_authority)?,
     if ix.key_token_owner_account_two_a == input_token_owner_account { input_amount } else { 0u64 }
   );
   // vault_two_a
   replayer.set_token_account(
-    pubkey(&ix.key_vault_two_a),
+    pubkey(&ix.key_vault_two_a)?,
     mint_two_a,
-    pubkey(&ix.key_whirlpool_two),
+    pubkey(&ix.key_whirlpool_two)?,
     if mint_two_a == output_mint { output_amount } else { 0u64 }
   );
   // token_owner_account_two_b
   replayer.set_token_account(
-    pubkey(&ix.key_token_owner_account_two_b),
+    pubkey(&ix.key_token_owner_account_two_b)?,
     mint_two_b,
-    pubkey(&ix.key_token_authority),
+    pubkey(&ix.key_token_authority)?,
     if ix.key_token_owner_account_two_b == input_token_owner_account { input_amount } else { 0u64 }
   );
   // vault_two_b
   replayer.set_token_account(
-    pubkey(&ix.key_vault_two_b),
+    pubkey(&ix.key_vault_two_b)?,
     mint_two_b,
-    pubkey(&ix.key_whirlpool_two),
+    pubkey(&ix.key_whirlpool_two)?,
     if mint_two_b == output_mint { output_amount } else { 0u64 }
   );
   // tick_array_one_0
-  replayer.set_whirlpool_account(&ix.key_tick_array_one_0, account_map);
+  replayer.set_whirlpool_account(&ix.key_tick_array_one_0, account_map)?;
   // tick_array_one_1
-  replayer.set_whirlpool_account(&ix.key_tick_array_one_1, account_map);
+  replayer.set_whirlpool_account(&ix.key_tick_array_one_1, account_map)?;
   // tick_array_one_2
-  replayer.set_whirlpool_account(&ix.key_tick_array_one_2, account_map);
+  replayer.set_whirlpool_account(&ix.key_tick_array_one_2, account_map)?;
   // tick_array_two_0
-  replayer.set_whirlpool_account(&ix.key_tick_array_two_0, account_map);
+  replayer.set_whirlpool_account(&ix.key_tick_array_two_0, account_map)?;
   // tick_array_two_1
-  replayer.set_whirlpool_account(&ix.key_tick_array_two_1, account_map);
+  replayer.set_whirlpool_account(&ix.key_tick_array_two_1, account_map)?;
   // tick_array_two_2
-  replayer.set_whirlpool_account(&ix.key_tick_array_two_2, account_map);
+  replayer.set_whirlpool_account(&ix.key_tick_array_two_2, account_map)?;
   // oracle_one
   // oracle_two
 
@@ -115,26 +116,26 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedTwoHopSw
       a_to_b_two: ix.data_a_to_b_two,
     },
     whirlpool_ix_accounts::TwoHopSwap {
-      token_program: pubkey(&ix.key_token_program),
-      token_authority: pubkey(&ix.key_token_authority),
-      whirlpool_one: pubkey(&ix.key_whirlpool_one),
-      whirlpool_two: pubkey(&ix.key_whirlpool_two),
-      token_owner_account_one_a: pubkey(&ix.key_token_owner_account_one_a),
-      token_vault_one_a: pubkey(&ix.key_vault_one_a),
-      token_owner_account_one_b: pubkey(&ix.key_token_owner_account_one_b),
-      token_vault_one_b: pubkey(&ix.key_vault_one_b),
-      token_owner_account_two_a: pubkey(&ix.key_token_owner_account_two_a),
-      token_vault_two_a: pubkey(&ix.key_vault_two_a),
-      token_owner_account_two_b: pubkey(&ix.key_token_owner_account_two_b),
-      token_vault_two_b: pubkey(&ix.key_vault_two_b),
-      tick_array_one_0: pubkey(&ix.key_tick_array_one_0),
-      tick_array_one_1: pubkey(&ix.key_tick_array_one_1),
-      tick_array_one_2: pubkey(&ix.key_tick_array_one_2),
-      tick_array_two_0: pubkey(&ix.key_tick_array_two_0),
-      tick_array_two_1: pubkey(&ix.key_tick_array_two_1),
-      tick_array_two_2: pubkey(&ix.key_tick_array_two_2),
-      oracle_one: pubkey(&ix.key_oracle_one),
-      oracle_two: pubkey(&ix.key_oracle_two),
+      token_program: pubkey(&ix.key_token_program)?,
+      token_authority: pubkey(&ix.key_token_authority)?,
+      whirlpool_one: pubkey(&ix.key_whirlpool_one)?,
+      whirlpool_two: pubkey(&ix.key_whirlpool_two)?,
+      token_owner_account_one_a: pubkey(&ix.key_token_owner_account_one_a)?,
+      token_vault_one_a: pubkey(&ix.key_vault_one_a)?,
+      token_owner_account_one_b: pubkey(&ix.key_token_owner_account_one_b)?,
+      token_vault_one_b: pubkey(&ix.key_vault_one_b)?,
+      token_owner_account_two_a: pubkey(&ix.key_token_owner_account_two_a)?,
+      token_vault_two_a: pubkey(&ix.key_vault_two_a)?,
+      token_owner_account_two_b: pubkey(&ix.key_token_owner_account_two_b)?,
+      token_vault_two_b: pubkey(&ix.key_vault_two_b)?,
+      tick_array_one_0: pubkey(&ix.key_tick_array_one_0)?,
+      tick_array_one_1: pubkey(&ix.key_tick_array_one_1)?,
+      tick_array_one_2: pubkey(&ix.key_tick_array_one_2)?,
+      tick_array_two_0: pubkey(&ix.key_tick_array_two_0)?,
+      tick_array_two_1: pubkey(&ix.key_tick_array_two_1)?,
+      tick_array_two_2: pubkey(&ix.key_tick_array_two_2)?,
+      oracle_one: pubkey(&ix.key_oracle_one)?,
+      oracle_two: pubkey(&ix.key_oracle_two)?,
     },
   );
 
@@ -147,11 +148,11 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedTwoHopSw
     &ix.key_tick_array_two_0,
     &ix.key_tick_array_two_1,
     &ix.key_tick_array_two_2,
-  ]);
+  ])?;
   
   let replay_result = replayer.execute_transaction(tx);
 
-  let post_snapshot = replayer.take_snapshot(&[
+  let post_snapshot = replayer.take_post_snapshot(&replay_result, &[
     &ix.key_whirlpool_one,
     &ix.key_whirlpool_two,
     &ix.key_tick_array_one_0,
@@ -160,13 +161,13 @@ pub fn replay(req: ReplayInstructionParams<decoded_instructions::DecodedTwoHopSw
     &ix.key_tick_array_two_0,
     &ix.key_tick_array_two_1,
     &ix.key_tick_array_two_2,
-  ]);
+  ])?;
 
-  return ReplayInstructionResult {
+  return Ok(ReplayInstructionResult {
     transaction_status: replay_result,
     snapshot: WritableAccountSnapshot {
       pre_snapshot,
       post_snapshot,
     }
-  }
+  });
 }
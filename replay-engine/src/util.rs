@@ -4,35 +4,54 @@ use solana_sdk::pubkey::Pubkey;
 use std::str::FromStr;
 use whirlpool_base::state::{Position, PositionBundle, Whirlpool};
 
+use crate::account_index::AccountIndex;
+use crate::errors::ErrorCode;
 use crate::pubkeys::ORCA_WHIRLPOOL_PROGRAM_ID;
 use crate::types::AccountMap;
 
-pub fn get_whirlpool_data(pubkey_string: &String, account_map: &AccountMap) -> Whirlpool {
-    let data = account_map.get(pubkey_string).unwrap();
-    let whirlpool_data =
-        whirlpool_base::state::Whirlpool::try_deserialize(&mut data.as_slice()).unwrap();
-    return whirlpool_data;
+pub fn get_whirlpool_data(
+    pubkey_string: &String,
+    account_map: &AccountMap,
+) -> Result<Whirlpool, ErrorCode> {
+    let data = get_account_data(pubkey_string, account_map)?;
+    let whirlpool_data = whirlpool_base::state::Whirlpool::try_deserialize(&mut data.as_slice())
+        .map_err(|_| ErrorCode::InvalidAccountData(pubkey_string.clone()))?;
+    return Ok(whirlpool_data);
 }
 
-pub fn get_position_data(pubkey_string: &String, account_map: &AccountMap) -> Position {
-    let data = account_map.get(pubkey_string).unwrap();
-    let position_data =
-        whirlpool_base::state::Position::try_deserialize(&mut data.as_slice()).unwrap();
-    return position_data;
+pub fn get_position_data(
+    pubkey_string: &String,
+    account_map: &AccountMap,
+) -> Result<Position, ErrorCode> {
+    let data = get_account_data(pubkey_string, account_map)?;
+    let position_data = whirlpool_base::state::Position::try_deserialize(&mut data.as_slice())
+        .map_err(|_| ErrorCode::InvalidAccountData(pubkey_string.clone()))?;
+    return Ok(position_data);
 }
 
 pub fn get_position_bundle_data(
     pubkey_string: &String,
     account_map: &AccountMap,
-) -> PositionBundle {
-    let data = account_map.get(pubkey_string).unwrap();
+) -> Result<PositionBundle, ErrorCode> {
+    let data = get_account_data(pubkey_string, account_map)?;
     let position_bundle_data =
-        whirlpool_base::state::PositionBundle::try_deserialize(&mut data.as_slice()).unwrap();
-    return position_bundle_data;
+        whirlpool_base::state::PositionBundle::try_deserialize(&mut data.as_slice())
+            .map_err(|_| ErrorCode::InvalidAccountData(pubkey_string.clone()))?;
+    return Ok(position_bundle_data);
 }
 
-pub fn pubkey(pubkey_string: &String) -> Pubkey {
-    return Pubkey::from_str(pubkey_string).unwrap();
+pub fn get_account_data<'a>(
+    pubkey_string: &String,
+    account_map: &'a AccountMap,
+) -> Result<&'a Vec<u8>, ErrorCode> {
+    return account_map
+        .get(pubkey_string)
+        .ok_or_else(|| ErrorCode::AccountNotFound(pubkey_string.clone()));
+}
+
+pub fn pubkey(pubkey_string: &String) -> Result<Pubkey, ErrorCode> {
+    return Pubkey::from_str(pubkey_string)
+        .map_err(|_| ErrorCode::InvalidPubkey(pubkey_string.clone()));
 }
 
 pub fn derive_position_bump(position_mint: &Pubkey) -> u8 {
@@ -64,6 +83,7 @@ pub fn derive_whirlpool_bump(
 
 pub fn update_account_map(
     account_map: &mut AccountMap,
+    account_index: Option<&mut AccountIndex>,
     pre_snapshot: AccountMap,
     post_snapshot: AccountMap,
 ) {
@@ -73,6 +93,15 @@ pub fn update_account_map(
         .map(|k| k.clone())
         .collect();
 
+    if let Some(account_index) = account_index {
+        for (pubkey_string, data) in post_snapshot.iter() {
+            account_index.update(pubkey_string, Some(data));
+        }
+        for pubkey_string in closed_account_pubkeys.iter() {
+            account_index.update(pubkey_string, None);
+        }
+    }
+
     // add created & update accounts
     account_map.extend(post_snapshot);
 
@@ -0,0 +1,595 @@
+use std::io::{Read, Write};
+
+use bincode::Options;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::errors::ReplayError;
+use crate::pipeline::{decode_instructions, run_pipeline, PipelineConfig};
+use crate::schema::{Transaction, TransactionBalance, TransactionInstruction, WhirlpoolState, WhirlpoolStateAccount, WhirlpoolStatePosition, WhirlpoolTransaction};
+
+pub const BINARY_FILE_EXTENSION: &str = ".bin.zst";
+pub const BINARY_FORMAT_VERSION: u16 = 2;
+
+const STATE_MAGIC: [u8; 4] = *b"WRPS";
+const TRANSACTION_MAGIC: [u8; 4] = *b"WRPT";
+const ZSTD_LEVEL: i32 = 3;
+
+const RECORD_TAG_END: u8 = 0;
+const RECORD_TAG_SLOT: u8 = 1;
+
+// a value (header, pubkey or a slot record) larger than this is treated as corrupt,
+// so a corrupt length prefix never allocates a huge buffer
+const MAX_VALUE_SIZE: u64 = 256 * 1024 * 1024;
+
+/*
+
+Whirlpool Binary File Format
+
+A binary file (whirlpool-state-yyyymmdd.bin.zst, whirlpool-transaction-yyyymmdd.bin.zst) has the following layout:
+
+  magic: [u8; 4] ("WRPS" for state, "WRPT" for transactions)
+  version: u16 (little endian)
+  body: zstd compressed stream of bincode encoded values
+
+State body (accounts are streamed, so the whole file never needs to be in memory at once):
+
+  BinaryStateHeader { slot: u64, blockHeight: u64, blockTime: i64, accountCount: u64, position: Option<(u32, u32)> }
+  (pubkey: String(base58 encoding), data: Vec<u8>) * accountCount
+  programData: Vec<u8>
+
+Transaction body (one record per slot, terminated by the end tag):
+
+  (tag: u8 = 1, BinaryWhirlpoolTransaction) * number of slots
+  tag: u8 = 0
+
+payload of instructions is stored as a tree of BinaryValue (the JSON value without JSON text),
+so reading a file needs no JSON parsing. the values are kept as in the JSON format
+(e.g. u64 fields of instructions are strings), and decoded into instructions on worker threads.
+u64 values (balances) are stored as u64, not as string.
+
+io::load_* and io::save_* choose the format by the file extension.
+StorageBackend looks up the binary file of a day first and falls back to the JSON file,
+so a storage can hold either format (or both) for each day.
+
+version history:
+  1: payload as JSON string
+  2: payload as BinaryValue, position in BinaryStateHeader
+
+*/
+
+#[derive(Serialize, Deserialize)]
+struct BinaryStateHeader {
+    slot: u64,
+    block_height: u64,
+    block_time: i64,
+    account_count: u64,
+    // (transaction index, instruction index) of a state saved in the middle of the slot
+    position: Option<(u32, u32)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinaryWhirlpoolTransaction {
+    slot: u64,
+    block_height: u64,
+    block_time: i64,
+    transactions: Vec<BinaryTransaction>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinaryTransaction {
+    index: u32,
+    signature: String,
+    payer: String,
+    balances: Vec<BinaryTransactionBalance>,
+    instructions: Vec<BinaryTransactionInstruction>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinaryTransactionBalance {
+    account: String,
+    pre: u64,
+    post: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinaryTransactionInstruction {
+    name: String,
+    payload: BinaryValue,
+}
+
+// serde_json::Value can't be read by bincode (it needs deserialize_any), so the tree is mirrored
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum BinaryValue {
+    Null,
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    String(String),
+    Array(Vec<BinaryValue>),
+    Object(Vec<(String, BinaryValue)>),
+}
+
+impl From<&Value> for BinaryValue {
+    fn from(value: &Value) -> Self {
+        return match value {
+            Value::Null => BinaryValue::Null,
+            Value::Bool(b) => BinaryValue::Bool(*b),
+            Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(n), _) => BinaryValue::U64(n),
+                (None, Some(n)) => BinaryValue::I64(n),
+                // as_f64 never fails without arbitrary_precision
+                (None, None) => BinaryValue::F64(n.as_f64().unwrap_or_default()),
+            },
+            Value::String(s) => BinaryValue::String(s.clone()),
+            Value::Array(values) => BinaryValue::Array(values.iter().map(BinaryValue::from).collect()),
+            Value::Object(map) => BinaryValue::Object(map.iter().map(|(key, value)| (key.clone(), BinaryValue::from(value))).collect()),
+        };
+    }
+}
+
+impl From<BinaryValue> for Value {
+    fn from(value: BinaryValue) -> Self {
+        return match value {
+            BinaryValue::Null => Value::Null,
+            BinaryValue::Bool(b) => Value::Bool(b),
+            BinaryValue::U64(n) => Value::from(n),
+            BinaryValue::I64(n) => Value::from(n),
+            // NaN and infinity are not valid JSON (never written from a JSON value)
+            BinaryValue::F64(n) => serde_json::Number::from_f64(n).map_or(Value::Null, Value::Number),
+            BinaryValue::String(s) => Value::String(s),
+            BinaryValue::Array(values) => Value::Array(values.into_iter().map(Value::from).collect()),
+            BinaryValue::Object(entries) => Value::Object(entries.into_iter().map(|(key, value)| (key, Value::from(value))).collect()),
+        };
+    }
+}
+
+pub fn read_whirlpool_state_binary<R: Read>(mut reader: R, path: &String) -> Result<WhirlpoolState, ReplayError> {
+    read_header(&mut reader, &STATE_MAGIC, path)?;
+    let mut decoder = zstd::stream::read::Decoder::new(reader).map_err(|source| io_error(path, source))?;
+
+    let header: BinaryStateHeader = deserialize_from(&mut decoder, path)?;
+    // account_count is not trusted for preallocation (a corrupt header would allocate a huge buffer)
+    let mut accounts = Vec::new();
+    for _ in 0..header.account_count {
+        let pubkey: String = deserialize_from(&mut decoder, path)?;
+        let data = read_bytes(&mut decoder, path)?;
+        accounts.push(WhirlpoolStateAccount { pubkey, data });
+    }
+    let program_data = read_bytes(&mut decoder, path)?;
+
+    return Ok(WhirlpoolState {
+        slot: header.slot,
+        block_height: header.block_height,
+        block_time: header.block_time,
+        accounts,
+        program_data,
+        position: header.position.map(|(transaction_index, instruction_index)| WhirlpoolStatePosition {
+            transaction_index,
+            instruction_index,
+        }),
+    });
+}
+
+pub fn write_whirlpool_state_binary<W: Write>(mut writer: W, state: &WhirlpoolState, path: &String) -> Result<(), ReplayError> {
+    write_header(&mut writer, &STATE_MAGIC, path)?;
+    let mut encoder = zstd::stream::write::Encoder::new(writer, ZSTD_LEVEL).map_err(|source| io_error(path, source))?;
+
+    let header = BinaryStateHeader {
+        slot: state.slot,
+        block_height: state.block_height,
+        block_time: state.block_time,
+        account_count: state.accounts.len() as u64,
+        position: state.position.map(|position| (position.transaction_index, position.instruction_index)),
+    };
+    serialize_into(&mut encoder, &header, path)?;
+    for account in state.accounts.iter() {
+        serialize_into(&mut encoder, &account.pubkey, path)?;
+        write_bytes(&mut encoder, &account.data, path)?;
+    }
+    write_bytes(&mut encoder, &state.program_data, path)?;
+
+    encoder.finish().map_err(|source| io_error(path, source))?.flush().map_err(|source| io_error(path, source))?;
+    return Ok(());
+}
+
+// decompression runs on the reader thread and decoding of payload on worker threads (see pipeline)
+pub fn read_whirlpool_transactions_binary<R: Read + Send + 'static>(
+    mut reader: R,
+    path: &String,
+    config: &PipelineConfig,
+) -> Result<impl Iterator<Item = Result<WhirlpoolTransaction, ReplayError>>, ReplayError> {
+    read_header(&mut reader, &TRANSACTION_MAGIC, path)?;
+    let decoder = zstd::stream::read::Decoder::new(reader).map_err(|source| io_error(path, source))?;
+
+    let records = BinaryRecords {
+        decoder,
+        path: path.clone(),
+        finished: false,
+    };
+    return Ok(run_pipeline(
+        records,
+        |binary| Ok(from_binary_whirlpool_transaction(binary)),
+        path,
+        config,
+    ));
+}
+
+// returns the number of slots written
+pub fn write_whirlpool_transactions_binary<W, I>(mut writer: W, transactions: I, path: &String) -> Result<usize, ReplayError>
+where
+    W: Write,
+    I: Iterator<Item = Result<WhirlpoolTransaction, ReplayError>>,
+{
+    write_header(&mut writer, &TRANSACTION_MAGIC, path)?;
+    let mut encoder = zstd::stream::write::Encoder::new(writer, ZSTD_LEVEL).map_err(|source| io_error(path, source))?;
+
+    let mut count = 0;
+    for whirlpool_transaction in transactions {
+        let binary = to_binary_whirlpool_transaction(&whirlpool_transaction?);
+        serialize_into(&mut encoder, &RECORD_TAG_SLOT, path)?;
+        serialize_into(&mut encoder, &binary, path)?;
+        count += 1;
+    }
+    serialize_into(&mut encoder, &RECORD_TAG_END, path)?;
+
+    encoder.finish().map_err(|source| io_error(path, source))?.flush().map_err(|source| io_error(path, source))?;
+    return Ok(count);
+}
+
+// records of the transaction body
+struct BinaryRecords<R: Read> {
+    decoder: zstd::stream::read::Decoder<'static, std::io::BufReader<R>>,
+    path: String,
+    finished: bool,
+}
+
+impl<R: Read> Iterator for BinaryRecords<R> {
+    type Item = Result<BinaryWhirlpoolTransaction, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let result = deserialize_from::<u8, _>(&mut self.decoder, &self.path).and_then(|tag| match tag {
+            RECORD_TAG_END => Ok(None),
+            RECORD_TAG_SLOT => deserialize_from(&mut self.decoder, &self.path).map(Some),
+            _ => Err(ReplayError::InvalidBinaryFile {
+                path: self.path.clone(),
+                reason: format!("unknown record tag {}", tag),
+            }),
+        });
+
+        return match result {
+            Ok(Some(binary)) => Some(Ok(binary)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+        };
+    }
+}
+
+fn to_binary_whirlpool_transaction(whirlpool_transaction: &WhirlpoolTransaction) -> BinaryWhirlpoolTransaction {
+    return BinaryWhirlpoolTransaction {
+        slot: whirlpool_transaction.slot,
+        block_height: whirlpool_transaction.block_height,
+        block_time: whirlpool_transaction.block_time,
+        transactions: whirlpool_transaction
+            .transactions
+            .iter()
+            .map(|transaction| BinaryTransaction {
+                index: transaction.index,
+                signature: transaction.signature.clone(),
+                payer: transaction.payer.clone(),
+                balances: transaction
+                    .balances
+                    .iter()
+                    .map(|balance| BinaryTransactionBalance {
+                        account: balance.account.clone(),
+                        pre: balance.pre,
+                        post: balance.post,
+                    })
+                    .collect(),
+                instructions: transaction
+                    .instructions
+                    .iter()
+                    .map(|instruction| BinaryTransactionInstruction {
+                        name: instruction.name.clone(),
+                        payload: BinaryValue::from(&instruction.payload),
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+}
+
+fn from_binary_whirlpool_transaction(binary: BinaryWhirlpoolTransaction) -> WhirlpoolTransaction {
+    let mut transactions = Vec::with_capacity(binary.transactions.len());
+    for transaction in binary.transactions {
+        let instructions = transaction
+            .instructions
+            .into_iter()
+            .map(|instruction| TransactionInstruction {
+                name: instruction.name,
+                payload: Value::from(instruction.payload),
+                decoded: None,
+            })
+            .collect();
+
+        transactions.push(Transaction {
+            index: transaction.index,
+            signature: transaction.signature,
+            payer: transaction.payer,
+            balances: transaction
+                .balances
+                .into_iter()
+                .map(|balance| TransactionBalance {
+                    account: balance.account,
+                    pre: balance.pre,
+                    post: balance.post,
+                })
+                .collect(),
+            instructions,
+        });
+    }
+
+    let mut whirlpool_transaction = WhirlpoolTransaction {
+        slot: binary.slot,
+        block_height: binary.block_height,
+        block_time: binary.block_time,
+        transactions,
+    };
+    decode_instructions(&mut whirlpool_transaction);
+    return whirlpool_transaction;
+}
+
+fn read_header<R: Read>(reader: &mut R, magic: &[u8; 4], path: &String) -> Result<(), ReplayError> {
+    let mut header = [0u8; 6];
+    reader.read_exact(&mut header).map_err(|source| io_error(path, source))?;
+
+    if header[0..4] != magic[..] {
+        return Err(ReplayError::InvalidBinaryFile {
+            path: path.clone(),
+            reason: format!("unexpected magic (expected {})", String::from_utf8_lossy(magic)),
+        });
+    }
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version != BINARY_FORMAT_VERSION {
+        return Err(ReplayError::InvalidBinaryFile {
+            path: path.clone(),
+            reason: format!("unsupported version {} (supported: {})", version, BINARY_FORMAT_VERSION),
+        });
+    }
+    return Ok(());
+}
+
+fn write_header<W: Write>(writer: &mut W, magic: &[u8; 4], path: &String) -> Result<(), ReplayError> {
+    writer.write_all(magic).map_err(|source| io_error(path, source))?;
+    writer
+        .write_all(&BINARY_FORMAT_VERSION.to_le_bytes())
+        .map_err(|source| io_error(path, source))?;
+    return Ok(());
+}
+
+// fixint encoding is the encoding of bincode::serialize (files are compatible with the default options)
+fn bincode_options() -> impl Options {
+    return bincode::options()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_VALUE_SIZE);
+}
+
+fn deserialize_from<T: serde::de::DeserializeOwned, R: Read>(reader: &mut R, path: &String) -> Result<T, ReplayError> {
+    return bincode_options().deserialize_from(reader).map_err(|source| ReplayError::InvalidBinaryData {
+        path: path.clone(),
+        source,
+    });
+}
+
+fn serialize_into<T: serde::Serialize + ?Sized, W: Write>(writer: &mut W, value: &T, path: &String) -> Result<(), ReplayError> {
+    return bincode_options().serialize_into(writer, value).map_err(|source| ReplayError::InvalidBinaryData {
+        path: path.clone(),
+        source,
+    });
+}
+
+// Vec<u8> in the same encoding as bincode (u64 length + bytes), but read and written at once
+// (bincode handles Vec<u8> byte by byte)
+fn read_bytes<R: Read>(reader: &mut R, path: &String) -> Result<Vec<u8>, ReplayError> {
+    let len: u64 = deserialize_from(reader, path)?;
+    let mut bytes = Vec::new();
+    reader
+        .take(len)
+        .read_to_end(&mut bytes)
+        .map_err(|source| io_error(path, source))?;
+    if bytes.len() as u64 != len {
+        return Err(ReplayError::InvalidBinaryFile {
+            path: path.clone(),
+            reason: "unexpected end of file".to_string(),
+        });
+    }
+    return Ok(bytes);
+}
+
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8], path: &String) -> Result<(), ReplayError> {
+    serialize_into(writer, &(bytes.len() as u64), path)?;
+    writer.write_all(bytes).map_err(|source| io_error(path, source))?;
+    return Ok(());
+}
+
+fn io_error(path: &String, source: std::io::Error) -> ReplayError {
+    return ReplayError::Io {
+        path: path.clone(),
+        source,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use crate::io;
+
+    const SAMPLE_TRANSACTION_FILE: &str =
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../data/sample_local_storage/2022/0406/whirlpool-transaction-20220406.jsonl.gz");
+
+    fn state(position: Option<WhirlpoolStatePosition>) -> WhirlpoolState {
+        return WhirlpoolState {
+            slot: 128,
+            block_height: 100,
+            block_time: 1649203200,
+            accounts: vec![
+                WhirlpoolStateAccount {
+                    pubkey: "whirlpool".to_string(),
+                    data: vec![1, 2, 3],
+                },
+                WhirlpoolStateAccount {
+                    pubkey: "position".to_string(),
+                    data: vec![],
+                },
+            ],
+            program_data: vec![0x7f, b'E', b'L', b'F'],
+            position,
+        };
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let path = "whirlpool-state.bin.zst".to_string();
+        for position in [
+            None,
+            Some(WhirlpoolStatePosition {
+                transaction_index: 3,
+                instruction_index: 1,
+            }),
+        ] {
+            let mut buffer = Vec::new();
+            write_whirlpool_state_binary(&mut buffer, &state(position), &path).unwrap();
+            assert_eq!(&buffer[0..4], b"WRPS");
+            assert_eq!(read_whirlpool_state_binary(buffer.as_slice(), &path).unwrap(), state(position));
+        }
+    }
+
+    #[test]
+    fn test_state_with_corrupt_account_count() {
+        let path = "whirlpool-state.bin.zst".to_string();
+        let mut buffer = Vec::new();
+        write_header(&mut buffer, &STATE_MAGIC, &path).unwrap();
+        let mut encoder = zstd::stream::write::Encoder::new(&mut buffer, ZSTD_LEVEL).unwrap();
+        let header = BinaryStateHeader {
+            slot: 1,
+            block_height: 1,
+            block_time: 1,
+            account_count: u64::MAX,
+            position: None,
+        };
+        serialize_into(&mut encoder, &header, &path).unwrap();
+        encoder.finish().unwrap();
+
+        // fails at the end of the data (no allocation for u64::MAX accounts)
+        assert!(read_whirlpool_state_binary(buffer.as_slice(), &path).is_err());
+    }
+
+    #[test]
+    fn test_state_with_corrupt_string_length() {
+        let path = "whirlpool-state.bin.zst".to_string();
+        let mut buffer = Vec::new();
+        write_header(&mut buffer, &STATE_MAGIC, &path).unwrap();
+        let mut encoder = zstd::stream::write::Encoder::new(&mut buffer, ZSTD_LEVEL).unwrap();
+        let header = BinaryStateHeader {
+            slot: 1,
+            block_height: 1,
+            block_time: 1,
+            account_count: 1,
+            position: None,
+        };
+        serialize_into(&mut encoder, &header, &path).unwrap();
+        // pubkey with a length prefix of u64::MAX
+        encoder.write_all(&u64::MAX.to_le_bytes()).unwrap();
+        encoder.write_all(b"whirlpool").unwrap();
+        encoder.finish().unwrap();
+
+        // rejected by the size limit (no allocation for the length)
+        assert!(matches!(
+            read_whirlpool_state_binary(buffer.as_slice(), &path),
+            Err(ReplayError::InvalidBinaryData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_transactions_with_corrupt_string_length() {
+        let path = "whirlpool-transaction.bin.zst".to_string();
+        let mut buffer = Vec::new();
+        write_header(&mut buffer, &TRANSACTION_MAGIC, &path).unwrap();
+        let mut encoder = zstd::stream::write::Encoder::new(&mut buffer, ZSTD_LEVEL).unwrap();
+        serialize_into(&mut encoder, &RECORD_TAG_SLOT, &path).unwrap();
+        // slot, block_height, block_time, a transaction with index 0
+        serialize_into(&mut encoder, &(1u64, 1u64, 1i64, 1u64, 0u32), &path).unwrap();
+        // signature with a length prefix of u64::MAX
+        encoder.write_all(&u64::MAX.to_le_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let mut iter = read_whirlpool_transactions_binary(Cursor::new(buffer), &path, &PipelineConfig::default()).unwrap();
+        assert!(matches!(iter.next(), Some(Err(ReplayError::InvalidBinaryData { .. }))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_state_with_unsupported_version() {
+        let path = "whirlpool-state.bin.zst".to_string();
+        let mut buffer = Vec::new();
+        write_whirlpool_state_binary(&mut buffer, &state(None), &path).unwrap();
+        buffer[4..6].copy_from_slice(&1u16.to_le_bytes());
+        assert!(matches!(
+            read_whirlpool_state_binary(buffer.as_slice(), &path),
+            Err(ReplayError::InvalidBinaryFile { .. })
+        ));
+    }
+
+    #[test]
+    fn test_binary_value_round_trip() {
+        let value = serde_json::json!({
+            "dataAmount": "18446744073709551615",
+            "dataAToB": 1,
+            "dataTickLowerIndex": -443584,
+            "dataRatio": 0.5,
+            "dataFlag": true,
+            "dataNone": null,
+            "remainingAccounts": [["a", 1], []],
+        });
+        assert_eq!(Value::from(BinaryValue::from(&value)), value);
+    }
+
+    #[test]
+    fn test_transactions_round_trip() {
+        let json_path = SAMPLE_TRANSACTION_FILE.to_string();
+        let load_json = || io::read_whirlpool_transactions(io::open_local_file(&json_path).unwrap(), &json_path);
+        let expected: Vec<WhirlpoolTransaction> = load_json().map(|transaction| transaction.unwrap()).collect();
+
+        let binary_path = "whirlpool-transaction.bin.zst".to_string();
+        let mut buffer = Vec::new();
+        let count = write_whirlpool_transactions_binary(&mut buffer, load_json(), &binary_path).unwrap();
+        assert_eq!(count, expected.len());
+
+        let transactions: Vec<WhirlpoolTransaction> =
+            read_whirlpool_transactions_binary(Cursor::new(buffer), &binary_path, &PipelineConfig::default())
+                .unwrap()
+                .map(|transaction| transaction.unwrap())
+                .collect();
+        assert_eq!(transactions, expected);
+        // instructions are decoded by the pipeline
+        assert!(transactions
+            .iter()
+            .flat_map(|transaction| transaction.transactions.iter())
+            .flat_map(|transaction| transaction.instructions.iter())
+            .all(|instruction| instruction.decoded.is_some()));
+    }
+}
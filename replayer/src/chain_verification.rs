@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+
+use crate::errors::ReplayError;
+use crate::state_comparison::{compare_states, StateComparison};
+use crate::storage::StorageBackend;
+use crate::{parse_yyyymmdd, ReplayUntil, WhirlpoolReplayer};
+
+// Chain verification audits the published data set with the key formula:
+//
+//   state(D-1) + transactions(D) = state(D)
+//
+// each day is replayed independently from the published state of the previous day,
+// and the replayed state is compared with the published state of the day account by account.
+pub struct DayVerification {
+    pub date: NaiveDate,
+    // Err if the day could not be replayed or compared (e.g. missing file, slot mismatch)
+    pub result: Result<DayVerificationResult, ReplayError>,
+}
+
+pub struct DayVerificationResult {
+    pub slot: u64,
+    pub comparison: StateComparison,
+    pub program_data_matched: bool,
+    // instructions failed while replaying (failed instructions are not written back)
+    pub failed_instructions: u64,
+}
+
+impl DayVerification {
+    pub fn is_passed(&self) -> bool {
+        return match self.result.as_ref() {
+            Ok(result) => result.comparison.is_identical() && result.program_data_matched && result.failed_instructions == 0,
+            Err(_) => false,
+        };
+    }
+}
+
+// verify the days from start_yyyymmdd to end_yyyymmdd (inclusive) one by one.
+// a failure of a day doesn't stop the verification of the following days.
+pub struct ChainVerifier {
+    storage: Arc<dyn StorageBackend>,
+    next_date: Option<NaiveDate>,
+    end: NaiveDate,
+}
+
+impl ChainVerifier {
+    pub fn new(
+        storage: Arc<dyn StorageBackend>,
+        start_yyyymmdd: &String,
+        end_yyyymmdd: &String,
+    ) -> Result<ChainVerifier, ReplayError> {
+        let start = parse_yyyymmdd(start_yyyymmdd)?;
+        let end = parse_yyyymmdd(end_yyyymmdd)?;
+        if end < start {
+            return Err(ReplayError::InvalidDateRange {
+                start: start_yyyymmdd.clone(),
+                end: end_yyyymmdd.clone(),
+            });
+        }
+
+        return Ok(ChainVerifier {
+            storage,
+            next_date: Some(start),
+            end,
+        });
+    }
+}
+
+impl Iterator for ChainVerifier {
+    type Item = DayVerification;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let date = self.next_date.filter(|date| *date <= self.end)?;
+        self.next_date = date.succ_opt();
+
+        return Some(DayVerification {
+            date,
+            result: verify_day(self.storage.clone(), &date),
+        });
+    }
+}
+
+// replay the transactions of the day from the state of the previous day and compare with the state of the day
+pub fn verify_day(storage: Arc<dyn StorageBackend>, date: &NaiveDate) -> Result<DayVerificationResult, ReplayError> {
+    let yyyymmdd = date.format("%Y%m%d").to_string();
+    let mut replayer = WhirlpoolReplayer::build_with_storage(storage.clone(), &yyyymmdd, Some(&yyyymmdd))?;
+    replayer.replay(ReplayUntil::End, None, None, None)?;
+
+    let replayed = replayer.get_whirlpool_state();
+    let published = storage.load_whirlpool_state(date)?;
+    let comparison = compare_states(&replayed, &published)?;
+
+    return Ok(DayVerificationResult {
+        slot: replayed.slot,
+        comparison,
+        program_data_matched: replayed.program_data == published.program_data,
+        failed_instructions: replayer.get_failure_count(),
+    });
+}
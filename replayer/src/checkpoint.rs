@@ -0,0 +1,258 @@
+use std::collections::VecDeque;
+
+use crate::errors::ReplayError;
+use crate::io;
+use crate::schema::WhirlpoolState;
+use crate::Slot;
+
+const CHECKPOINT_FILE_PREFIX: &str = "whirlpool-checkpoint-";
+const CHECKPOINT_FILE_SUFFIX: &str = ".json.gz";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CheckpointInterval {
+    Slots(u64),
+    BlockTimeSeconds(i64),
+    Instructions(u64),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CheckpointConfig {
+    pub dir_path: String,
+    pub interval: CheckpointInterval,
+    // keep only the latest N checkpoints written by the replayer (None: keep all)
+    pub retention: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Checkpoint {
+    pub slot: Slot,
+    pub file_path: String,
+}
+
+// Checkpointer writes the state at the end of a slot when the interval has elapsed.
+// Checkpoints are always taken at slot boundaries, so they can be resumed without double-applying.
+pub struct Checkpointer {
+    config: CheckpointConfig,
+    last_slot: Slot,
+    instructions_since_last: u64,
+    checkpoints: VecDeque<Checkpoint>,
+}
+
+impl Checkpointer {
+    pub fn new(config: CheckpointConfig, initial_slot: Slot) -> Checkpointer {
+        return Checkpointer {
+            config,
+            last_slot: initial_slot,
+            instructions_since_last: 0,
+            checkpoints: VecDeque::new(),
+        };
+    }
+
+    pub fn get_config(&self) -> &CheckpointConfig {
+        return &self.config;
+    }
+
+    pub fn get_checkpoints(&self) -> &VecDeque<Checkpoint> {
+        return &self.checkpoints;
+    }
+
+    pub fn on_instruction_replayed(&mut self) {
+        self.instructions_since_last += 1;
+    }
+
+    pub fn is_due(&self, slot: &Slot) -> bool {
+        match self.config.interval {
+            // slot may be earlier than the last checkpoint (e.g. resumed from an older state)
+            CheckpointInterval::Slots(slots) => slot.slot.saturating_sub(self.last_slot.slot) >= slots,
+            CheckpointInterval::BlockTimeSeconds(seconds) => {
+                slot.block_time.saturating_sub(self.last_slot.block_time) >= seconds
+            }
+            CheckpointInterval::Instructions(instructions) => self.instructions_since_last >= instructions,
+        }
+    }
+
+    pub fn write(&mut self, state: &WhirlpoolState) -> Result<&Checkpoint, ReplayError> {
+        let file_path = get_checkpoint_file_path(&self.config.dir_path, state.slot);
+        std::fs::create_dir_all(&self.config.dir_path).map_err(|source| ReplayError::Io {
+            path: self.config.dir_path.clone(),
+            source,
+        })?;
+
+        // write to a temporary file and rename it, so a crash never leaves a broken checkpoint
+        let tmp_file_path = format!("{}.tmp", file_path);
+        io::save_to_whirlpool_state_file(&tmp_file_path, state)?;
+        std::fs::rename(&tmp_file_path, &file_path).map_err(|source| ReplayError::Io {
+            path: file_path.clone(),
+            source,
+        })?;
+
+        let slot = Slot {
+            slot: state.slot,
+            block_height: state.block_height,
+            block_time: state.block_time,
+        };
+        self.last_slot = slot;
+        self.instructions_since_last = 0;
+        self.checkpoints.push_back(Checkpoint { slot, file_path });
+
+        if let Some(retention) = self.config.retention {
+            while self.checkpoints.len() > retention {
+                let expired = self.checkpoints.pop_front().unwrap();
+                std::fs::remove_file(&expired.file_path).map_err(|source| ReplayError::Io {
+                    path: expired.file_path.clone(),
+                    source,
+                })?;
+            }
+        }
+
+        return Ok(self.checkpoints.back().unwrap());
+    }
+}
+
+pub fn get_checkpoint_file_path(dir_path: &String, slot: u64) -> String {
+    return format!("{}/{}{}{}", dir_path, CHECKPOINT_FILE_PREFIX, slot, CHECKPOINT_FILE_SUFFIX);
+}
+
+// returns (slot, file path) of the checkpoints in the directory (sorted by slot)
+pub fn list_checkpoint_files(dir_path: &String) -> Result<Vec<(u64, String)>, ReplayError> {
+    let io_error = |source: std::io::Error| ReplayError::Io {
+        path: dir_path.clone(),
+        source,
+    };
+
+    let mut checkpoints = Vec::new();
+    for entry in std::fs::read_dir(dir_path).map_err(io_error)? {
+        let file_name = entry.map_err(io_error)?.file_name();
+        let slot = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix(CHECKPOINT_FILE_PREFIX))
+            .and_then(|name| name.strip_suffix(CHECKPOINT_FILE_SUFFIX))
+            .and_then(|slot| slot.parse::<u64>().ok());
+        if let Some(slot) = slot {
+            checkpoints.push((slot, get_checkpoint_file_path(dir_path, slot)));
+        }
+    }
+
+    checkpoints.sort();
+    return Ok(checkpoints);
+}
+
+// the latest checkpoint at or before the slot (useful for point-in-time queries)
+pub fn find_checkpoint_file(dir_path: &String, slot: u64) -> Result<Option<(u64, String)>, ReplayError> {
+    let checkpoints = list_checkpoint_files(dir_path)?;
+    return Ok(checkpoints.into_iter().rev().find(|(checkpoint_slot, _)| *checkpoint_slot <= slot));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_temp_dir(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("whirlpool-replayer-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        return path.to_string_lossy().to_string();
+    }
+
+    fn build_slot(slot: u64) -> Slot {
+        return Slot {
+            slot,
+            block_height: slot,
+            block_time: slot as i64 * 10,
+        };
+    }
+
+    fn build_state(slot: u64) -> WhirlpoolState {
+        return WhirlpoolState {
+            slot,
+            block_height: slot,
+            block_time: slot as i64 * 10,
+            accounts: Vec::new(),
+            program_data: Vec::new(),
+            position: None,
+        };
+    }
+
+    #[test]
+    fn test_checkpoint_interval() {
+        let config = |interval: CheckpointInterval| CheckpointConfig {
+            dir_path: "unused".to_string(),
+            interval,
+            retention: None,
+        };
+
+        let checkpointer = Checkpointer::new(config(CheckpointInterval::Slots(100)), build_slot(1000));
+        assert!(!checkpointer.is_due(&build_slot(1099)));
+        assert!(checkpointer.is_due(&build_slot(1100)));
+
+        let checkpointer = Checkpointer::new(config(CheckpointInterval::BlockTimeSeconds(60)), build_slot(1000));
+        assert!(!checkpointer.is_due(&build_slot(1005)));
+        assert!(checkpointer.is_due(&build_slot(1006)));
+
+        // earlier slot (e.g. resumed from an older state)
+        let checkpointer = Checkpointer::new(config(CheckpointInterval::Slots(100)), build_slot(1000));
+        assert!(!checkpointer.is_due(&build_slot(900)));
+        let checkpointer = Checkpointer::new(config(CheckpointInterval::BlockTimeSeconds(60)), build_slot(1000));
+        assert!(!checkpointer.is_due(&build_slot(900)));
+
+        let mut checkpointer = Checkpointer::new(config(CheckpointInterval::Instructions(2)), build_slot(1000));
+        checkpointer.on_instruction_replayed();
+        assert!(!checkpointer.is_due(&build_slot(1001)));
+        checkpointer.on_instruction_replayed();
+        assert!(checkpointer.is_due(&build_slot(1001)));
+    }
+
+    #[test]
+    fn test_checkpoint_retention() {
+        let dir_path = create_temp_dir("checkpoint-retention");
+        let config = CheckpointConfig {
+            dir_path: dir_path.clone(),
+            interval: CheckpointInterval::Slots(100),
+            retention: Some(2),
+        };
+
+        // a checkpoint not written by this checkpointer is kept
+        std::fs::create_dir_all(&dir_path).unwrap();
+        io::save_to_whirlpool_state_file(&get_checkpoint_file_path(&dir_path, 900), &build_state(900)).unwrap();
+
+        let mut checkpointer = Checkpointer::new(config, build_slot(1000));
+        for slot in [1100, 1200, 1300] {
+            assert!(checkpointer.is_due(&build_slot(slot)));
+            let checkpoint = checkpointer.write(&build_state(slot)).unwrap();
+            assert_eq!(checkpoint.slot, build_slot(slot));
+            assert!(!checkpointer.is_due(&build_slot(slot + 99)));
+        }
+
+        let slots: Vec<u64> = checkpointer.get_checkpoints().iter().map(|checkpoint| checkpoint.slot.slot).collect();
+        assert_eq!(slots, vec![1200, 1300]);
+
+        let files: Vec<u64> = list_checkpoint_files(&dir_path).unwrap().into_iter().map(|(slot, _)| slot).collect();
+        assert_eq!(files, vec![900, 1200, 1300]);
+
+        let latest = io::load_from_local_whirlpool_state_file(&get_checkpoint_file_path(&dir_path, 1300)).unwrap();
+        assert_eq!(latest, build_state(1300));
+
+        std::fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_find_checkpoint_file() {
+        let dir_path = create_temp_dir("checkpoint-find");
+        std::fs::create_dir_all(&dir_path).unwrap();
+        for slot in [100, 300, 200] {
+            io::save_to_whirlpool_state_file(&get_checkpoint_file_path(&dir_path, slot), &build_state(slot)).unwrap();
+        }
+        // other files (e.g. a temporary file of an interrupted write) are ignored
+        std::fs::write(format!("{}.tmp", get_checkpoint_file_path(&dir_path, 400)), b"").unwrap();
+        std::fs::write(format!("{}/whirlpool-checkpoint-latest.json.gz", dir_path), b"").unwrap();
+
+        let slots: Vec<u64> = list_checkpoint_files(&dir_path).unwrap().into_iter().map(|(slot, _)| slot).collect();
+        assert_eq!(slots, vec![100, 200, 300]);
+
+        assert_eq!(find_checkpoint_file(&dir_path, 99).unwrap(), None);
+        assert_eq!(find_checkpoint_file(&dir_path, 200).unwrap(), Some((200, get_checkpoint_file_path(&dir_path, 200))));
+        assert_eq!(find_checkpoint_file(&dir_path, 299).unwrap(), Some((200, get_checkpoint_file_path(&dir_path, 200))));
+        assert_eq!(find_checkpoint_file(&dir_path, 1000).unwrap(), Some((300, get_checkpoint_file_path(&dir_path, 300))));
+
+        std::fs::remove_dir_all(&dir_path).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+use thiserror::Error;
+
+pub use replay_engine::errors::ErrorCode;
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("invalid date (expected yyyymmdd): {0}")]
+    InvalidDate(String),
+
+    #[error("invalid date range: {start} - {end}")]
+    InvalidDateRange { start: String, end: String },
+
+    #[error("failed to access {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("failed to fetch {url}: {source}")]
+    Http {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+
+    #[error("incomplete download {path}: {size} of {expected_size} bytes")]
+    IncompleteDownload {
+        path: String,
+        size: u64,
+        expected_size: u64,
+    },
+
+    #[error("corrupt cache entry {path}: {reason}")]
+    CorruptCacheEntry { path: String, reason: String },
+
+    #[error("invalid manifest {path}: {source}")]
+    InvalidManifest {
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("invalid whirlpool state file {path}: {source}")]
+    InvalidStateFile {
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("invalid whirlpool transaction file {path} (line {line}): {source}")]
+    InvalidTransactionFile {
+        path: String,
+        line: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("invalid binary file {path}: {reason}")]
+    InvalidBinaryFile { path: String, reason: String },
+
+    #[error("invalid binary data {path}: {source}")]
+    InvalidBinaryData {
+        path: String,
+        #[source]
+        source: bincode::Error,
+    },
+
+    #[error("invalid snapshot file {path} (line {line}): {reason}")]
+    InvalidSnapshotFile {
+        path: String,
+        line: usize,
+        reason: String,
+    },
+
+    #[error("invalid program file (not ELF): {0}")]
+    InvalidProgramFile(String),
+
+    #[error("invalid S3 endpoint {endpoint}: {reason}")]
+    InvalidS3Endpoint { endpoint: String, reason: String },
+
+    #[error("invalid RPC response from {url}: {reason}")]
+    InvalidRpcResponse { url: String, reason: String },
+
+    #[error("states at different slots are compared: replayed {replayed}, reference {reference}")]
+    StateSlotMismatch { replayed: u64, reference: u64 },
+
+    #[error("state delta is not for the base state: base slot {base_slot}, base slot of delta {delta_base_slot}")]
+    StateDeltaBaseMismatch { base_slot: u64, delta_base_slot: u64 },
+
+    #[error("database error: {source}")]
+    Database {
+        #[source]
+        source: mysql::Error,
+    },
+
+    #[error("no slots found in the database for {0}")]
+    NoSlotsInDatabase(String),
+
+    #[error("invalid instruction json in the database {name} (txid={txid}): {source}")]
+    InvalidDatabaseRow {
+        txid: u64,
+        name: String,
+        #[source]
+        source: ErrorCode,
+    },
+
+    #[error("failed to decode instruction {name} (slot={slot}, signature={signature}): {source}")]
+    InvalidInstruction {
+        slot: u64,
+        signature: String,
+        name: String,
+        #[source]
+        source: ErrorCode,
+    },
+
+    #[error("failed to replay instruction {name} (slot={slot}, signature={signature}): {source}")]
+    InstructionReplayFailed {
+        slot: u64,
+        signature: String,
+        name: String,
+        #[source]
+        source: ErrorCode,
+    },
+}
+
+impl ReplayError {
+    // missing file (local), 404 (remote) or no slots of the day (database)
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            ReplayError::NoSlotsInDatabase(_) => true,
+            ReplayError::Io { source, .. } => source.kind() == std::io::ErrorKind::NotFound,
+            ReplayError::Http { source, .. } => {
+                source.status() == Some(reqwest::StatusCode::NOT_FOUND)
+            }
+            _ => false,
+        }
+    }
+
+    // temporary failure that may succeed on retry
+    // (network error, 5xx, 429, interrupted or corrupt download)
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ReplayError::Io { source, .. } => !matches!(
+                source.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied
+            ),
+            ReplayError::Http { source, .. } => match source.status() {
+                Some(status) => status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+                None => true,
+            },
+            ReplayError::IncompleteDownload { .. } => true,
+            ReplayError::CorruptCacheEntry { .. } => true,
+            _ => false,
+        }
+    }
+}
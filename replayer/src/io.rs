@@ -2,11 +2,43 @@ use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use reqwest;
 use std::{
+    collections::{BTreeMap, HashMap, HashSet},
     fs::File,
-    io::{BufRead, BufReader, BufWriter},
+    io::{BufReader, BufWriter, Read, Write},
 };
 
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use replay_engine::account_kind::{classify_account, AccountKind};
+use replay_engine::pubkeys::ORCA_WHIRLPOOL_PROGRAM_ID;
+use replay_engine::types::Slot;
+
+use crate::binary_format::{
+    read_whirlpool_state_binary, read_whirlpool_transactions_binary, write_whirlpool_state_binary,
+    write_whirlpool_transactions_binary, BINARY_FILE_EXTENSION,
+};
+use crate::errors::ReplayError;
+use crate::pipeline::{read_whirlpool_transactions_pipelined, PipelineConfig};
 use crate::schema::*;
+use crate::state_delta;
+use crate::WhirlpoolTransactionIterator;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    // gzipped JSON (state) or JSON lines (transactions)
+    Json,
+    // see binary_format
+    Binary,
+}
+
+impl FileFormat {
+    // *.bin.zst is binary, others are JSON (*.json.gz, *.jsonl.gz)
+    pub fn from_path(path: &String) -> FileFormat {
+        if path.ends_with(BINARY_FILE_EXTENSION) {
+            return FileFormat::Binary;
+        }
+        return FileFormat::Json;
+    }
+}
 
 pub fn get_whirlpool_state_file_relative_path(date: &chrono::NaiveDate) -> String {
     format!(
@@ -17,6 +49,15 @@ pub fn get_whirlpool_state_file_relative_path(date: &chrono::NaiveDate) -> Strin
     )
 }
 
+pub fn get_whirlpool_state_delta_file_relative_path(date: &chrono::NaiveDate) -> String {
+    format!(
+        "{}/{}/whirlpool-state-delta-{}.json.gz",
+        date.format("%Y"),
+        date.format("%m%d"),
+        date.format("%Y%m%d"),
+    )
+}
+
 pub fn get_whirlpool_transaction_file_relative_path(date: &chrono::NaiveDate) -> String {
     format!(
         "{}/{}/whirlpool-transaction-{}.jsonl.gz",
@@ -26,64 +67,1019 @@ pub fn get_whirlpool_transaction_file_relative_path(date: &chrono::NaiveDate) ->
     )
 }
 
-pub fn load_from_local_whirlpool_state_file(file_path: &String) -> WhirlpoolState {
-    let file = File::open(file_path).unwrap();
-    let decoder = GzDecoder::new(file);
+// whirlpool-state-{yyyymmdd}.json.gz -> whirlpool-state-{yyyymmdd}.bin.zst (same for transaction files)
+pub fn get_binary_file_relative_path(relative_path: &String) -> String {
+    let stem = relative_path
+        .strip_suffix(".json.gz")
+        .or_else(|| relative_path.strip_suffix(".jsonl.gz"))
+        .unwrap_or(relative_path);
+    return format!("{}{}", stem, BINARY_FILE_EXTENSION);
+}
+
+pub fn load_from_local_whirlpool_state_file(
+    file_path: &String,
+) -> Result<WhirlpoolState, ReplayError> {
+    let file = open_local_file(file_path)?;
+    return read_whirlpool_state(file, file_path);
+}
+
+// read state file, the format is detected from the extension of path (path is also used in error messages)
+pub fn read_whirlpool_state<R: Read>(reader: R, path: &String) -> Result<WhirlpoolState, ReplayError> {
+    if FileFormat::from_path(path) == FileFormat::Binary {
+        return read_whirlpool_state_binary(reader, path);
+    }
+
+    let decoder = GzDecoder::new(reader);
     let reader = BufReader::new(decoder);
-    return serde_json::from_reader(reader).unwrap();
+    return serde_json::from_reader(reader).map_err(|source| ReplayError::InvalidStateFile {
+        path: path.clone(),
+        source,
+    });
 }
 
-pub fn save_to_whirlpool_state_file(file_path: &String, state: &WhirlpoolState) {
-    let file = File::create(file_path).unwrap();
+pub fn save_to_whirlpool_state_file(
+    file_path: &String,
+    state: &WhirlpoolState,
+) -> Result<(), ReplayError> {
+    let io_error = |source: std::io::Error| ReplayError::Io {
+        path: file_path.clone(),
+        source,
+    };
+
+    let file = File::create(file_path).map_err(io_error)?;
+    if FileFormat::from_path(file_path) == FileFormat::Binary {
+        return write_whirlpool_state_binary(BufWriter::new(file), state, file_path);
+    }
+
     let encoder = GzEncoder::new(file, flate2::Compression::default());
-    let writer = BufWriter::new(encoder);
-    serde_json::to_writer(writer, state).unwrap();
+    let mut writer = BufWriter::new(encoder);
+    serde_json::to_writer(&mut writer, state).map_err(|source| ReplayError::Io {
+        path: file_path.clone(),
+        source: source.into(),
+    })?;
+
+    // flush explicitly to surface write errors (drop would ignore them)
+    let encoder = writer.into_inner().map_err(|err| io_error(err.into_error()))?;
+    encoder.finish().map_err(io_error)?;
+    return Ok(());
 }
 
-pub fn load_from_remote_whirlpool_state_file(url: &String) -> WhirlpoolState {
-    let response = reqwest::blocking::get(url).unwrap();
-    let decoder = GzDecoder::new(response);
-    let reader = BufReader::new(decoder);
-    return serde_json::from_reader(reader).unwrap();
+pub fn load_from_remote_whirlpool_state_file(url: &String) -> Result<WhirlpoolState, ReplayError> {
+    let response = fetch_remote_file(url)?;
+    return read_whirlpool_state(response, url);
 }
 
 pub fn load_from_local_whirlpool_transaction_file(
     file_path: &String,
-) -> impl Iterator<Item = WhirlpoolTransaction> {
-    let file = File::open(file_path).unwrap();
+) -> Result<impl Iterator<Item = Result<WhirlpoolTransaction, ReplayError>>, ReplayError> {
+    let file = open_local_file(file_path)?;
+    return Ok(read_whirlpool_transactions(file, file_path));
+}
+
+pub fn load_from_remote_whirlpool_transaction_file(
+    url: &String,
+) -> Result<impl Iterator<Item = Result<WhirlpoolTransaction, ReplayError>>, ReplayError> {
+    let response = fetch_remote_file(url)?;
+    return Ok(read_whirlpool_transactions(response, url));
+}
+
+// read transaction file, the format is detected from the extension of path (path is also used in error messages).
+// decompression, parsing and instruction decoding run on background threads (see pipeline).
+pub fn read_whirlpool_transactions<R: Read + Send + 'static>(
+    reader: R,
+    path: &String,
+) -> WhirlpoolTransactionIterator {
+    let config = PipelineConfig::default();
+    if FileFormat::from_path(path) == FileFormat::Binary {
+        return match read_whirlpool_transactions_binary(reader, path, &config) {
+            Ok(transaction_iter) => Box::new(transaction_iter),
+            Err(err) => Box::new(std::iter::once(Err(err))),
+        };
+    }
+    return Box::new(read_whirlpool_transactions_pipelined(reader, path, &config));
+}
+
+// write transactions into the file in the format detected from the extension.
+// returns the number of slots written.
+pub fn save_to_whirlpool_transaction_file<I>(file_path: &String, transactions: I) -> Result<usize, ReplayError>
+where
+    I: Iterator<Item = Result<WhirlpoolTransaction, ReplayError>>,
+{
+    let io_error = |source: std::io::Error| ReplayError::Io {
+        path: file_path.clone(),
+        source,
+    };
 
-    let decoder = GzDecoder::new(file);
-    let buf = BufReader::new(decoder);
+    let file = File::create(file_path).map_err(io_error)?;
+    if FileFormat::from_path(file_path) == FileFormat::Binary {
+        return write_whirlpool_transactions_binary(BufWriter::new(file), transactions, file_path);
+    }
 
-    let iter = buf.lines().map(|jsonl| jsonl.unwrap()).map(|jsonl| {
-        let t: Result<WhirlpoolTransaction, serde_json::Error> =
-            serde_json::from_str(jsonl.as_str());
-        return t.unwrap();
+    let mut writer = BufWriter::new(GzEncoder::new(file, flate2::Compression::default()));
+    let mut count = 0;
+    for whirlpool_transaction in transactions {
+        serde_json::to_writer(&mut writer, &whirlpool_transaction?).map_err(|source| io_error(source.into()))?;
+        writer.write_all(b"\n").map_err(io_error)?;
+        count += 1;
+    }
+
+    // flush explicitly to surface write errors (drop would ignore them)
+    let encoder = writer.into_inner().map_err(|err| io_error(err.into_error()))?;
+    encoder.finish().map_err(io_error)?;
+    return Ok(count);
+}
+
+// convert state file between JSON and binary formats (detected from the extensions)
+pub fn convert_whirlpool_state_file(input_file_path: &String, output_file_path: &String) -> Result<(), ReplayError> {
+    let state = load_from_local_whirlpool_state_file(input_file_path)?;
+    return save_to_whirlpool_state_file(output_file_path, &state);
+}
+
+pub fn load_from_local_whirlpool_state_delta_file(
+    file_path: &String,
+) -> Result<WhirlpoolStateDelta, ReplayError> {
+    let file = open_local_file(file_path)?;
+    return read_whirlpool_state_delta(file, file_path);
+}
+
+// read gzipped state delta file (path is used in error messages)
+pub fn read_whirlpool_state_delta<R: Read>(reader: R, path: &String) -> Result<WhirlpoolStateDelta, ReplayError> {
+    let decoder = GzDecoder::new(reader);
+    let reader = BufReader::new(decoder);
+    return serde_json::from_reader(reader).map_err(|source| ReplayError::InvalidStateFile {
+        path: path.clone(),
+        source,
     });
+}
 
-    return iter;
+pub fn save_to_whirlpool_state_delta_file(
+    file_path: &String,
+    delta: &WhirlpoolStateDelta,
+) -> Result<(), ReplayError> {
+    let io_error = |source: std::io::Error| ReplayError::Io {
+        path: file_path.clone(),
+        source,
+    };
+
+    let file = File::create(file_path).map_err(io_error)?;
+    let encoder = GzEncoder::new(file, flate2::Compression::default());
+    let mut writer = BufWriter::new(encoder);
+    serde_json::to_writer(&mut writer, delta).map_err(|source| ReplayError::Io {
+        path: file_path.clone(),
+        source: source.into(),
+    })?;
+
+    let encoder = writer.into_inner().map_err(|err| io_error(err.into_error()))?;
+    encoder.finish().map_err(io_error)?;
+    return Ok(());
 }
 
-pub fn load_from_remote_whirlpool_transaction_file(
+// write the delta from the base state file to the state file (e.g. the states of 2 consecutive days)
+pub fn convert_whirlpool_state_file_to_delta(
+    base_file_path: &String,
+    input_file_path: &String,
+    output_file_path: &String,
+) -> Result<WhirlpoolStateDelta, ReplayError> {
+    let base = load_from_local_whirlpool_state_file(base_file_path)?;
+    let state = load_from_local_whirlpool_state_file(input_file_path)?;
+    let delta = state_delta::build_state_delta(&base, &state);
+    save_to_whirlpool_state_delta_file(output_file_path, &delta)?;
+    return Ok(delta);
+}
+
+// write the full state reconstructed from the base state file and the delta files (in the order of dates)
+pub fn convert_whirlpool_state_delta_files_to_state(
+    base_file_path: &String,
+    delta_file_paths: &[String],
+    output_file_path: &String,
+) -> Result<WhirlpoolState, ReplayError> {
+    let mut state = load_from_local_whirlpool_state_file(base_file_path)?;
+    for delta_file_path in delta_file_paths {
+        let delta = load_from_local_whirlpool_state_delta_file(delta_file_path)?;
+        state = state_delta::apply_state_delta(state, &delta)?;
+    }
+    save_to_whirlpool_state_file(output_file_path, &state)?;
+    return Ok(state);
+}
+
+// convert transaction file between JSON and binary formats (detected from the extensions).
+// returns the number of slots converted.
+pub fn convert_whirlpool_transaction_file(input_file_path: &String, output_file_path: &String) -> Result<usize, ReplayError> {
+    let transactions = load_from_local_whirlpool_transaction_file(input_file_path)?;
+    return save_to_whirlpool_transaction_file(output_file_path, transactions);
+}
+
+/*
+
+Account Snapshot CSV (whirlpool-snapshot-{slot}.csv.gz)
+
+Accounts owned by Whirlpool program extracted from Jito/Solana-Labs snapshots (solana-snapshot-gpa).
+A snapshot file is GZIP compressed CSV file without header in one of the following variants:
+
+  short:        pubkey(base58),data(base64)
+  full-columns: pubkey(base58),owner(base58),data_len,lamports,write_version,data(base64)
+
+the snapshot doesn't contain block height, block time and program data.
+
+*/
+
+pub struct SnapshotImportSummary {
+    // number of imported accounts by kind
+    pub imported: HashMap<AccountKind, usize>,
+    // pubkeys of the accounts that are not whirlpool state accounts (e.g. IDL accounts)
+    pub dropped: Vec<String>,
+}
+
+// slot in the file name (whirlpool-snapshot-{slot}.csv.gz)
+pub fn get_snapshot_slot_from_file_path(file_path: &String) -> Option<u64> {
+    let file_name = std::path::Path::new(file_path).file_name()?.to_str()?;
+    return file_name
+        .strip_prefix("whirlpool-snapshot-")?
+        .strip_suffix(".csv.gz")?
+        .parse::<u64>()
+        .ok();
+}
+
+// build WhirlpoolState from account snapshot CSV and program (.so) files.
+// accounts are validated by anchor discriminator, and accounts other than whirlpool state accounts are dropped.
+pub fn load_from_local_snapshot_csv_file(
+    snapshot_file_path: &String,
+    program_file_path: &String,
+    slot: &Slot,
+) -> Result<(WhirlpoolState, SnapshotImportSummary), ReplayError> {
+    let program_data = std::fs::read(program_file_path).map_err(|source| ReplayError::Io {
+        path: program_file_path.clone(),
+        source,
+    })?;
+    if !program_data.starts_with(b"\x7fELF") {
+        return Err(ReplayError::InvalidProgramFile(program_file_path.clone()));
+    }
+
+    let file = open_local_file(snapshot_file_path)?;
+    return read_snapshot_csv(file, snapshot_file_path, program_data, slot);
+}
+
+// read gzipped account snapshot CSV (path is used in error messages)
+pub fn read_snapshot_csv<R: Read>(
+    reader: R,
+    path: &String,
+    program_data: Vec<u8>,
+    slot: &Slot,
+) -> Result<(WhirlpoolState, SnapshotImportSummary), ReplayError> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(GzDecoder::new(reader));
+
+    let whirlpool_program_id = ORCA_WHIRLPOOL_PROGRAM_ID.to_string();
+    let invalid_line = |line: usize, reason: String| ReplayError::InvalidSnapshotFile {
+        path: path.clone(),
+        line,
+        reason,
+    };
+
+    // sorted by pubkey
+    let mut accounts = BTreeMap::new();
+    let mut imported = HashMap::new();
+    let mut dropped = HashSet::new();
+    for (index, record) in csv_reader.records().enumerate() {
+        let line = index + 1;
+        let record = record.map_err(|err| invalid_line(line, err.to_string()))?;
+
+        let (pubkey, owner, data_len, data) = match record.len() {
+            2 => (&record[0], None, None, &record[1]),
+            6 => (&record[0], Some(&record[1]), Some(&record[2]), &record[5]),
+            columns => return Err(invalid_line(line, format!("unexpected number of columns {} (expected 2 or 6)", columns))),
+        };
+        let data = BASE64_STANDARD
+            .decode(data)
+            .map_err(|err| invalid_line(line, format!("invalid base64 data: {}", err)))?;
+        if let Some(data_len) = data_len {
+            if data_len.parse::<usize>() != Ok(data.len()) {
+                return Err(invalid_line(line, format!("data_len {} does not match the data ({} bytes)", data_len, data.len())));
+            }
+        }
+        if accounts.contains_key(pubkey) || dropped.contains(pubkey) {
+            return Err(invalid_line(line, format!("duplicate account {}", pubkey)));
+        }
+
+        let kind = classify_account(&data);
+        if kind == AccountKind::Unknown || owner.is_some_and(|owner| owner != whirlpool_program_id) {
+            dropped.insert(pubkey.to_string());
+            continue;
+        }
+
+        *imported.entry(kind).or_insert(0) += 1;
+        accounts.insert(pubkey.to_string(), data);
+    }
+
+    let state = WhirlpoolState {
+        slot: slot.slot,
+        block_height: slot.block_height,
+        block_time: slot.block_time,
+        accounts: accounts
+            .into_iter()
+            .map(|(pubkey, data)| WhirlpoolStateAccount { pubkey, data })
+            .collect(),
+        program_data,
+        position: None,
+    };
+
+    let mut dropped: Vec<String> = dropped.into_iter().collect();
+    dropped.sort();
+    return Ok((state, SnapshotImportSummary { imported, dropped }));
+}
+
+// block height and block time of the slot with getBlock RPC (e.g. https://api.mainnet-beta.solana.com)
+pub fn fetch_slot_from_rpc(rpc_url: &String, slot: u64) -> Result<Slot, ReplayError> {
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getBlock",
+        "params": [slot, { "transactionDetails": "none", "rewards": false, "maxSupportedTransactionVersion": 0 }],
+    });
+    let body = reqwest::blocking::Client::new()
+        .post(rpc_url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(request.to_string())
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text())
+        .map_err(|source| ReplayError::Http {
+            url: rpc_url.clone(),
+            source,
+        })?;
+
+    let invalid_response = |reason: String| ReplayError::InvalidRpcResponse {
+        url: rpc_url.clone(),
+        reason,
+    };
+    let response: serde_json::Value = serde_json::from_str(&body).map_err(|err| invalid_response(err.to_string()))?;
+    if let Some(error) = response.get("error") {
+        return Err(invalid_response(error.to_string()));
+    }
+    let block_height = response["result"]["blockHeight"].as_u64();
+    let block_time = response["result"]["blockTime"].as_i64();
+    return match (block_height, block_time) {
+        (Some(block_height), Some(block_time)) => Ok(Slot {
+            slot,
+            block_height,
+            block_time,
+        }),
+        _ => Err(invalid_response(format!("no block height or block time for slot {}", slot))),
+    };
+}
+
+// download is atomic and retried (see download_file)
+pub fn download_from_remote_storage(url: &String, file_path: &String) -> Result<(), ReplayError> {
+    let client = reqwest::blocking::Client::new();
+    return download_file(file_path, &ExpectedFile::default(), &RetryPolicy::default(), |offset| {
+        open_remote_file_at(&client, url, offset)
+    });
+}
+
+// reader of a file from the offset
+pub struct RangeReader {
+    pub reader: Box<dyn Read + Send>,
+    // offset of the first byte (0 if range request is not supported)
+    pub offset: u64,
+    // size of the whole file if known
+    pub total_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    // doubled on each retry
+    pub initial_backoff: std::time::Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        return RetryPolicy {
+            max_retries: 3,
+            initial_backoff: std::time::Duration::from_secs(1),
+        };
+    }
+}
+
+// limits the average read rate to max_bytes_per_second (used to throttle background downloads)
+pub struct ThrottledReader<R: Read> {
+    reader: R,
+    max_bytes_per_second: u64,
+    started_at: std::time::Instant,
+    read_size: u64,
+}
+
+impl<R: Read> ThrottledReader<R> {
+    pub fn new(reader: R, max_bytes_per_second: u64) -> ThrottledReader<R> {
+        return ThrottledReader {
+            reader,
+            max_bytes_per_second: max_bytes_per_second.max(1),
+            started_at: std::time::Instant::now(),
+            read_size: 0,
+        };
+    }
+}
+
+impl<R: Read> Read for ThrottledReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // read in small chunks (1/10 sec) to avoid bursts
+        let chunk_size = buf.len().min((self.max_bytes_per_second / 10).max(1) as usize);
+        let size = self.reader.read(&mut buf[..chunk_size])?;
+        self.read_size += size as u64;
+
+        let expected_elapsed = std::time::Duration::from_secs_f64(self.read_size as f64 / self.max_bytes_per_second as f64);
+        let elapsed = self.started_at.elapsed();
+        if expected_elapsed > elapsed {
+            std::thread::sleep(expected_elapsed - elapsed);
+        }
+        return Ok(size);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ExpectedFile {
+    pub size: Option<u64>,
+    // hex encoding
+    pub sha256: Option<String>,
+}
+
+// download a file with open_at (returns the reader from the given offset).
+//
+// - data is written to {file_path}.part and renamed to file_path after verification,
+//   so file_path never contains a partial file.
+// - an interrupted download is resumed from the end of .part if open_at supports range.
+//   .part is discarded if the size of the whole file differs from the size when .part was started.
+// - size is verified against expected.size (or the size reported by open_at), sha256 against expected.sha256.
+// - retryable errors are retried with exponential backoff.
+pub fn download_file<F>(
+    file_path: &String,
+    expected: &ExpectedFile,
+    retry_policy: &RetryPolicy,
+    mut open_at: F,
+) -> Result<(), ReplayError>
+where
+    F: FnMut(u64) -> Result<RangeReader, ReplayError>,
+{
+    let part_file_path = format!("{}.part", file_path);
+    let mut backoff = retry_policy.initial_backoff;
+    let mut retries = 0;
+    loop {
+        match download_file_once(file_path, &part_file_path, expected, &mut open_at) {
+            Ok(()) => return Ok(()),
+            Err(err) if err.is_retryable() && retries < retry_policy.max_retries => {
+                retries += 1;
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn download_file_once<F>(
+    file_path: &String,
+    part_file_path: &String,
+    expected: &ExpectedFile,
+    open_at: &mut F,
+) -> Result<(), ReplayError>
+where
+    F: FnMut(u64) -> Result<RangeReader, ReplayError>,
+{
+    let io_error = |source: std::io::Error| ReplayError::Io {
+        path: part_file_path.clone(),
+        source,
+    };
+
+    if let Some(parent) = std::path::Path::new(file_path).parent() {
+        std::fs::create_dir_all(parent).map_err(io_error)?;
+    }
+
+    // the size of the whole file when .part was started (to detect a file replaced on the server)
+    let part_size_file_path = format!("{}.size", part_file_path);
+    let part_total_size = std::fs::read_to_string(&part_size_file_path)
+        .ok()
+        .and_then(|size| size.trim().parse::<u64>().ok());
+
+    let downloaded_size = std::fs::metadata(part_file_path).map_or(0, |metadata| metadata.len());
+    let mut range_reader = open_at(downloaded_size)?;
+    let mut resumable = downloaded_size > 0 && range_reader.offset == downloaded_size;
+    if resumable && part_total_size.is_some() {
+        let changed = [range_reader.total_size, expected.size]
+            .iter()
+            .any(|total_size| total_size.is_some() && *total_size != part_total_size);
+        if changed {
+            // .part is a prefix of the old file, start over
+            range_reader = open_at(0)?;
+            resumable = false;
+        }
+    }
+
+    let mut file = if resumable {
+        std::fs::OpenOptions::new().append(true).open(part_file_path).map_err(io_error)?
+    } else {
+        // start over (nothing downloaded, range is not supported or the file has been changed)
+        let file = File::create(part_file_path).map_err(io_error)?;
+        match range_reader.total_size.or(expected.size) {
+            Some(total_size) => std::fs::write(&part_size_file_path, total_size.to_string()).map_err(io_error)?,
+            None => remove_file_if_exists(&part_size_file_path).map_err(io_error)?,
+        }
+        file
+    };
+    std::io::copy(&mut range_reader.reader, &mut file).map_err(io_error)?;
+    file.sync_all().map_err(io_error)?;
+    drop(file);
+
+    let size = std::fs::metadata(part_file_path).map_err(io_error)?.len();
+    if let Some(expected_size) = expected.size.or(range_reader.total_size) {
+        if size < expected_size {
+            // keep .part to resume
+            return Err(ReplayError::IncompleteDownload {
+                path: file_path.clone(),
+                size,
+                expected_size,
+            });
+        }
+        if size > expected_size {
+            std::fs::remove_file(part_file_path).map_err(io_error)?;
+            return Err(ReplayError::CorruptCacheEntry {
+                path: file_path.clone(),
+                reason: format!("size {} exceeds the expected size {}", size, expected_size),
+            });
+        }
+    }
+
+    if let Some(expected_sha256) = expected.sha256.as_ref() {
+        let sha256 = crate::manifest::get_file_sha256(part_file_path)?;
+        if sha256 != *expected_sha256 {
+            std::fs::remove_file(part_file_path).map_err(io_error)?;
+            return Err(ReplayError::CorruptCacheEntry {
+                path: file_path.clone(),
+                reason: format!("sha256 {} differs from the expected {}", sha256, expected_sha256),
+            });
+        }
+    }
+
+    std::fs::rename(part_file_path, file_path).map_err(io_error)?;
+    remove_file_if_exists(&part_size_file_path).map_err(io_error)?;
+    return Ok(());
+}
+
+fn remove_file_if_exists(file_path: &String) -> std::io::Result<()> {
+    return match std::fs::remove_file(file_path) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    };
+}
+
+// GET with Range header if offset > 0
+pub(crate) fn open_remote_file_at(
+    client: &reqwest::blocking::Client,
     url: &String,
-) -> impl Iterator<Item = WhirlpoolTransaction> {
-    let response = reqwest::blocking::get(url).unwrap();
+    offset: u64,
+) -> Result<RangeReader, ReplayError> {
+    let request = client.get(url);
+    return send_range_request(request, url, offset);
+}
+
+pub(crate) fn send_range_request(
+    request: reqwest::blocking::RequestBuilder,
+    url: &String,
+    offset: u64,
+) -> Result<RangeReader, ReplayError> {
+    let http_error = |source: reqwest::Error| ReplayError::Http {
+        url: url.clone(),
+        source,
+    };
+
+    let request = if offset > 0 {
+        request.header(reqwest::header::RANGE, format!("bytes={}-", offset))
+    } else {
+        request
+    };
+    let response = request.send().map_err(http_error)?;
 
-    let decoder = GzDecoder::new(response);
-    let buf = BufReader::new(decoder);
+    // Content-Range: bytes {start}-{end}/{total} or bytes */{total}
+    let content_range = response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("bytes "))
+        .map(|value| value.to_string());
+    let range_total_size = content_range
+        .as_ref()
+        .and_then(|value| value.rsplit_once('/'))
+        .and_then(|(_, total)| total.parse::<u64>().ok());
 
-    let iter = buf.lines().map(|jsonl| jsonl.unwrap()).map(|jsonl| {
-        let t: Result<WhirlpoolTransaction, serde_json::Error> =
-            serde_json::from_str(jsonl.as_str());
-        return t.unwrap();
+    match response.status() {
+        reqwest::StatusCode::PARTIAL_CONTENT => {
+            let range_offset = content_range
+                .as_ref()
+                .and_then(|value| value.split_once('-'))
+                .and_then(|(start, _)| start.parse::<u64>().ok())
+                .unwrap_or(0);
+            return Ok(RangeReader {
+                reader: Box::new(response),
+                offset: range_offset,
+                total_size: range_total_size,
+            });
+        }
+        // offset is at (or beyond) the end of the file
+        reqwest::StatusCode::RANGE_NOT_SATISFIABLE => {
+            return Ok(RangeReader {
+                reader: Box::new(std::io::empty()),
+                offset,
+                total_size: range_total_size,
+            });
+        }
+        _ => {
+            let response = response.error_for_status().map_err(http_error)?;
+            let total_size = response.content_length();
+            return Ok(RangeReader {
+                reader: Box::new(response),
+                offset: 0,
+                total_size,
+            });
+        }
+    }
+}
+
+pub(crate) fn open_local_file(file_path: &String) -> Result<File, ReplayError> {
+    return File::open(file_path).map_err(|source| ReplayError::Io {
+        path: file_path.clone(),
+        source,
     });
+}
 
-    return iter;
+pub(crate) fn fetch_remote_file(url: &String) -> Result<reqwest::blocking::Response, ReplayError> {
+    // treat 404 and other error status as error (reqwest doesn't by default)
+    return reqwest::blocking::get(url)
+        .and_then(|response| response.error_for_status())
+        .map_err(|source| ReplayError::Http {
+            url: url.clone(),
+            source,
+        });
 }
 
-pub fn download_from_remote_storage(url: &String, file_path: &String) {
-    let mut response = reqwest::blocking::get(url).unwrap();
-    std::fs::create_dir_all(std::path::Path::new(file_path).parent().unwrap()).unwrap();
-    let mut file = File::create(file_path).unwrap();
-    std::io::copy(&mut response, &mut file).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SNAPSHOT_FILE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../data/reference_snapshot/solana-labs-snapshot/whirlpool-snapshot-139535179.csv.gz"
+    );
+    const FULL_COLUMNS_SNAPSHOT_FILE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../data/reference_snapshot/solana-labs-snapshot/full-columns/whirlpool-snapshot-139535179.csv.gz"
+    );
+
+    fn create_temp_dir(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("whirlpool-replayer-io-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        return path.to_string_lossy().to_string();
+    }
+
+    fn build_data(size: usize, seed: u8) -> Vec<u8> {
+        return (0..size).map(|i| (i as u8).wrapping_mul(7).wrapping_add(seed)).collect();
+    }
+
+    fn no_wait_retry_policy(max_retries: u32) -> RetryPolicy {
+        return RetryPolicy {
+            max_retries,
+            initial_backoff: std::time::Duration::from_millis(1),
+        };
+    }
+
+    fn io_error(kind: std::io::ErrorKind) -> ReplayError {
+        return ReplayError::Io {
+            path: "remote".to_string(),
+            source: std::io::Error::new(kind, "test"),
+        };
+    }
+
+    // returns data from offset with Content-Range (like 206 and 416 responses)
+    fn range_reader(data: &[u8], offset: u64) -> RangeReader {
+        let start = (offset as usize).min(data.len());
+        return RangeReader {
+            reader: Box::new(std::io::Cursor::new(data[start..].to_vec())),
+            offset,
+            total_size: Some(data.len() as u64),
+        };
+    }
+
+    // yields data and then fails (connection reset in the middle of a download)
+    struct InterruptedReader {
+        data: std::io::Cursor<Vec<u8>>,
+    }
+
+    impl Read for InterruptedReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let size = self.data.read(buf)?;
+            if size == 0 {
+                return Err(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "test"));
+            }
+            return Ok(size);
+        }
+    }
+
+    #[test]
+    fn test_download_file_resume() {
+        let dir = create_temp_dir("resume");
+        let file_path = format!("{}/file.bin", dir);
+        let data = build_data(100, 1);
+
+        let mut offsets = Vec::new();
+        let result = download_file(&file_path, &ExpectedFile::default(), &no_wait_retry_policy(3), |offset| {
+            offsets.push(offset);
+            if offset == 0 {
+                return Ok(RangeReader {
+                    reader: Box::new(InterruptedReader {
+                        data: std::io::Cursor::new(data[..40].to_vec()),
+                    }),
+                    offset: 0,
+                    total_size: Some(data.len() as u64),
+                });
+            }
+            return Ok(range_reader(&data, offset));
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(offsets, vec![0, 40]);
+        assert_eq!(std::fs::read(&file_path).unwrap(), data);
+        assert!(!std::path::Path::new(&format!("{}.part", file_path)).exists());
+        assert!(!std::path::Path::new(&format!("{}.part.size", file_path)).exists());
+    }
+
+    #[test]
+    fn test_download_file_restart_if_file_changed() {
+        let dir = create_temp_dir("changed");
+        let file_path = format!("{}/file.bin", dir);
+        let old_data = build_data(100, 1);
+        let new_data = build_data(120, 2);
+
+        // interrupted download of the old file
+        std::fs::write(format!("{}.part", file_path), &old_data[..40]).unwrap();
+        std::fs::write(format!("{}.part.size", file_path), "100").unwrap();
+
+        let mut offsets = Vec::new();
+        let result = download_file(&file_path, &ExpectedFile::default(), &no_wait_retry_policy(0), |offset| {
+            offsets.push(offset);
+            return Ok(range_reader(&new_data, offset));
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(offsets, vec![40, 0]);
+        assert_eq!(std::fs::read(&file_path).unwrap(), new_data);
+    }
+
+    #[test]
+    fn test_download_file_restart_if_range_is_not_supported() {
+        let dir = create_temp_dir("no-range");
+        let file_path = format!("{}/file.bin", dir);
+        let data = build_data(100, 1);
+
+        std::fs::write(format!("{}.part", file_path), &data[..40]).unwrap();
+
+        let result = download_file(&file_path, &ExpectedFile::default(), &no_wait_retry_policy(0), |_offset| {
+            // 200 OK with the whole file
+            return Ok(range_reader(&data, 0));
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(std::fs::read(&file_path).unwrap(), data);
+    }
+
+    #[test]
+    fn test_download_file_range_not_satisfiable_at_end() {
+        let dir = create_temp_dir("range-not-satisfiable");
+        let file_path = format!("{}/file.bin", dir);
+        let data = build_data(100, 1);
+
+        // fully downloaded, but not renamed yet
+        std::fs::write(format!("{}.part", file_path), &data).unwrap();
+        std::fs::write(format!("{}.part.size", file_path), "100").unwrap();
+
+        let mut offsets = Vec::new();
+        let result = download_file(&file_path, &ExpectedFile::default(), &no_wait_retry_policy(0), |offset| {
+            offsets.push(offset);
+            // 416 with Content-Range: bytes */100
+            return Ok(RangeReader {
+                reader: Box::new(std::io::empty()),
+                offset,
+                total_size: Some(100),
+            });
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(offsets, vec![100]);
+        assert_eq!(std::fs::read(&file_path).unwrap(), data);
+    }
+
+    #[test]
+    fn test_download_file_oversize() {
+        let dir = create_temp_dir("oversize");
+        let file_path = format!("{}/file.bin", dir);
+        let data = build_data(100, 1);
+        let expected = ExpectedFile {
+            size: Some(80),
+            sha256: None,
+        };
+
+        let result = download_file(&file_path, &expected, &no_wait_retry_policy(0), |offset| {
+            return Ok(range_reader(&data, offset));
+        });
+
+        assert!(matches!(result, Err(ReplayError::CorruptCacheEntry { .. })));
+        assert!(!std::path::Path::new(&file_path).exists());
+        assert!(!std::path::Path::new(&format!("{}.part", file_path)).exists());
+    }
+
+    #[test]
+    fn test_download_file_sha256() {
+        let dir = create_temp_dir("sha256");
+        let file_path = format!("{}/file.bin", dir);
+        let data = build_data(100, 1);
+
+        let mismatch = ExpectedFile {
+            size: Some(100),
+            sha256: Some("00".repeat(32)),
+        };
+        let result = download_file(&file_path, &mismatch, &no_wait_retry_policy(0), |offset| {
+            return Ok(range_reader(&data, offset));
+        });
+        assert!(matches!(result, Err(ReplayError::CorruptCacheEntry { .. })));
+        assert!(!std::path::Path::new(&file_path).exists());
+        assert!(!std::path::Path::new(&format!("{}.part", file_path)).exists());
+
+        let sha256_file_path = format!("{}/sha256.bin", dir);
+        std::fs::write(&sha256_file_path, &data).unwrap();
+        let matched = ExpectedFile {
+            size: Some(100),
+            sha256: Some(crate::manifest::get_file_sha256(&sha256_file_path).unwrap()),
+        };
+        let result = download_file(&file_path, &matched, &no_wait_retry_policy(0), |offset| {
+            return Ok(range_reader(&data, offset));
+        });
+        assert!(result.is_ok());
+        assert_eq!(std::fs::read(&file_path).unwrap(), data);
+    }
+
+    #[test]
+    fn test_download_file_retry() {
+        let dir = create_temp_dir("retry");
+        let file_path = format!("{}/file.bin", dir);
+        let retry_policy = RetryPolicy {
+            max_retries: 2,
+            initial_backoff: std::time::Duration::from_millis(20),
+        };
+
+        // retryable error: 1 + max_retries attempts with backoff 20ms, 40ms
+        let mut attempts = 0;
+        let started_at = std::time::Instant::now();
+        let result = download_file(&file_path, &ExpectedFile::default(), &retry_policy, |_offset| {
+            attempts += 1;
+            return Err(io_error(std::io::ErrorKind::ConnectionReset));
+        });
+        assert!(matches!(result, Err(ReplayError::Io { .. })));
+        assert_eq!(attempts, 3);
+        assert!(started_at.elapsed() >= std::time::Duration::from_millis(60));
+
+        // not retryable
+        let mut attempts = 0;
+        let result = download_file(&file_path, &ExpectedFile::default(), &retry_policy, |_offset| {
+            attempts += 1;
+            return Err(io_error(std::io::ErrorKind::NotFound));
+        });
+        assert!(matches!(result, Err(ReplayError::Io { .. })));
+        assert_eq!(attempts, 1);
+
+        // succeeds after a failure
+        let data = build_data(100, 1);
+        let mut attempts = 0;
+        let result = download_file(&file_path, &ExpectedFile::default(), &retry_policy, |offset| {
+            attempts += 1;
+            if attempts == 1 {
+                return Err(io_error(std::io::ErrorKind::ConnectionReset));
+            }
+            return Ok(range_reader(&data, offset));
+        });
+        assert!(result.is_ok());
+        assert_eq!(attempts, 2);
+        assert_eq!(std::fs::read(&file_path).unwrap(), data);
+    }
+
+    fn snapshot_slot() -> Slot {
+        return Slot {
+            slot: 139535179,
+            block_height: 127000000,
+            block_time: 1654000000,
+        };
+    }
+
+    fn read_snapshot_file(file_path: &str) -> Result<(WhirlpoolState, SnapshotImportSummary), ReplayError> {
+        let file = File::open(file_path).unwrap();
+        return read_snapshot_csv(file, &file_path.to_string(), vec![0x7f, b'E', b'L', b'F'], &snapshot_slot());
+    }
+
+    // the first lines of the full-columns snapshot (pubkey, owner, data_len, lamports, ..., data)
+    fn read_full_columns_lines(count: usize) -> Vec<Vec<String>> {
+        let file = File::open(FULL_COLUMNS_SNAPSHOT_FILE).unwrap();
+        return std::io::BufRead::lines(BufReader::new(GzDecoder::new(file)))
+            .take(count)
+            .map(|line| line.unwrap().split(',').map(|column| column.to_string()).collect())
+            .collect();
+    }
+
+    fn read_snapshot_lines(lines: &[Vec<String>]) -> Result<(WhirlpoolState, SnapshotImportSummary), ReplayError> {
+        let csv = lines.iter().map(|columns| columns.join(",")).collect::<Vec<String>>().join("\n");
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(csv.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        return read_snapshot_csv(gzipped.as_slice(), &"snapshot.csv.gz".to_string(), vec![], &snapshot_slot());
+    }
+
+    #[test]
+    fn test_read_snapshot_csv_short_and_full_columns() {
+        let (state, summary) = read_snapshot_file(SNAPSHOT_FILE).unwrap();
+        let (full_columns_state, full_columns_summary) = read_snapshot_file(FULL_COLUMNS_SNAPSHOT_FILE).unwrap();
+
+        assert_eq!(state.slot, 139535179);
+        assert!(!state.accounts.is_empty());
+        assert!(state.accounts.windows(2).all(|accounts| accounts[0].pubkey < accounts[1].pubkey));
+        assert_eq!(summary.imported.values().sum::<usize>(), state.accounts.len());
+        assert!(summary.imported.get(&AccountKind::Whirlpool).copied().unwrap_or_default() > 0);
+
+        // both variants have the same accounts
+        assert_eq!(full_columns_state, state);
+        assert_eq!(full_columns_summary.imported, summary.imported);
+        assert_eq!(full_columns_summary.dropped, summary.dropped);
+    }
+
+    #[test]
+    fn test_read_snapshot_csv_drops_non_state_accounts() {
+        let mut lines = read_full_columns_lines(3);
+
+        // owned by another program
+        lines[1][1] = "TokenkegQfeZyiNwAJbNbGkPWqvkyuMNtPaPSDaNBMpbH7j".to_string();
+        // IDL account (unknown discriminator)
+        let idl_data = BASE64_STANDARD.encode([0x18, 0x46, 0x62, 0xbf, 0x3a, 0x90, 0x7b, 0x9e, 1, 2, 3, 4]);
+        lines.push(vec![
+            "idLeuA6s2fE7DHFeDWbC3BPcezBrWFGqCZX4x6u1hmT".to_string(),
+            lines[0][1].clone(),
+            "12".to_string(),
+            "1000".to_string(),
+            "0".to_string(),
+            idl_data,
+        ]);
+
+        let (state, summary) = read_snapshot_lines(&lines).unwrap();
+        let pubkeys: Vec<String> = state.accounts.iter().map(|account| account.pubkey.clone()).collect();
+        let mut expected_pubkeys = vec![lines[0][0].clone(), lines[2][0].clone()];
+        expected_pubkeys.sort();
+        assert_eq!(pubkeys, expected_pubkeys);
+
+        let mut expected_dropped = vec![lines[1][0].clone(), lines[3][0].clone()];
+        expected_dropped.sort();
+        assert_eq!(summary.dropped, expected_dropped);
+    }
+
+    #[test]
+    fn test_read_snapshot_csv_data_len_mismatch() {
+        let mut lines = read_full_columns_lines(3);
+        lines[1][2] = "1".to_string();
+
+        match read_snapshot_lines(&lines) {
+            Err(ReplayError::InvalidSnapshotFile { line, reason, .. }) => {
+                assert_eq!(line, 2);
+                assert!(reason.contains("data_len"));
+            }
+            _ => panic!("expected InvalidSnapshotFile"),
+        }
+    }
+
+    #[test]
+    fn test_read_snapshot_csv_duplicate_pubkey() {
+        let mut lines = read_full_columns_lines(3);
+        lines.push(lines[1].clone());
+
+        match read_snapshot_lines(&lines) {
+            Err(ReplayError::InvalidSnapshotFile { line, reason, .. }) => {
+                assert_eq!(line, 4);
+                assert!(reason.contains("duplicate"));
+            }
+            _ => panic!("expected InvalidSnapshotFile"),
+        }
+
+        // duplicate of a dropped account
+        let mut lines = read_full_columns_lines(2);
+        lines[1][1] = "TokenkegQfeZyiNwAJbNbGkPWqvkyuMNtPaPSDaNBMpbH7j".to_string();
+        lines.push(lines[1].clone());
+        assert!(matches!(
+            read_snapshot_lines(&lines),
+            Err(ReplayError::InvalidSnapshotFile { line: 3, .. })
+        ));
+    }
 }
@@ -4,26 +4,53 @@ use std::sync::Arc;
 
 use replay_engine::decoded_instructions;
 use replay_engine::decoded_instructions::DecodedWhirlpoolInstruction;
+use replay_engine::pool_filter::FilteredInstruction;
 use replay_engine::replay_engine::ReplayEngine;
 
+pub use replay_engine::account_index::AccountIndex;
+pub use replay_engine::account_kind::AccountKind;
+pub use replay_engine::account_view::AccountView;
+pub use replay_engine::pool_filter::PoolFilter;
+pub use replay_engine::program_versions::ProgramVersionRegistry;
+pub use replay_engine::replay_engine::{FailurePolicy, ReplayFailureReport, DEFAULT_MAX_FAILURE_REPORTS};
 pub use replay_engine::replay_instruction::ReplayInstructionResult;
 pub use replay_engine::types::{AccountMap, Slot};
 
+pub mod binary_format;
+pub mod chain_verification;
+pub mod checkpoint;
+pub mod database;
+pub mod errors;
 pub mod io;
+pub mod manifest;
+pub mod pipeline;
+pub mod prefetch;
+pub mod producer;
+pub mod replay_event;
+pub mod replay_observer;
+pub mod replay_until;
 pub mod schema;
+pub mod state_comparison;
+pub mod state_delta;
+pub mod storage;
+pub mod transaction_stream;
 pub mod util;
-
-use schema::{Transaction, WhirlpoolTransaction};
+pub mod verification;
+
+pub use errors::{ErrorCode, ReplayError};
+pub use replay_event::{ProgramDeployment, ReplayEvent, ReplayEvents, ReplayedInstruction};
+pub use replay_observer::ReplayObserver;
+pub use replay_until::{ReplayUntil, ReplayUntilBound, ReplayUntilPredicate};
+
+use checkpoint::{Checkpoint, CheckpointConfig, Checkpointer};
+use database::MysqlTransactionSource;
+use replay_observer::CallbackObserver;
+use schema::{Transaction, TransactionInstruction, WhirlpoolState, WhirlpoolStatePosition, WhirlpoolTransaction};
+use storage::{CachedStorage, HttpStorage, LocalFileStorage, StorageBackend};
+use transaction_stream::{DailyTransactionStream, TransactionStreamItem, WhirlpoolTransactionLoader};
+use verification::{VerificationMismatch, Verifier};
 use tokio::sync::Mutex;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub enum ReplayUntil {
-    End,
-    Slot(u64),
-    BlockHeight(u64),
-    BlockTime(i64),
-}
-
 pub type SlotCallback = fn(&Slot);
 
 pub type InstructionCallback = fn(
@@ -35,6 +62,9 @@ pub type InstructionCallback = fn(
     &ReplayInstructionResult,
 );
 
+// called at the end of each day with the state after all transactions of the day
+pub type DayCallback = fn(&chrono::NaiveDate, &Slot, &Vec<u8>, &AccountMap);
+
 pub type AsyncSlotCallback = Box<dyn Fn(&Slot) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
 pub type AsyncInstructionCallback = Box<
     dyn Fn(
@@ -47,111 +77,217 @@ pub type AsyncInstructionCallback = Box<
     ) -> Pin<Box<dyn 'static + Future<Output = ()> + Send>> + Send
 >;
 
+pub type AsyncDayCallback = Box<
+    dyn Fn(
+        &chrono::NaiveDate,
+        &Slot,
+        &Vec<u8>,
+        &AccountMap
+    ) -> Pin<Box<dyn 'static + Future<Output = ()> + Send>> + Send
+>;
+
+// called with the deployment and the new program data
+pub type AsyncProgramDeployCallback = Box<
+    dyn Fn(
+        &ProgramDeployment,
+        &Vec<u8>
+    ) -> Pin<Box<dyn 'static + Future<Output = ()> + Send>> + Send
+>;
+
+// called once when replay stops (until condition reached or no more transactions)
+pub type AsyncFinishCallback = Box<
+    dyn Fn(
+        &Slot,
+        &Vec<u8>,
+        &AccountMap
+    ) -> Pin<Box<dyn 'static + Future<Output = ()> + Send>> + Send
+>;
+
+pub type WhirlpoolTransactionIterator =
+    Box<dyn Iterator<Item = Result<WhirlpoolTransaction, ReplayError>> + Send>;
+
 pub struct WhirlpoolReplayer {
     replay_engine: ReplayEngine,
-    transaction_iter: Box<dyn Iterator<Item = WhirlpoolTransaction> + Send>,
+    transaction_stream: DailyTransactionStream,
+    // slots at or before the slot of the initial state have already been applied
+    resume_slot: u64,
+    // the initial state was saved in the middle of resume_slot (taken when the slot is reached)
+    resume_position: Option<WhirlpoolStatePosition>,
+    verifier: Option<Verifier>,
+    // false if the transaction source doesn't provide balances (database)
+    has_balances: bool,
+    checkpointer: Option<Checkpointer>,
+    program_version_registry: ProgramVersionRegistry,
+    pool_filter: Option<PoolFilter>,
+    cursor: Option<ReplayCursor>,
+    stop_pending: bool,
 }
 
 impl WhirlpoolReplayer {
-    // TODO: build_with_mysql_database
-
     pub fn build_with_local_file_storage(
         base_path: &String,
         yyyymmdd: &String,
-    ) -> WhirlpoolReplayer {
-        let current = chrono::NaiveDate::parse_from_str(yyyymmdd, "%Y%m%d").unwrap();
-        let previous = current.pred_opt().unwrap();
-
-        // snapshot of the previous day
-        let state_file_relative_path = io::get_whirlpool_state_file_relative_path(&previous);
-        let state_file_path = format!("{}/{}", base_path, state_file_relative_path);
-        // transactions of the day
-        let transaction_file_relative_path =
-            io::get_whirlpool_transaction_file_relative_path(&current);
-        let transaction_file_path = format!("{}/{}", base_path, transaction_file_relative_path);
-
-        let state = io::load_from_local_whirlpool_state_file(&state_file_path);
-        let transaction_iter =
-            io::load_from_local_whirlpool_transaction_file(&transaction_file_path);
-
-        let replay_engine = ReplayEngine::new(
-            state.slot,
-            state.block_height,
-            state.block_time,
-            state.program_data,
-            util::convert_accounts_to_account_map(&state.accounts),
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        return WhirlpoolReplayer::build_with_local_file_storage_for_date_range(
+            base_path,
+            yyyymmdd,
+            Some(yyyymmdd),
         );
-
-        return WhirlpoolReplayer {
-            replay_engine,
-            transaction_iter: Box::new(transaction_iter),
-        };
     }
 
     pub fn build_with_remote_file_storage(
         base_url: &String,
         yyyymmdd: &String,
-    ) -> WhirlpoolReplayer {
-        let current = chrono::NaiveDate::parse_from_str(yyyymmdd, "%Y%m%d").unwrap();
-        let previous = current.pred_opt().unwrap();
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        return WhirlpoolReplayer::build_with_remote_file_storage_for_date_range(
+            base_url,
+            yyyymmdd,
+            Some(yyyymmdd),
+        );
+    }
+
+    pub fn build_with_remote_file_storage_with_local_cache(
+        base_url: &String,
+        yyyymmdd: &String,
+        cache_dir_path: &String,
+        refresh: bool,
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        return WhirlpoolReplayer::build_with_remote_file_storage_with_local_cache_for_date_range(
+            base_url,
+            yyyymmdd,
+            Some(yyyymmdd),
+            cache_dir_path,
+            refresh,
+        );
+    }
+
+    // replay transactions from start_yyyymmdd to end_yyyymmdd (inclusive) with the state of the previous day of start_yyyymmdd.
+    // if end_yyyymmdd is None, replay continues until the latest available transaction file.
+    pub fn build_with_storage(
+        storage: Arc<dyn StorageBackend>,
+        start_yyyymmdd: &String,
+        end_yyyymmdd: Option<&String>,
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        let (start, end, previous) = parse_date_range(start_yyyymmdd, end_yyyymmdd)?;
 
         // snapshot of the previous day
-        let state_file_relative_path = io::get_whirlpool_state_file_relative_path(&previous);
-        let state_file_url = format!("{}/{}", base_url, state_file_relative_path);
-        // transactions of the day
-        let transaction_file_relative_path =
-            io::get_whirlpool_transaction_file_relative_path(&current);
-        let transaction_file_url = format!("{}/{}", base_url, transaction_file_relative_path);
+        let state = storage.load_whirlpool_state(&previous)?;
 
-        let state = io::load_from_remote_whirlpool_state_file(&state_file_url);
-        let transaction_iter =
-            io::load_from_remote_whirlpool_transaction_file(&transaction_file_url);
+        let loader = build_storage_loader(storage);
+        return WhirlpoolReplayer::new(state, loader, start, end);
+    }
 
-        let replay_engine = ReplayEngine::new(
-            state.slot,
-            state.block_height,
-            state.block_time,
-            state.program_data,
-            util::convert_accounts_to_account_map(&state.accounts),
-        );
+    pub fn build_with_local_file_storage_for_date_range(
+        base_path: &String,
+        start_yyyymmdd: &String,
+        end_yyyymmdd: Option<&String>,
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        let storage = Arc::new(LocalFileStorage::new(base_path));
+        return WhirlpoolReplayer::build_with_storage(storage, start_yyyymmdd, end_yyyymmdd);
+    }
 
-        return WhirlpoolReplayer {
-            replay_engine,
-            transaction_iter: Box::new(transaction_iter),
-        };
+    pub fn build_with_remote_file_storage_for_date_range(
+        base_url: &String,
+        start_yyyymmdd: &String,
+        end_yyyymmdd: Option<&String>,
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        let storage = Arc::new(HttpStorage::new(base_url));
+        return WhirlpoolReplayer::build_with_storage(storage, start_yyyymmdd, end_yyyymmdd);
     }
 
-    pub fn build_with_remote_file_storage_with_local_cache(
+    pub fn build_with_remote_file_storage_with_local_cache_for_date_range(
         base_url: &String,
-        yyyymmdd: &String,
+        start_yyyymmdd: &String,
+        end_yyyymmdd: Option<&String>,
         cache_dir_path: &String,
         refresh: bool,
-    ) -> WhirlpoolReplayer {
-        let current = chrono::NaiveDate::parse_from_str(yyyymmdd, "%Y%m%d").unwrap();
-        let previous = current.pred_opt().unwrap();
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        let storage = Arc::new(CachedStorage::new(Box::new(HttpStorage::new(base_url)), cache_dir_path, refresh));
+        return WhirlpoolReplayer::build_with_storage(storage, start_yyyymmdd, end_yyyymmdd);
+    }
 
-        // snapshot of the previous day
-        let state_file_relative_path = io::get_whirlpool_state_file_relative_path(&previous);
-        let state_file_url = format!("{}/{}", base_url, state_file_relative_path);
-        // transactions of the day
-        let transaction_file_relative_path =
-            io::get_whirlpool_transaction_file_relative_path(&current);
-        let transaction_file_url = format!("{}/{}", base_url, transaction_file_relative_path);
-
-        let state_file_path = format!("{}/{}", cache_dir_path, state_file_relative_path);
-        if refresh || !std::path::Path::new(&state_file_path).exists() {
-            io::download_from_remote_storage(&state_file_url, &state_file_path);
-        }
+    // resume replay from a state saved at any slot (e.g. in the middle of a day).
+    // start_yyyymmdd should be the date of the transaction file that contains state.slot.
+    // transactions in slots at or before state.slot are skipped because they are already applied to the state.
+    pub fn build_with_state_and_storage(
+        state: WhirlpoolState,
+        storage: Arc<dyn StorageBackend>,
+        start_yyyymmdd: &String,
+        end_yyyymmdd: Option<&String>,
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        let (start, end, _) = parse_date_range(start_yyyymmdd, end_yyyymmdd)?;
+        let loader = build_storage_loader(storage);
+        return WhirlpoolReplayer::new(state, loader, start, end);
+    }
 
-        let transaction_file_path =
-            format!("{}/{}", cache_dir_path, transaction_file_relative_path);
-        if refresh || !std::path::Path::new(&transaction_file_path).exists() {
-            io::download_from_remote_storage(&transaction_file_url, &transaction_file_path);
-        }
+    pub fn build_with_state_and_local_file_storage(
+        state: WhirlpoolState,
+        base_path: &String,
+        start_yyyymmdd: &String,
+        end_yyyymmdd: Option<&String>,
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        let storage = Arc::new(LocalFileStorage::new(base_path));
+        return WhirlpoolReplayer::build_with_state_and_storage(state, storage, start_yyyymmdd, end_yyyymmdd);
+    }
+
+    pub fn build_with_state_and_remote_file_storage(
+        state: WhirlpoolState,
+        base_url: &String,
+        start_yyyymmdd: &String,
+        end_yyyymmdd: Option<&String>,
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        let storage = Arc::new(HttpStorage::new(base_url));
+        return WhirlpoolReplayer::build_with_state_and_storage(state, storage, start_yyyymmdd, end_yyyymmdd);
+    }
+
+    pub fn build_with_state_and_remote_file_storage_with_local_cache(
+        state: WhirlpoolState,
+        base_url: &String,
+        start_yyyymmdd: &String,
+        end_yyyymmdd: Option<&String>,
+        cache_dir_path: &String,
+        refresh: bool,
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        let storage = Arc::new(CachedStorage::new(Box::new(HttpStorage::new(base_url)), cache_dir_path, refresh));
+        return WhirlpoolReplayer::build_with_state_and_storage(state, storage, start_yyyymmdd, end_yyyymmdd);
+    }
+
+    // replay transactions in the indexer database from start_yyyymmdd to end_yyyymmdd (inclusive).
+    // state should be the state at the end of the previous day of start_yyyymmdd or at any slot in start_yyyymmdd.
+    // if end_yyyymmdd is None, replay continues until the latest day in the database.
+    pub fn build_with_mysql_database(
+        state: WhirlpoolState,
+        database_url: &String,
+        start_yyyymmdd: &String,
+        end_yyyymmdd: Option<&String>,
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        let (start, end, _) = parse_date_range(start_yyyymmdd, end_yyyymmdd)?;
+        let source = MysqlTransactionSource::new(database_url)?;
+        let loader: WhirlpoolTransactionLoader = Box::new(move |date| source.load_whirlpool_transactions(date));
+        let mut replayer = WhirlpoolReplayer::new(state, loader, start, end)?;
+        replayer.has_balances = false;
+        return Ok(replayer);
+    }
+
+    // the state of the previous day of start_yyyymmdd is loaded from the storage
+    pub fn build_with_storage_and_mysql_database(
+        storage: Arc<dyn StorageBackend>,
+        database_url: &String,
+        start_yyyymmdd: &String,
+        end_yyyymmdd: Option<&String>,
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        let (_, _, previous) = parse_date_range(start_yyyymmdd, end_yyyymmdd)?;
+        let state = storage.load_whirlpool_state(&previous)?;
+        return WhirlpoolReplayer::build_with_mysql_database(state, database_url, start_yyyymmdd, end_yyyymmdd);
+    }
 
-        let state = io::load_from_local_whirlpool_state_file(&state_file_path);
-        let transaction_iter =
-            io::load_from_local_whirlpool_transaction_file(&transaction_file_path);
+    fn new(
+        state: WhirlpoolState,
+        loader: WhirlpoolTransactionLoader,
+        start: chrono::NaiveDate,
+        end: Option<chrono::NaiveDate>,
+    ) -> Result<WhirlpoolReplayer, ReplayError> {
+        let transaction_stream = DailyTransactionStream::new(loader, start, end)?;
 
         let replay_engine = ReplayEngine::new(
             state.slot,
@@ -161,10 +297,24 @@ impl WhirlpoolReplayer {
             util::convert_accounts_to_account_map(&state.accounts),
         );
 
-        return WhirlpoolReplayer {
+        return Ok(WhirlpoolReplayer {
             replay_engine,
-            transaction_iter: Box::new(transaction_iter),
-        };
+            transaction_stream,
+            resume_slot: state.slot,
+            resume_position: state.position,
+            verifier: None,
+            has_balances: true,
+            checkpointer: None,
+            program_version_registry: ProgramVersionRegistry::new(),
+            pool_filter: None,
+            cursor: None,
+            stop_pending: false,
+        });
+    }
+
+    // the date of the transaction file being replayed (None after all days have been replayed)
+    pub fn get_current_date(&self) -> Option<chrono::NaiveDate> {
+        return self.transaction_stream.get_current_date();
     }
 
     pub fn get_slot(&self) -> Slot {
@@ -179,71 +329,223 @@ impl WhirlpoolReplayer {
         return self.replay_engine.get_accounts();
     }
 
-    pub fn replay(
-        &mut self,
-        cond: ReplayUntil,
-        slot_callback: Option<SlotCallback>,
-        instruction_callback: Option<InstructionCallback>,
-    ) {
-        let mut next_whirlpool_transaction = self.transaction_iter.next();
-        while next_whirlpool_transaction.is_some() {
-            let whirlpool_transaction = next_whirlpool_transaction.unwrap();
+    pub fn get_account_view(&self) -> AccountView<'_> {
+        return AccountView::new(self.replay_engine.get_accounts());
+    }
 
-            let slot = Slot {
-                slot: whirlpool_transaction.slot,
-                block_height: whirlpool_transaction.block_height,
-                block_time: whirlpool_transaction.block_time,
-            };
+    // sha256 (base58) of the current program data
+    pub fn get_program_data_hash(&self) -> &String {
+        return self.replay_engine.get_program_data_hash();
+    }
 
-            if has_reached_until_condition(&cond, slot) {
-                break;
-            }
+    // version of the current program (None if the program data is unknown to the registry)
+    pub fn get_program_version(&self) -> Option<&String> {
+        return self
+            .program_version_registry
+            .get_version(self.replay_engine.get_program_data_hash());
+    }
+
+    pub fn get_program_version_registry_mut(&mut self) -> &mut ProgramVersionRegistry {
+        return &mut self.program_version_registry;
+    }
+
+    pub fn get_failure_policy(&self) -> FailurePolicy {
+        return self.replay_engine.get_failure_policy();
+    }
+
+    pub fn set_failure_policy(&mut self, failure_policy: FailurePolicy) {
+        self.replay_engine.set_failure_policy(failure_policy);
+    }
 
-            self.replay_engine
-                .update_slot(slot.slot, slot.block_height, slot.block_time);
+    pub fn get_failure_reports(&self) -> &Vec<ReplayFailureReport> {
+        return self.replay_engine.get_failure_reports();
+    }
 
-            if let Some(callback) = slot_callback {
-                callback(&slot);
+    pub fn take_failure_reports(&mut self) -> Vec<ReplayFailureReport> {
+        return self.replay_engine.take_failure_reports();
+    }
+
+    pub fn get_failure_count(&self) -> u64 {
+        return self.replay_engine.get_failure_count();
+    }
+
+    // reports are kept only for the first failures (DEFAULT_MAX_FAILURE_REPORTS by default)
+    pub fn set_max_failure_reports(&mut self, max_failure_reports: usize) {
+        self.replay_engine.set_max_failure_reports(max_failure_reports);
+    }
+
+    // verification is opt-in because it adds some overhead to each instruction.
+    // balance changes are not verified if the transaction source doesn't provide balances (database).
+    pub fn enable_verification(&mut self) {
+        if self.verifier.is_none() {
+            let mut verifier = Verifier::new();
+            if !self.has_balances {
+                verifier.disable_balance_change_verification();
             }
+            self.verifier = Some(verifier);
+        }
+    }
 
-            for transaction in whirlpool_transaction.transactions {
-                for instruction in transaction.clone().instructions {
-                    let name = instruction.name;
-                    let payload = instruction.payload.to_string();
-                    let decoded = decoded_instructions::from_json(&name, &payload).unwrap();
-
-                    match decoded {
-                        decoded_instructions::DecodedInstruction::ProgramDeployInstruction(
-                            deploy_instruction,
-                        ) => {
-                            self.replay_engine
-                                .update_program_data(deploy_instruction.program_data);
-                        }
-                        decoded_instructions::DecodedInstruction::WhirlpoolInstruction(
-                            whirlpool_instruction,
-                        ) => {
-                            let result = self
-                                .replay_engine
-                                .replay_instruction(&whirlpool_instruction)
-                                .unwrap();
-
-                            if let Some(callback) = instruction_callback {
-                                callback(
-                                    &slot,
-                                    &transaction,
-                                    &name,
-                                    &whirlpool_instruction,
-                                    self.replay_engine.get_accounts(),
-                                    &result,
-                                );
-                            }
-                        }
-                    }
+    pub fn has_balances(&self) -> bool {
+        return self.has_balances;
+    }
+
+    pub fn is_verification_enabled(&self) -> bool {
+        return self.verifier.is_some();
+    }
+
+    pub fn get_verification_mismatches(&self) -> &[VerificationMismatch] {
+        return match self.verifier.as_ref() {
+            Some(verifier) => verifier.get_mismatches(),
+            None => &[],
+        };
+    }
+
+    pub fn take_verification_mismatches(&mut self) -> Vec<VerificationMismatch> {
+        return match self.verifier.as_mut() {
+            Some(verifier) => verifier.take_mismatches(),
+            None => vec![],
+        };
+    }
+
+    // replay only the selected whirlpools.
+    // accounts unrelated to them are dropped, and instructions that touch nothing in them are skipped.
+    pub fn set_pool_filter(&mut self, whirlpools: Vec<String>) {
+        let pool_filter = PoolFilter::new(whirlpools);
+        self.replay_engine.prune_accounts(&pool_filter);
+        self.pool_filter = Some(pool_filter);
+    }
+
+    pub fn get_pool_filter(&self) -> Option<&PoolFilter> {
+        return self.pool_filter.as_ref();
+    }
+
+    // maintain secondary indexes for the query methods below.
+    // without the index, each query scans all accounts.
+    pub fn enable_account_index(&mut self) {
+        self.replay_engine.enable_account_index();
+    }
+
+    pub fn is_account_index_enabled(&self) -> bool {
+        return self.replay_engine.get_account_index().is_some();
+    }
+
+    pub fn get_positions_of(&self, whirlpool: &String) -> Vec<String> {
+        return self.query_account_index(|index| index.get_positions_of(whirlpool));
+    }
+
+    pub fn get_tick_arrays_of(&self, whirlpool: &String) -> Vec<String> {
+        return self.query_account_index(|index| index.get_tick_arrays_of(whirlpool));
+    }
+
+    pub fn get_position_by_mint(&self, position_mint: &String) -> Option<String> {
+        return self.query_account_index(|index| index.get_position_by_mint(position_mint));
+    }
+
+    pub fn get_bundled_positions_of(&self, position_bundle: &String) -> Vec<String> {
+        return self.query_account_index(|index| index.get_bundled_positions_of(position_bundle));
+    }
+
+    fn query_account_index<T>(&self, query: impl FnOnce(&AccountIndex) -> T) -> T {
+        return match self.replay_engine.get_account_index() {
+            Some(index) => query(index),
+            None => query(&AccountIndex::build(self.replay_engine.get_accounts())),
+        };
+    }
+
+    // the state at the current position (accounts are converted, so it is not cheap).
+    // if replay stopped in the middle of a slot, the position in the slot is kept in the state
+    // so that a replayer built with the state resumes from there.
+    pub fn get_whirlpool_state(&self) -> WhirlpoolState {
+        let slot = self.replay_engine.get_slot();
+        let position = match self.cursor.as_ref() {
+            Some(cursor) if cursor.slot_started => Some(WhirlpoolStatePosition {
+                transaction_index: cursor.transaction_index as u32,
+                instruction_index: cursor.instruction_index as u32,
+            }),
+            _ => None,
+        };
+        return WhirlpoolState {
+            slot: slot.slot,
+            block_height: slot.block_height,
+            block_time: slot.block_time,
+            program_data: self.replay_engine.get_program_data().clone(),
+            accounts: util::convert_account_map_to_accounts(self.replay_engine.get_accounts()),
+            position,
+        };
+    }
+
+    // write a state file periodically while replaying.
+    // the interval is measured from the current position.
+    pub fn enable_checkpoint(&mut self, config: CheckpointConfig) {
+        self.checkpointer = Some(Checkpointer::new(config, self.replay_engine.get_slot()));
+    }
+
+    pub fn get_checkpoints(&self) -> Vec<Checkpoint> {
+        return match self.checkpointer.as_ref() {
+            Some(checkpointer) => checkpointer.get_checkpoints().iter().cloned().collect(),
+            None => vec![],
+        };
+    }
+
+    pub fn replay(
+        &mut self,
+        cond: ReplayUntil,
+        slot_callback: Option<SlotCallback>,
+        instruction_callback: Option<InstructionCallback>,
+        day_callback: Option<DayCallback>,
+    ) -> Result<(), ReplayError> {
+        let mut observer = CallbackObserver {
+            slot_callback,
+            instruction_callback,
+            day_callback,
+        };
+        return self.replay_with_observer(cond, &mut observer);
+    }
+
+    pub fn replay_with_observer<O: ReplayObserver + ?Sized>(
+        &mut self,
+        cond: ReplayUntil,
+        observer: &mut O,
+    ) -> Result<(), ReplayError> {
+        while let Some(event) = self.next_event(&cond)? {
+            match event {
+                ReplayEvent::SlotStart(slot) => {
+                    observer.on_slot(&slot);
+                }
+                ReplayEvent::TransactionStart(slot, transaction) => {
+                    observer.on_transaction(&slot, &transaction);
+                }
+                ReplayEvent::Instruction(replayed) => {
+                    observer.on_instruction(
+                        &replayed.slot,
+                        &replayed.transaction,
+                        &replayed.name,
+                        &replayed.instruction,
+                        self.replay_engine.get_accounts(),
+                        &replayed.result,
+                    );
+                }
+                ReplayEvent::ProgramDeploy(deployment) => {
+                    observer.on_program_deploy(&deployment, self.replay_engine.get_program_data());
+                }
+                ReplayEvent::DayEnd(date) => {
+                    observer.on_day_end(
+                        &date,
+                        &self.replay_engine.get_slot(),
+                        self.replay_engine.get_program_data(),
+                        self.replay_engine.get_accounts(),
+                    );
                 }
             }
-
-            next_whirlpool_transaction = self.transaction_iter.next();
         }
+
+        observer.on_finish(
+            &self.replay_engine.get_slot(),
+            self.replay_engine.get_program_data(),
+            self.replay_engine.get_accounts(),
+        );
+        return Ok(());
     }
 
     pub async fn replay_async(
@@ -251,80 +553,361 @@ impl WhirlpoolReplayer {
         cond: ReplayUntil,
         slot_callback: Option<Arc<Mutex<AsyncSlotCallback>>>,
         instruction_callback: Option<Arc<Mutex<AsyncInstructionCallback>>>,
-    ) {
-        let mut next_whirlpool_transaction = self.transaction_iter.next();
-        while next_whirlpool_transaction.is_some() {
-            let whirlpool_transaction = next_whirlpool_transaction.unwrap();
+        day_callback: Option<Arc<Mutex<AsyncDayCallback>>>,
+        program_deploy_callback: Option<Arc<Mutex<AsyncProgramDeployCallback>>>,
+        finish_callback: Option<Arc<Mutex<AsyncFinishCallback>>>,
+    ) -> Result<(), ReplayError> {
+        while let Some(event) = self.next_event(&cond)? {
+            match event {
+                ReplayEvent::DayEnd(date) => {
+                    if let Some(callback) = day_callback.as_ref() {
+                        let day_c = callback.lock().await;
+                        let d = day_c.as_ref()(
+                            &date,
+                            &self.replay_engine.get_slot(),
+                            self.replay_engine.get_program_data(),
+                            self.replay_engine.get_accounts(),
+                        );
+                        d.await;
+                    }
+                }
+                ReplayEvent::SlotStart(slot) => {
+                    if let Some(callback) = slot_callback.as_ref() {
+                        let slot_c = callback.lock().await;
+                        let d = slot_c.as_ref()(&slot);
+                        d.await;
+                    }
+                }
+                ReplayEvent::Instruction(replayed) => {
+                    let accounts = self.replay_engine.get_accounts();
+
+                    if let Some(ix_guard) = instruction_callback.as_ref() {
+                        let ix_c = ix_guard.lock().await;
+                        let c = ix_c.as_ref()(
+                            &replayed.slot,
+                            &replayed.transaction,
+                            &replayed.name,
+                            &replayed.instruction,
+                            accounts,
+                            &replayed.result);
+                        c.await;
+                    }
+                }
+                ReplayEvent::ProgramDeploy(deployment) => {
+                    if let Some(callback) = program_deploy_callback.as_ref() {
+                        let deploy_c = callback.lock().await;
+                        let d = deploy_c.as_ref()(&deployment, self.replay_engine.get_program_data());
+                        d.await;
+                    }
+                }
+                ReplayEvent::TransactionStart(..) => {}
+            }
+        }
 
-            let slot = Slot {
-                slot: whirlpool_transaction.slot,
-                block_height: whirlpool_transaction.block_height,
-                block_time: whirlpool_transaction.block_time,
+        if let Some(callback) = finish_callback.as_ref() {
+            let finish_c = callback.lock().await;
+            let f = finish_c.as_ref()(
+                &self.replay_engine.get_slot(),
+                self.replay_engine.get_program_data(),
+                self.replay_engine.get_accounts(),
+            );
+            f.await;
+        }
+
+        return Ok(());
+    }
+
+    // pull-based alternative to the callbacks
+    pub fn events(&mut self, cond: ReplayUntil) -> ReplayEvents<'_> {
+        return ReplayEvents::new(self, cond);
+    }
+
+    // advance the replay until the next event.
+    // None is returned when the until condition has been reached or there are no more transactions.
+    //
+    // the position is kept in the cursor, so replay can stop in the middle of a slot
+    // (or a transaction) and the next call resumes from there.
+    pub fn next_event(&mut self, cond: &ReplayUntil) -> Result<Option<ReplayEvent>, ReplayError> {
+        // the previous instruction matched the Inclusive until condition
+        if self.stop_pending {
+            self.stop_pending = false;
+            return Ok(None);
+        }
+
+        loop {
+            let cursor = match self.cursor.as_mut() {
+                Some(cursor) => cursor,
+                None => match self.transaction_stream.next()? {
+                    None => return Ok(None),
+                    Some(TransactionStreamItem::EndOfDay(date)) => return Ok(Some(ReplayEvent::DayEnd(date))),
+                    Some(TransactionStreamItem::Transaction(whirlpool_transaction)) => {
+                        if whirlpool_transaction.slot > self.resume_slot {
+                            self.cursor = Some(ReplayCursor::new(whirlpool_transaction));
+                        } else if whirlpool_transaction.slot == self.resume_slot {
+                            if let Some(position) = self.resume_position.take() {
+                                let cursor = ReplayCursor::resume(whirlpool_transaction, &position);
+                                // the instructions before the position are not observed by the verifier
+                                if cursor.transaction_started {
+                                    if let Some(verifier) = self.verifier.as_mut() {
+                                        verifier.mark_balance_changes_incomplete();
+                                    }
+                                }
+                                self.cursor = Some(cursor);
+                            }
+                        }
+                        continue;
+                    }
+                },
             };
+            let slot = cursor.slot;
+
+            if !cursor.slot_started {
+                if replay_until::should_stop_before_slot(cond, &slot) {
+                    return Ok(None);
+                }
 
-            if has_reached_until_condition(&cond, slot) {
-                break;
+                self.replay_engine
+                    .update_slot(slot.slot, slot.block_height, slot.block_time);
+                cursor.slot_started = true;
+                return Ok(Some(ReplayEvent::SlotStart(slot)));
             }
 
-            self.replay_engine
-                .update_slot(slot.slot, slot.block_height, slot.block_time);
+            let transaction = match cursor.transactions.get(cursor.transaction_index) {
+                Some(transaction) => Arc::clone(transaction),
+                None => {
+                    // all transactions in the slot have been replayed
+                    self.cursor = None;
+                    self.write_checkpoint_if_due(&slot)?;
+                    continue;
+                }
+            };
+
+            if !cursor.transaction_started {
+                if replay_until::should_stop_before_transaction(cond, &slot, &transaction) {
+                    return Ok(None);
+                }
 
-            if let Some(callback) = slot_callback.as_ref() {
-                let slot_c = callback.lock().await;
-                let d = slot_c.as_ref()(&slot);
-                d.await;
+                cursor.transaction_started = true;
+                return Ok(Some(ReplayEvent::TransactionStart(slot, transaction)));
             }
 
-            for transaction in whirlpool_transaction.transactions {
-                for instruction in transaction.clone().instructions {
-                    let name = instruction.name.clone();
-                    let payload = instruction.payload.to_string().clone();
-                    let decoded = decoded_instructions::from_json(&name, &payload).unwrap();
-
-                    match decoded {
-                        decoded_instructions::DecodedInstruction::ProgramDeployInstruction(
-                            deploy_instruction,
-                        ) => {
-                            self.replay_engine
-                                .update_program_data(deploy_instruction.program_data);
+            let instruction = match transaction.instructions.get(cursor.instruction_index) {
+                Some(instruction) => instruction,
+                None => {
+                    // all instructions in the transaction have been replayed
+                    cursor.transaction_index += 1;
+                    cursor.instruction_index = 0;
+                    cursor.transaction_started = false;
+
+                    if let Some(verifier) = self.verifier.as_mut() {
+                        verifier.verify_transaction(&slot, &transaction);
+                    }
+
+                    if replay_until::should_stop_after_transaction(cond, &transaction) {
+                        return Ok(None);
+                    }
+                    continue;
+                }
+            };
+
+            let whirlpool_instruction = match decode_transaction_instruction(&slot, &transaction, instruction)? {
+                decoded_instructions::DecodedInstruction::ProgramDeployInstruction(
+                    deploy_instruction,
+                ) => {
+                    self.replay_engine
+                        .update_program_data(deploy_instruction.program_data);
+                    cursor.instruction_index += 1;
+
+                    let program_data_hash = self.replay_engine.get_program_data_hash().clone();
+                    return Ok(Some(ReplayEvent::ProgramDeploy(ProgramDeployment {
+                        slot,
+                        signature: transaction.signature.clone(),
+                        version: self.program_version_registry.get_version(&program_data_hash).cloned(),
+                        program_data_hash,
+                    })));
+                }
+                decoded_instructions::DecodedInstruction::WhirlpoolInstruction(
+                    whirlpool_instruction,
+                ) => whirlpool_instruction,
+            };
+
+            // the until condition is evaluated on the recorded instruction, even if it is skipped by the pool filter
+            let bound = replay_until::match_instruction(
+                cond,
+                &slot,
+                &transaction,
+                &instruction.name,
+                &whirlpool_instruction,
+            );
+            if bound == Some(ReplayUntilBound::Exclusive) {
+                return Ok(None);
+            }
+
+            // narrowed instruction is replayed instead of the recorded one, but events carry the recorded one
+            let narrowed_instruction = match self.pool_filter.as_ref() {
+                None => None,
+                Some(pool_filter) => match pool_filter.filter_instruction(&whirlpool_instruction, self.replay_engine.get_accounts()) {
+                    FilteredInstruction::Replay => None,
+                    FilteredInstruction::ReplayAs(narrowed_instruction) => {
+                        if let Some(verifier) = self.verifier.as_mut() {
+                            verifier.mark_balance_changes_incomplete();
                         }
-                        decoded_instructions::DecodedInstruction::WhirlpoolInstruction(
-                            whirlpool_instruction,
-                        ) => {
-                            let result = self
-                                .replay_engine
-                                .replay_instruction(&whirlpool_instruction)
-                                .unwrap();
-
-                            let accounts = self.replay_engine.get_accounts();
-
-                            if let Some(ix_guard) = instruction_callback.as_ref() {
-                                let ix_c = ix_guard.lock().await;
-                                let c = ix_c.as_ref()(
-                                    &slot,
-                                    &transaction,
-                                    &name,
-                                    &whirlpool_instruction,
-                                    &accounts,
-                                    &result);
-                                c.await;
-                            }
+                        Some(narrowed_instruction)
+                    }
+                    filtered @ (FilteredInstruction::Skip | FilteredInstruction::UpdatePositionBundle { .. }) => {
+                        if let FilteredInstruction::UpdatePositionBundle { position_bundle, data } = filtered {
+                            self.replay_engine.update_account(&position_bundle, data);
+                        }
+                        if let Some(verifier) = self.verifier.as_mut() {
+                            verifier.mark_balance_changes_incomplete();
                         }
+                        cursor.instruction_index += 1;
+                        if bound == Some(ReplayUntilBound::Inclusive) {
+                            return Ok(None);
+                        }
+                        continue;
+                    }
+                },
+            };
+            let replayed_instruction = narrowed_instruction.as_ref().unwrap_or(&whirlpool_instruction);
+
+            // FailurePolicy::ReturnError: the failed instruction has not been written back and is skipped on resume
+            // (the same as RecordAndContinue). the cursor is not advanced on the other errors
+            // (e.g. missing account), so the instruction will be retried on resume.
+            let result = match self.replay_engine.replay_instruction(replayed_instruction) {
+                Ok(result) => result,
+                Err(source) => {
+                    if let ErrorCode::ReplayedInstructionFailed(_) = source {
+                        cursor.instruction_index += 1;
+                        self.stop_pending = bound == Some(ReplayUntilBound::Inclusive);
                     }
+                    return Err(ReplayError::InstructionReplayFailed {
+                        slot: slot.slot,
+                        signature: transaction.signature.clone(),
+                        name: instruction.name.clone(),
+                        source,
+                    });
                 }
+            };
+            cursor.instruction_index += 1;
+
+            if let Some(verifier) = self.verifier.as_mut() {
+                verifier.verify_instruction(&slot, &transaction, &instruction.name, replayed_instruction, &result);
             }
+            if let Some(checkpointer) = self.checkpointer.as_mut() {
+                checkpointer.on_instruction_replayed();
+            }
+
+            self.stop_pending = bound == Some(ReplayUntilBound::Inclusive);
+            return Ok(Some(ReplayEvent::Instruction(ReplayedInstruction {
+                slot,
+                name: instruction.name.clone(),
+                transaction,
+                instruction: whirlpool_instruction,
+                result,
+            })));
+        }
+    }
+
+    fn write_checkpoint_if_due(&mut self, slot: &Slot) -> Result<(), ReplayError> {
+        let is_due = match self.checkpointer.as_ref() {
+            Some(checkpointer) => checkpointer.is_due(slot),
+            None => false,
+        };
 
-            next_whirlpool_transaction = self.transaction_iter.next();
+        if is_due {
+            let state = self.get_whirlpool_state();
+            self.checkpointer.as_mut().unwrap().write(&state)?;
         }
+        return Ok(());
+    }
+}
+
+// position of the replay in the slot being replayed
+struct ReplayCursor {
+    slot: Slot,
+    transactions: Vec<Arc<Transaction>>,
+    slot_started: bool,
+    transaction_index: usize,
+    transaction_started: bool,
+    instruction_index: usize,
+}
+
+impl ReplayCursor {
+    fn new(whirlpool_transaction: WhirlpoolTransaction) -> ReplayCursor {
+        return ReplayCursor {
+            slot: Slot {
+                slot: whirlpool_transaction.slot,
+                block_height: whirlpool_transaction.block_height,
+                block_time: whirlpool_transaction.block_time,
+            },
+            transactions: whirlpool_transaction.transactions.into_iter().map(Arc::new).collect(),
+            slot_started: false,
+            transaction_index: 0,
+            transaction_started: false,
+            instruction_index: 0,
+        };
     }
 
+    // the slot has already been started (the engine is at the slot of the saved state)
+    fn resume(whirlpool_transaction: WhirlpoolTransaction, position: &WhirlpoolStatePosition) -> ReplayCursor {
+        let mut cursor = ReplayCursor::new(whirlpool_transaction);
+        cursor.slot_started = true;
+        cursor.transaction_index = position.transaction_index as usize;
+        cursor.instruction_index = position.instruction_index as usize;
+        cursor.transaction_started = position.instruction_index > 0;
+        return cursor;
+    }
 }
 
-fn has_reached_until_condition(cond: &ReplayUntil, slot: Slot) -> bool {
-    match cond {
-        ReplayUntil::End => false,
-        ReplayUntil::Slot(until_slot) => slot.slot > *until_slot,
-        ReplayUntil::BlockHeight(until_block_height) => slot.block_height > *until_block_height,
-        ReplayUntil::BlockTime(until_block_time) => slot.block_time > *until_block_time,
+fn decode_transaction_instruction(
+    slot: &Slot,
+    transaction: &Transaction,
+    instruction: &TransactionInstruction,
+) -> Result<decoded_instructions::DecodedInstruction, ReplayError> {
+    // already decoded by the ingestion pipeline
+    if let Some(decoded) = instruction.decoded.as_ref() {
+        return Ok(decoded.clone());
     }
-}
\ No newline at end of file
+
+    let name = &instruction.name;
+    return decoded_instructions::from_json_value(name, &instruction.payload).map_err(|source| {
+        ReplayError::InvalidInstruction {
+            slot: slot.slot,
+            signature: transaction.signature.clone(),
+            name: name.clone(),
+            source,
+        }
+    });
+}
+
+pub(crate) fn parse_yyyymmdd(yyyymmdd: &String) -> Result<chrono::NaiveDate, ReplayError> {
+    return chrono::NaiveDate::parse_from_str(yyyymmdd, "%Y%m%d")
+        .map_err(|_| ReplayError::InvalidDate(yyyymmdd.clone()));
+}
+
+// returns (start, end, previous day of start)
+pub(crate) fn parse_date_range(
+    start_yyyymmdd: &String,
+    end_yyyymmdd: Option<&String>,
+) -> Result<(chrono::NaiveDate, Option<chrono::NaiveDate>, chrono::NaiveDate), ReplayError> {
+    let start = parse_yyyymmdd(start_yyyymmdd)?;
+    let end = end_yyyymmdd.map(parse_yyyymmdd).transpose()?;
+    if let Some(end) = end {
+        if end < start {
+            return Err(ReplayError::InvalidDateRange {
+                start: start_yyyymmdd.clone(),
+                end: end.format("%Y%m%d").to_string(),
+            });
+        }
+    }
+
+    let previous = start
+        .pred_opt()
+        .ok_or_else(|| ReplayError::InvalidDate(start_yyyymmdd.clone()))?;
+    return Ok((start, end, previous));
+}
+
+fn build_storage_loader(storage: Arc<dyn StorageBackend>) -> WhirlpoolTransactionLoader {
+    return Box::new(move |date| storage.load_whirlpool_transactions(date));
+}
@@ -0,0 +1,264 @@
+use std::io::Read;
+
+use chrono::NaiveDate;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::errors::ReplayError;
+use crate::io;
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/*
+
+Storage Manifest JSON Schema
+
+A manifest (manifest.json) is placed at the root of a storage to describe the files in it:
+
+{
+  files: [
+    { path: String(relative path), size: u64, sha256: String(hex encoding) },
+    ...
+  ],
+  dates: [
+    {
+      date: String(yyyymmdd),
+      startSlot: u64 | null(no transaction file),
+      endSlot: u64(slot of the state),
+      stateFile: String(relative path),
+      transactionFile: String(relative path) | null,
+    },
+    ...
+  ]
+}
+
+dates is optional (manifests without dates only describe the files).
+
+*/
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageManifest {
+    pub files: Vec<ManifestFile>,
+    #[serde(default)]
+    pub dates: Vec<ManifestDate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestFile {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestDate {
+    pub date: String,
+    // slot range of the transaction file (startSlot is None if the storage has only the state of the date)
+    pub start_slot: Option<u64>,
+    pub end_slot: u64,
+    pub state_file: String,
+    pub transaction_file: Option<String>,
+}
+
+impl StorageManifest {
+    pub fn get_file(&self, relative_path: &String) -> Option<&ManifestFile> {
+        return self.files.iter().find(|file| file.path == *relative_path);
+    }
+
+    pub fn get_date(&self, date: &NaiveDate) -> Option<&ManifestDate> {
+        let yyyymmdd = date.format("%Y%m%d").to_string();
+        return self.dates.iter().find(|manifest_date| manifest_date.date == yyyymmdd);
+    }
+
+    // dates from start to end (inclusive) whose transaction file is in the storage
+    pub fn list_transaction_dates(&self, start: &NaiveDate, end: &NaiveDate) -> Vec<NaiveDate> {
+        let mut dates: Vec<NaiveDate> = self
+            .dates
+            .iter()
+            .filter(|manifest_date| manifest_date.transaction_file.is_some())
+            .filter_map(|manifest_date| NaiveDate::parse_from_str(&manifest_date.date, "%Y%m%d").ok())
+            .filter(|date| date >= start && date <= end)
+            .collect();
+        dates.sort();
+        return dates;
+    }
+
+    // add or replace (by path)
+    pub fn put_file(&mut self, file: ManifestFile) {
+        self.files.retain(|existing| existing.path != file.path);
+        self.files.push(file);
+        self.files.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    // add or replace (by date)
+    pub fn put_date(&mut self, date: ManifestDate) {
+        self.dates.retain(|existing| existing.date != date.date);
+        self.dates.push(date);
+        self.dates.sort_by(|a, b| a.date.cmp(&b.date));
+    }
+}
+
+// path is used in error messages
+pub fn read_storage_manifest<R: Read>(reader: R, path: &String) -> Result<StorageManifest, ReplayError> {
+    return serde_json::from_reader(reader).map_err(|source| ReplayError::InvalidManifest {
+        path: path.clone(),
+        source,
+    });
+}
+
+// write manifest.json via a temporary file, so readers never see a partially written manifest
+pub fn write_storage_manifest(file_path: &String, manifest: &StorageManifest) -> Result<(), ReplayError> {
+    let temporary_file_path = format!("{}.part", file_path);
+    let io_error = |source: std::io::Error| ReplayError::Io {
+        path: file_path.clone(),
+        source,
+    };
+
+    let json = serde_json::to_vec_pretty(manifest).map_err(|source| ReplayError::InvalidManifest {
+        path: file_path.clone(),
+        source,
+    })?;
+    std::fs::write(&temporary_file_path, json).map_err(io_error)?;
+    std::fs::rename(&temporary_file_path, file_path).map_err(io_error)?;
+    return Ok(());
+}
+
+// size and sha256 of a file in a local storage
+pub fn build_manifest_file(base_path: &String, relative_path: &String) -> Result<ManifestFile, ReplayError> {
+    let file_path = format!("{}/{}", base_path, relative_path);
+    let metadata = std::fs::metadata(&file_path).map_err(|source| ReplayError::Io {
+        path: file_path.clone(),
+        source,
+    })?;
+    return Ok(ManifestFile {
+        path: relative_path.clone(),
+        size: metadata.len(),
+        sha256: get_file_sha256(&file_path)?,
+    });
+}
+
+// sha256 (hex) of the local file
+pub fn get_file_sha256(file_path: &String) -> Result<String, ReplayError> {
+    let mut file = io::open_local_file(file_path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(|source| ReplayError::Io {
+        path: file_path.clone(),
+        source,
+    })?;
+    return Ok(hex_encode(&hasher.finalize()));
+}
+
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_date(yyyymmdd: &str, transaction_file: bool) -> ManifestDate {
+        return ManifestDate {
+            date: yyyymmdd.to_string(),
+            start_slot: if transaction_file { Some(100) } else { None },
+            end_slot: 200,
+            state_file: format!("whirlpool-state-{}.json.gz", yyyymmdd),
+            transaction_file: if transaction_file {
+                Some(format!("whirlpool-transaction-{}.jsonl.gz", yyyymmdd))
+            } else {
+                None
+            },
+        };
+    }
+
+    fn build_file(path: &str, size: u64) -> ManifestFile {
+        return ManifestFile {
+            path: path.to_string(),
+            size,
+            sha256: "00".repeat(32),
+        };
+    }
+
+    #[test]
+    fn test_put_and_get() {
+        let mut manifest = StorageManifest::default();
+        manifest.put_file(build_file("b", 1));
+        manifest.put_file(build_file("a", 2));
+        manifest.put_file(build_file("b", 3));
+
+        // sorted by path, replaced by path
+        let paths: Vec<&str> = manifest.files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, vec!["a", "b"]);
+        assert_eq!(manifest.get_file(&"b".to_string()).unwrap().size, 3);
+        assert!(manifest.get_file(&"c".to_string()).is_none());
+
+        manifest.put_date(build_date("20220407", true));
+        manifest.put_date(build_date("20220405", false));
+        manifest.put_date(build_date("20220406", true));
+        manifest.put_date(build_date("20220407", false));
+
+        // sorted by date, replaced by date
+        let dates: Vec<&str> = manifest.dates.iter().map(|date| date.date.as_str()).collect();
+        assert_eq!(dates, vec!["20220405", "20220406", "20220407"]);
+        let date = NaiveDate::from_ymd_opt(2022, 4, 7).unwrap();
+        assert_eq!(manifest.get_date(&date), Some(&build_date("20220407", false)));
+        assert!(manifest.get_date(&NaiveDate::from_ymd_opt(2022, 4, 8).unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_list_transaction_dates() {
+        let mut manifest = StorageManifest::default();
+        for (yyyymmdd, transaction_file) in [("20220405", false), ("20220406", true), ("20220407", true), ("20220409", true)] {
+            manifest.put_date(build_date(yyyymmdd, transaction_file));
+        }
+
+        let date = |day: u32| NaiveDate::from_ymd_opt(2022, 4, day).unwrap();
+        // dates with only the state are not listed
+        assert_eq!(manifest.list_transaction_dates(&date(1), &date(30)), vec![date(6), date(7), date(9)]);
+        // inclusive
+        assert_eq!(manifest.list_transaction_dates(&date(7), &date(9)), vec![date(7), date(9)]);
+        assert!(manifest.list_transaction_dates(&date(8), &date(8)).is_empty());
+    }
+
+    #[test]
+    fn test_read_and_write_manifest() {
+        // manifest without dates (only the files are described)
+        let json = r#"{"files":[{"path":"2022/0406/whirlpool-state-20220406.json.gz","size":10,"sha256":"ab"}]}"#;
+        let manifest = read_storage_manifest(json.as_bytes(), &"manifest.json".to_string()).unwrap();
+        assert_eq!(manifest.files.len(), 1);
+        assert!(manifest.dates.is_empty());
+
+        assert!(matches!(
+            read_storage_manifest("{".as_bytes(), &"manifest.json".to_string()),
+            Err(ReplayError::InvalidManifest { .. })
+        ));
+
+        let dir_path = std::env::temp_dir().join(format!("whirlpool-replayer-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let dir_path = dir_path.to_string_lossy().to_string();
+
+        std::fs::write(format!("{}/data", dir_path), b"abc").unwrap();
+        let mut manifest = StorageManifest::default();
+        manifest.put_file(build_manifest_file(&dir_path, &"data".to_string()).unwrap());
+        manifest.put_date(build_date("20220406", true));
+        assert_eq!(
+            manifest.get_file(&"data".to_string()).unwrap(),
+            &ManifestFile {
+                path: "data".to_string(),
+                size: 3,
+                sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+            }
+        );
+
+        let manifest_path = format!("{}/{}", dir_path, MANIFEST_FILE_NAME);
+        write_storage_manifest(&manifest_path, &manifest).unwrap();
+        let file = io::open_local_file(&manifest_path).unwrap();
+        assert_eq!(read_storage_manifest(file, &manifest_path).unwrap(), manifest);
+        // no temporary file is left
+        assert!(!std::path::Path::new(&format!("{}.part", manifest_path)).exists());
+
+        std::fs::remove_dir_all(&dir_path).unwrap();
+    }
+}
@@ -0,0 +1,381 @@
+use std::io::{BufRead, BufReader, Read};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+
+use flate2::read::GzDecoder;
+use replay_engine::decoded_instructions;
+
+use crate::errors::ReplayError;
+use crate::schema::WhirlpoolTransaction;
+
+// Transaction files are read by a pipeline so that the replay thread only executes instructions.
+//
+//   reader thread: decompress and split into records (lines), records are sent to workers in batches
+//   worker threads: parse records into WhirlpoolTransaction and decode instructions (TransactionInstruction.decoded)
+//   replay thread: receives the results of batches in the order of lines
+//
+// all channels are bounded, so the read-ahead is limited to about 2 * queue_capacity batches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineConfig {
+    // threads to parse and decode records
+    pub worker_threads: usize,
+    // records (slots) sent to a worker at once
+    pub batch_size: usize,
+    // batches waiting for workers (and results waiting for the replay thread)
+    pub queue_capacity: usize,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        // leave a core for the replay thread
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        return PipelineConfig {
+            worker_threads: cores.saturating_sub(1).clamp(1, 4),
+            batch_size: 64,
+            queue_capacity: 16,
+        };
+    }
+}
+
+type BatchResult = Vec<Result<WhirlpoolTransaction, ReplayError>>;
+
+struct Batch<T> {
+    records: Vec<T>,
+    result_sender: SyncSender<BatchResult>,
+}
+
+// read gzipped transaction file with the pipeline (path is used in error messages).
+// the iterator ends after the first error.
+pub fn read_whirlpool_transactions_pipelined<R: Read + Send + 'static>(
+    reader: R,
+    path: &String,
+    config: &PipelineConfig,
+) -> impl Iterator<Item = Result<WhirlpoolTransaction, ReplayError>> {
+    let reader_path = path.clone();
+    let lines = BufReader::new(GzDecoder::new(reader))
+        .lines()
+        .enumerate()
+        .map(move |(index, line)| match line {
+            Ok(line) => Ok((index + 1, line)),
+            Err(source) => Err(ReplayError::Io {
+                path: reader_path.clone(),
+                source,
+            }),
+        });
+
+    let worker_path = path.clone();
+    return run_pipeline(lines, move |(line, jsonl)| parse_line(line, &jsonl, &worker_path), path, config);
+}
+
+// records are read from source on the reader thread and converted into WhirlpoolTransaction on worker threads.
+// source is not read any more after an error.
+pub(crate) fn run_pipeline<T, S, F>(
+    source: S,
+    convert: F,
+    path: &String,
+    config: &PipelineConfig,
+) -> impl Iterator<Item = Result<WhirlpoolTransaction, ReplayError>>
+where
+    T: Send + 'static,
+    S: Iterator<Item = Result<T, ReplayError>> + Send + 'static,
+    F: Fn(T) -> Result<WhirlpoolTransaction, ReplayError> + Send + Sync + 'static,
+{
+    let queue_capacity = config.queue_capacity.max(1);
+    let (batch_sender, batch_receiver) = sync_channel::<Batch<T>>(queue_capacity);
+    let (result_sender, result_receiver) = sync_channel::<Receiver<BatchResult>>(queue_capacity);
+
+    let batch_receiver = Arc::new(Mutex::new(batch_receiver));
+    let convert = Arc::new(convert);
+    for _ in 0..config.worker_threads.max(1) {
+        let batch_receiver = batch_receiver.clone();
+        let convert = convert.clone();
+        std::thread::spawn(move || loop {
+            // the lock is released as soon as a batch is received
+            let batch = match batch_receiver.lock().unwrap().recv() {
+                Ok(batch) => batch,
+                // the reader thread has finished
+                Err(_) => return,
+            };
+
+            let mut results = Vec::with_capacity(batch.records.len());
+            for record in batch.records {
+                let result = convert(record);
+                let is_err = result.is_err();
+                results.push(result);
+                if is_err {
+                    break;
+                }
+            }
+            let _ = batch.result_sender.send(results);
+        });
+    }
+
+    let batch_size = config.batch_size.max(1);
+    std::thread::spawn(move || {
+        let mut source = source;
+        loop {
+            let mut records = Vec::with_capacity(batch_size);
+            let mut source_error = None;
+            for record in source.by_ref().take(batch_size) {
+                match record {
+                    Ok(record) => records.push(record),
+                    Err(err) => {
+                        source_error = Some(err);
+                        break;
+                    }
+                }
+            }
+
+            let end_of_source = records.len() < batch_size || source_error.is_some();
+            if !records.is_empty() {
+                let (batch_result_sender, batch_result_receiver) = sync_channel(1);
+                // send fails if the iterator has been dropped
+                if result_sender.send(batch_result_receiver).is_err() {
+                    return;
+                }
+                let batch = Batch {
+                    records,
+                    result_sender: batch_result_sender,
+                };
+                if batch_sender.send(batch).is_err() {
+                    return;
+                }
+            }
+
+            if let Some(err) = source_error {
+                let (batch_result_sender, batch_result_receiver) = sync_channel(1);
+                let _ = batch_result_sender.send(vec![Err(err)]);
+                let _ = result_sender.send(batch_result_receiver);
+            }
+            if end_of_source {
+                return;
+            }
+        }
+    });
+
+    return PipelinedTransactionIterator {
+        result_receiver,
+        current: Vec::new().into_iter(),
+        path: path.clone(),
+        finished: false,
+    };
+}
+
+fn parse_line(line: usize, jsonl: &String, path: &String) -> Result<WhirlpoolTransaction, ReplayError> {
+    let mut whirlpool_transaction =
+        serde_json::from_str::<WhirlpoolTransaction>(jsonl).map_err(|source| ReplayError::InvalidTransactionFile {
+            path: path.clone(),
+            line,
+            source,
+        })?;
+    decode_instructions(&mut whirlpool_transaction);
+    return Ok(whirlpool_transaction);
+}
+
+// instructions failed to decode are left as None, the error is reported when they are replayed
+pub fn decode_instructions(whirlpool_transaction: &mut WhirlpoolTransaction) {
+    for transaction in whirlpool_transaction.transactions.iter_mut() {
+        for instruction in transaction.instructions.iter_mut() {
+            instruction.decoded = decoded_instructions::from_json_value(&instruction.name, &instruction.payload).ok();
+        }
+    }
+}
+
+struct PipelinedTransactionIterator {
+    result_receiver: Receiver<Receiver<BatchResult>>,
+    current: std::vec::IntoIter<Result<WhirlpoolTransaction, ReplayError>>,
+    path: String,
+    finished: bool,
+}
+
+impl Iterator for PipelinedTransactionIterator {
+    type Item = Result<WhirlpoolTransaction, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.finished {
+                return None;
+            }
+
+            if let Some(result) = self.current.next() {
+                self.finished = result.is_err();
+                return Some(result);
+            }
+
+            let batch_result_receiver = match self.result_receiver.recv() {
+                Ok(batch_result_receiver) => batch_result_receiver,
+                // all batches have been received
+                Err(_) => return None,
+            };
+            match batch_result_receiver.recv() {
+                Ok(batch_result) => self.current = batch_result.into_iter(),
+                // worker has panicked
+                Err(_) => {
+                    self.finished = true;
+                    return Some(Err(ReplayError::Io {
+                        path: self.path.clone(),
+                        source: std::io::Error::new(std::io::ErrorKind::Other, "transaction pipeline worker terminated"),
+                    }));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn build_config(worker_threads: usize) -> PipelineConfig {
+        return PipelineConfig {
+            worker_threads,
+            batch_size: 4,
+            queue_capacity: 2,
+        };
+    }
+
+    fn build_whirlpool_transaction(slot: u64) -> WhirlpoolTransaction {
+        return WhirlpoolTransaction {
+            slot,
+            block_height: slot,
+            block_time: slot as i64,
+            transactions: vec![],
+        };
+    }
+
+    fn io_error(message: &str) -> ReplayError {
+        return ReplayError::Io {
+            path: "test".to_string(),
+            source: std::io::Error::other(message.to_string()),
+        };
+    }
+
+    // source of 0, 1, 2, ... (an error at error_at) that counts the records read and notifies when dropped
+    struct TestSource {
+        next: u64,
+        error_at: Option<u64>,
+        read: Arc<AtomicUsize>,
+        dropped: Option<SyncSender<()>>,
+    }
+
+    impl Iterator for TestSource {
+        type Item = Result<u64, ReplayError>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let record = self.next;
+            self.next += 1;
+            self.read.fetch_add(1, Ordering::SeqCst);
+            if self.error_at == Some(record) {
+                return Some(Err(io_error("source")));
+            }
+            return Some(Ok(record));
+        }
+    }
+
+    impl Drop for TestSource {
+        fn drop(&mut self) {
+            if let Some(dropped) = self.dropped.take() {
+                let _ = dropped.send(());
+            }
+        }
+    }
+
+    fn build_source(error_at: Option<u64>) -> (TestSource, Arc<AtomicUsize>) {
+        let read = Arc::new(AtomicUsize::new(0));
+        let source = TestSource {
+            next: 0,
+            error_at,
+            read: read.clone(),
+            dropped: None,
+        };
+        return (source, read);
+    }
+
+    #[test]
+    fn test_run_pipeline_preserves_order() {
+        let source = (0..1000u64).map(Ok);
+        let iter = run_pipeline(
+            source,
+            |record: u64| {
+                // workers finish batches out of order
+                std::thread::sleep(Duration::from_micros((record * 37) % 5 * 100));
+                return Ok(build_whirlpool_transaction(record));
+            },
+            &"test".to_string(),
+            &build_config(4),
+        );
+
+        let slots: Vec<u64> = iter.map(|result| result.unwrap().slot).collect();
+        assert_eq!(slots, (0..1000u64).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn test_run_pipeline_stops_after_convert_error() {
+        let (source, _) = build_source(None);
+        // 10 is in the middle of the third batch (8..12)
+        let mut iter = run_pipeline(
+            source.take(100),
+            |record: u64| {
+                if record == 10 {
+                    return Err(io_error("convert"));
+                }
+                return Ok(build_whirlpool_transaction(record));
+            },
+            &"test".to_string(),
+            &build_config(3),
+        );
+
+        for slot in 0..10 {
+            assert_eq!(iter.next().unwrap().unwrap().slot, slot);
+        }
+        assert!(matches!(iter.next(), Some(Err(ReplayError::Io { .. }))));
+        assert!(iter.next().is_none());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_run_pipeline_delivers_source_error_after_records() {
+        // 6 is in the middle of the second batch (4..8)
+        let (source, read) = build_source(Some(6));
+        let mut iter = run_pipeline(
+            source,
+            |record: u64| Ok(build_whirlpool_transaction(record)),
+            &"test".to_string(),
+            &build_config(2),
+        );
+
+        for slot in 0..6 {
+            assert_eq!(iter.next().unwrap().unwrap().slot, slot);
+        }
+        match iter.next() {
+            Some(Err(ReplayError::Io { source, .. })) => assert_eq!(source.to_string(), "source"),
+            _ => panic!("expected the source error"),
+        }
+        assert!(iter.next().is_none());
+
+        // source is not read after the error
+        assert_eq!(read.load(Ordering::SeqCst), 7);
+    }
+
+    #[test]
+    fn test_run_pipeline_drop_early() {
+        // endless source
+        let (mut source, _) = build_source(None);
+        let (dropped_sender, dropped_receiver) = sync_channel(1);
+        source.dropped = Some(dropped_sender);
+
+        let mut iter = run_pipeline(
+            source,
+            |record: u64| Ok(build_whirlpool_transaction(record)),
+            &"test".to_string(),
+            &build_config(2),
+        );
+        assert_eq!(iter.next().unwrap().unwrap().slot, 0);
+        drop(iter);
+
+        // the reader thread stops (and drops the source) instead of blocking on the bounded channels
+        assert!(dropped_receiver.recv_timeout(Duration::from_secs(10)).is_ok());
+    }
+}
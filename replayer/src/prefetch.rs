@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+
+use chrono::NaiveDate;
+
+use crate::errors::ReplayError;
+use crate::io;
+use crate::storage::{StorageBackend, StorageReader};
+use crate::WhirlpoolTransactionIterator;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefetchConfig {
+    // number of days to prefetch ahead of the day being replayed
+    pub depth: usize,
+    // prefetch state files (full and delta) too (transaction files only by default)
+    pub include_state: bool,
+    // bandwidth limit of prefetch (None for unlimited)
+    pub max_bytes_per_second: Option<u64>,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        return PrefetchConfig {
+            depth: 1,
+            include_state: false,
+            max_bytes_per_second: None,
+        };
+    }
+}
+
+// PrefetchingStorage wraps a storage with cache (e.g. CachedStorage) and downloads the files of the following days
+// into the cache on a background thread when the transaction file of a day is loaded.
+// so the next day can start without waiting for the download while the current day is being replayed.
+//
+// prefetch errors are ignored, the same error will be reported when the file is loaded.
+// if the replay reaches a file still being prefetched, it waits for the prefetch (bandwidth limit is still applied).
+pub struct PrefetchingStorage {
+    storage: Arc<dyn StorageBackend>,
+    config: PrefetchConfig,
+    sender: Mutex<Sender<String>>,
+    scheduled: Mutex<HashSet<String>>,
+}
+
+impl PrefetchingStorage {
+    pub fn new(storage: Arc<dyn StorageBackend>, config: PrefetchConfig) -> PrefetchingStorage {
+        let (sender, receiver) = channel::<String>();
+
+        // the worker exits when PrefetchingStorage is dropped (after the current download)
+        let worker_storage = storage.clone();
+        let max_bytes_per_second = config.max_bytes_per_second;
+        std::thread::spawn(move || {
+            for relative_path in receiver {
+                let _ = worker_storage.prefetch(&relative_path, max_bytes_per_second);
+            }
+        });
+
+        return PrefetchingStorage {
+            storage,
+            config,
+            sender: Mutex::new(sender),
+            scheduled: Mutex::new(HashSet::new()),
+        };
+    }
+
+    pub fn get_config(&self) -> &PrefetchConfig {
+        return &self.config;
+    }
+
+    fn schedule_following_days(&self, date: &NaiveDate) {
+        let mut scheduled = self.scheduled.lock().unwrap();
+        let sender = self.sender.lock().unwrap();
+
+        for next_date in date.iter_days().skip(1).take(self.config.depth) {
+            let transaction_relative_path = io::get_whirlpool_transaction_file_relative_path(&next_date);
+            let mut relative_paths = vec![io::get_binary_file_relative_path(&transaction_relative_path), transaction_relative_path];
+            // a day has either a full state or a delta, in binary or JSON (the missing ones fail and are ignored)
+            if self.config.include_state {
+                let state_relative_path = io::get_whirlpool_state_file_relative_path(&next_date);
+                relative_paths.push(io::get_binary_file_relative_path(&state_relative_path));
+                relative_paths.push(state_relative_path);
+                relative_paths.push(io::get_whirlpool_state_delta_file_relative_path(&next_date));
+            }
+
+            for relative_path in relative_paths {
+                if scheduled.insert(relative_path.clone()) {
+                    // send fails only if the worker has panicked
+                    let _ = sender.send(relative_path);
+                }
+            }
+        }
+    }
+}
+
+impl StorageBackend for PrefetchingStorage {
+    fn open(&self, relative_path: &String) -> Result<StorageReader, ReplayError> {
+        return self.storage.open(relative_path);
+    }
+
+    fn exists(&self, relative_path: &String) -> Result<bool, ReplayError> {
+        return self.storage.exists(relative_path);
+    }
+
+    fn open_at(&self, relative_path: &String, offset: u64) -> Result<io::RangeReader, ReplayError> {
+        return self.storage.open_at(relative_path, offset);
+    }
+
+    fn get_location(&self, relative_path: &String) -> String {
+        return self.storage.get_location(relative_path);
+    }
+
+    fn get_size(&self, relative_path: &String) -> Result<Option<u64>, ReplayError> {
+        return self.storage.get_size(relative_path);
+    }
+
+    fn prefetch(&self, relative_path: &String, max_bytes_per_second: Option<u64>) -> Result<(), ReplayError> {
+        return self.storage.prefetch(relative_path, max_bytes_per_second);
+    }
+
+    fn load_manifest(&self) -> Result<Option<crate::manifest::StorageManifest>, ReplayError> {
+        return self.storage.load_manifest();
+    }
+
+    fn load_whirlpool_state(&self, date: &NaiveDate) -> Result<crate::schema::WhirlpoolState, ReplayError> {
+        return self.storage.load_whirlpool_state(date);
+    }
+
+    fn load_whirlpool_transactions(&self, date: &NaiveDate) -> Result<WhirlpoolTransactionIterator, ReplayError> {
+        // scheduled after the file of the day is ready not to compete with its download
+        let transaction_iter = self.storage.load_whirlpool_transactions(date)?;
+        self.schedule_following_days(date);
+        return Ok(transaction_iter);
+    }
+
+    fn list_transaction_dates(&self, start: &NaiveDate, end: &NaiveDate) -> Result<Vec<NaiveDate>, ReplayError> {
+        return self.storage.list_transaction_dates(start, end);
+    }
+}
@@ -0,0 +1,344 @@
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+
+use crate::errors::ReplayError;
+use crate::io;
+use crate::manifest::{
+    build_manifest_file, read_storage_manifest, write_storage_manifest, ManifestDate, StorageManifest,
+    MANIFEST_FILE_NAME,
+};
+use crate::replay_event::ReplayEvent;
+use crate::schema::WhirlpoolState;
+use crate::storage::{open_binary_or_json, StorageBackend};
+use crate::{parse_date_range, FailurePolicy, ReplayUntil, WhirlpoolReplayer};
+
+// StorageProducer builds a storage (e.g. a mirror of the public storage) from a starting state and transaction files.
+//
+//   {output}/{yyyy}/{mmdd}/whirlpool-state-{yyyymmdd}.json.gz        replayed state at the end of the day
+//   {output}/{yyyy}/{mmdd}/whirlpool-transaction-{yyyymmdd}.jsonl.gz  copied from the transaction storage (or .bin.zst)
+//   {output}/manifest.json                                            files (size, sha256) and dates (slot range)
+//
+// the starting state is the state at the end of the previous day of start_yyyymmdd.
+// an existing manifest in the output is extended, so a storage can be produced incrementally.
+// the manifest is written after each day, so it describes only the complete days if the producer is interrupted.
+// files are written via {file}.part, so the output never has a partially written file.
+// a failed instruction stops the producer (a day with failed instructions is never published).
+pub struct StorageProducer {
+    output_dir_path: String,
+    manifest: StorageManifest,
+}
+
+impl StorageProducer {
+    pub fn new(output_dir_path: &String) -> Result<StorageProducer, ReplayError> {
+        std::fs::create_dir_all(output_dir_path).map_err(|source| ReplayError::Io {
+            path: output_dir_path.clone(),
+            source,
+        })?;
+
+        let manifest_path = format!("{}/{}", output_dir_path, MANIFEST_FILE_NAME);
+        let manifest = if Path::new(&manifest_path).exists() {
+            read_storage_manifest(io::open_local_file(&manifest_path)?, &manifest_path)?
+        } else {
+            StorageManifest::default()
+        };
+
+        return Ok(StorageProducer {
+            output_dir_path: output_dir_path.clone(),
+            manifest,
+        });
+    }
+
+    pub fn get_manifest(&self) -> &StorageManifest {
+        return &self.manifest;
+    }
+
+    // replay from start_yyyymmdd to end_yyyymmdd (inclusive, None for the latest available transaction file).
+    // returns the produced dates.
+    pub fn produce(
+        &mut self,
+        state: WhirlpoolState,
+        transaction_storage: Arc<dyn StorageBackend>,
+        start_yyyymmdd: &String,
+        end_yyyymmdd: Option<&String>,
+    ) -> Result<Vec<NaiveDate>, ReplayError> {
+        let (_, _, previous) = parse_date_range(start_yyyymmdd, end_yyyymmdd)?;
+
+        // the starting state is kept as the state of the previous day if the storage doesn't have it yet
+        if self.manifest.get_date(&previous).is_none() {
+            let state_file = io::get_whirlpool_state_file_relative_path(&previous);
+            self.write_file(&state_file, |file_path| io::save_to_whirlpool_state_file(file_path, &state))?;
+            self.manifest.put_file(build_manifest_file(&self.output_dir_path, &state_file)?);
+            self.manifest.put_date(ManifestDate {
+                date: previous.format("%Y%m%d").to_string(),
+                start_slot: None,
+                end_slot: state.slot,
+                state_file,
+                transaction_file: None,
+            });
+            self.write_manifest()?;
+        }
+
+        let mut replayer =
+            WhirlpoolReplayer::build_with_state_and_storage(state, transaction_storage.clone(), start_yyyymmdd, end_yyyymmdd)?;
+        replayer.set_failure_policy(FailurePolicy::ReturnError);
+
+        let mut produced = Vec::new();
+        let mut start_slot = None;
+        while let Some(event) = replayer.next_event(&ReplayUntil::End)? {
+            match event {
+                ReplayEvent::SlotStart(slot) => {
+                    start_slot.get_or_insert(slot.slot);
+                }
+                ReplayEvent::DayEnd(date) => {
+                    let state = replayer.get_whirlpool_state();
+                    self.produce_day(&date, start_slot.take().unwrap_or(state.slot), &state, transaction_storage.as_ref())?;
+                    produced.push(date);
+                }
+                _ => {}
+            }
+        }
+
+        return Ok(produced);
+    }
+
+    fn produce_day(
+        &mut self,
+        date: &NaiveDate,
+        start_slot: u64,
+        state: &WhirlpoolState,
+        transaction_storage: &dyn StorageBackend,
+    ) -> Result<(), ReplayError> {
+        let state_file = io::get_whirlpool_state_file_relative_path(date);
+        self.write_file(&state_file, |file_path| io::save_to_whirlpool_state_file(file_path, state))?;
+
+        // copied as is (no need to re-encode), the same file as the replayer has read
+        let (mut reader, transaction_file) =
+            open_binary_or_json(transaction_storage, &io::get_whirlpool_transaction_file_relative_path(date))?;
+        self.write_file(&transaction_file, |file_path| {
+            let io_error = |source: std::io::Error| ReplayError::Io {
+                path: file_path.clone(),
+                source,
+            };
+            let mut writer = File::create(file_path).map_err(io_error)?;
+            std::io::copy(&mut reader, &mut writer).map_err(io_error)?;
+            return writer.sync_all().map_err(io_error);
+        })?;
+
+        self.manifest.put_file(build_manifest_file(&self.output_dir_path, &state_file)?);
+        self.manifest.put_file(build_manifest_file(&self.output_dir_path, &transaction_file)?);
+        self.manifest.put_date(ManifestDate {
+            date: date.format("%Y%m%d").to_string(),
+            start_slot: Some(start_slot),
+            end_slot: state.slot,
+            state_file,
+            transaction_file: Some(transaction_file),
+        });
+        return self.write_manifest();
+    }
+
+    // write is called with {file}.part, which is renamed to the file after write succeeded
+    fn write_file<F>(&self, relative_path: &String, write: F) -> Result<(), ReplayError>
+    where
+        F: FnOnce(&String) -> Result<(), ReplayError>,
+    {
+        let file_path = format!("{}/{}", self.output_dir_path, relative_path);
+        let part_file_path = format!("{}.part", file_path);
+        let io_error = |source: std::io::Error| ReplayError::Io {
+            path: file_path.clone(),
+            source,
+        };
+
+        if let Some(dir_path) = Path::new(&file_path).parent() {
+            std::fs::create_dir_all(dir_path).map_err(io_error)?;
+        }
+        write(&part_file_path)?;
+        std::fs::rename(&part_file_path, &file_path).map_err(io_error)?;
+        return Ok(());
+    }
+
+    fn write_manifest(&self) -> Result<(), ReplayError> {
+        let manifest_path = format!("{}/{}", self.output_dir_path, MANIFEST_FILE_NAME);
+        return write_storage_manifest(&manifest_path, &self.manifest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{Transaction, TransactionInstruction, WhirlpoolTransaction};
+    use crate::storage::LocalFileStorage;
+
+    // state at the end of 2022/04/06 (slot 128556169)
+    const SAMPLE_STATE_FILE: &str =
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../data/sample_local_storage/2022/0406/whirlpool-state-20220406.json.gz");
+
+    fn create_temp_dir(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("whirlpool-replayer-producer-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        return path.to_string_lossy().to_string();
+    }
+
+    fn date(yyyymmdd: &str) -> NaiveDate {
+        return NaiveDate::parse_from_str(yyyymmdd, "%Y%m%d").unwrap();
+    }
+
+    fn build_whirlpool_transaction(slot: u64, block_time: i64, transactions: Vec<Transaction>) -> WhirlpoolTransaction {
+        return WhirlpoolTransaction {
+            slot,
+            block_height: slot - 12000000,
+            block_time,
+            transactions,
+        };
+    }
+
+    // transaction storage with the transaction file of 2022/04/07
+    fn build_transaction_storage(dir_path: &String, whirlpool_transactions: Vec<WhirlpoolTransaction>) -> Arc<dyn StorageBackend> {
+        let file_path = format!("{}/{}", dir_path, io::get_whirlpool_transaction_file_relative_path(&date("20220407")));
+        std::fs::create_dir_all(Path::new(&file_path).parent().unwrap()).unwrap();
+        io::save_to_whirlpool_transaction_file(&file_path, whirlpool_transactions.into_iter().map(Ok)).unwrap();
+        return Arc::new(LocalFileStorage::new(dir_path));
+    }
+
+    // part files must not be left in the output
+    fn list_files(dir_path: &String) -> Vec<String> {
+        let mut files = Vec::new();
+        let mut dirs = vec![std::path::PathBuf::from(dir_path)];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    files.push(path.strip_prefix(dir_path).unwrap().to_string_lossy().trim_start_matches('/').to_string());
+                }
+            }
+        }
+        files.sort();
+        return files;
+    }
+
+    #[test]
+    fn test_produce() {
+        let transaction_dir_path = create_temp_dir("transactions");
+        let output_dir_path = create_temp_dir("output");
+
+        // slots without transactions (the state is not changed, but the slot advances)
+        let transaction_storage = build_transaction_storage(
+            &transaction_dir_path,
+            vec![
+                build_whirlpool_transaction(128556170, 1649289600, vec![]),
+                build_whirlpool_transaction(128556171, 1649289601, vec![]),
+            ],
+        );
+        let state = io::load_from_local_whirlpool_state_file(&SAMPLE_STATE_FILE.to_string()).unwrap();
+        let start_state_slot = state.slot;
+
+        let mut producer = StorageProducer::new(&output_dir_path).unwrap();
+        let produced = producer
+            .produce(state, transaction_storage, &"20220407".to_string(), Some(&"20220407".to_string()))
+            .unwrap();
+        assert_eq!(produced, vec![date("20220407")]);
+
+        assert_eq!(
+            list_files(&output_dir_path),
+            vec![
+                "2022/0406/whirlpool-state-20220406.json.gz".to_string(),
+                "2022/0407/whirlpool-state-20220407.json.gz".to_string(),
+                "2022/0407/whirlpool-transaction-20220407.jsonl.gz".to_string(),
+                MANIFEST_FILE_NAME.to_string(),
+            ]
+        );
+
+        // transaction file is copied as is
+        let transaction_file = io::get_whirlpool_transaction_file_relative_path(&date("20220407"));
+        assert_eq!(
+            std::fs::read(format!("{}/{}", output_dir_path, transaction_file)).unwrap(),
+            std::fs::read(format!("{}/{}", transaction_dir_path, transaction_file)).unwrap()
+        );
+
+        let output = LocalFileStorage::new(&output_dir_path);
+        let starting_state = output.load_whirlpool_state(&date("20220406")).unwrap();
+        assert_eq!(starting_state.slot, start_state_slot);
+        let replayed_state = output.load_whirlpool_state(&date("20220407")).unwrap();
+        assert_eq!(replayed_state.slot, 128556171);
+        let to_map = |state: &WhirlpoolState| -> std::collections::BTreeMap<String, Vec<u8>> {
+            return state.accounts.iter().map(|account| (account.pubkey.clone(), account.data.clone())).collect();
+        };
+        assert_eq!(to_map(&replayed_state), to_map(&starting_state));
+
+        // the manifest written to the output is the same as the one of the producer
+        let manifest = output.load_manifest().unwrap().unwrap();
+        assert_eq!(&manifest, producer.get_manifest());
+        let starting_date = manifest.get_date(&date("20220406")).unwrap();
+        assert_eq!((starting_date.start_slot, starting_date.end_slot), (None, start_state_slot));
+        assert!(starting_date.transaction_file.is_none());
+        let produced_date = manifest.get_date(&date("20220407")).unwrap();
+        assert_eq!((produced_date.start_slot, produced_date.end_slot), (Some(128556170), 128556171));
+        assert_eq!(produced_date.transaction_file, Some(transaction_file.clone()));
+        assert_eq!(manifest.get_file(&transaction_file), Some(&build_manifest_file(&output_dir_path, &transaction_file).unwrap()));
+
+        std::fs::remove_dir_all(&transaction_dir_path).unwrap();
+        std::fs::remove_dir_all(&output_dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_produce_stops_at_failed_instruction() {
+        let transaction_dir_path = create_temp_dir("failed-transactions");
+        let output_dir_path = create_temp_dir("failed-output");
+
+        // tick indexes are not multiples of tick_spacing, so the instruction fails
+        let payload = serde_json::json!({
+            "dataTickLowerIndex": 1,
+            "dataTickUpperIndex": 2,
+            "keyFunder": "3PSEv8yEm8Z2Gv97iETRdX87RKNFL17CrMEC6rdftnd9",
+            "keyOwner": "3PSEv8yEm8Z2Gv97iETRdX87RKNFL17CrMEC6rdftnd9",
+            "keyPosition": "DSkBeB45F8TbfjX85HWL3FPTp4ymYfy5Bs1DTDkhiaGu",
+            "keyPositionMint": "A9mH23Jr5RXBnbLzDpbxUwWFGBFhhMYbcPQxftXtPpSC",
+            "keyPositionTokenAccount": "EWLXqyCdwTgp8575spvtynMqEWWgkGHDAmDXG9yiDTED",
+            "keyWhirlpool": "FSBDeuLQ19Hse2jzhHfDwTvEoeRWD7UKK4BM1zgbV7pY",
+            "keyTokenProgram": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+            "keySystemProgram": "11111111111111111111111111111111",
+            "keyRent": "SysvarRent111111111111111111111111111111111",
+            "keyAssociatedTokenProgram": "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+        });
+        let transaction = Transaction {
+            index: 0,
+            signature: "failed".to_string(),
+            payer: "3PSEv8yEm8Z2Gv97iETRdX87RKNFL17CrMEC6rdftnd9".to_string(),
+            balances: vec![],
+            instructions: vec![TransactionInstruction {
+                name: "openPosition".to_string(),
+                payload,
+                decoded: None,
+            }],
+        };
+        let transaction_storage = build_transaction_storage(
+            &transaction_dir_path,
+            vec![
+                build_whirlpool_transaction(128556170, 1649289600, vec![]),
+                build_whirlpool_transaction(128556171, 1649289601, vec![transaction]),
+            ],
+        );
+        let state = io::load_from_local_whirlpool_state_file(&SAMPLE_STATE_FILE.to_string()).unwrap();
+
+        let mut producer = StorageProducer::new(&output_dir_path).unwrap();
+        let result = producer.produce(state, transaction_storage, &"20220407".to_string(), Some(&"20220407".to_string()));
+        assert!(matches!(result, Err(ReplayError::InstructionReplayFailed { .. })));
+
+        // only the starting state is in the output
+        assert_eq!(
+            list_files(&output_dir_path),
+            vec!["2022/0406/whirlpool-state-20220406.json.gz".to_string(), MANIFEST_FILE_NAME.to_string()]
+        );
+        let manifest = LocalFileStorage::new(&output_dir_path).load_manifest().unwrap().unwrap();
+        assert!(manifest.get_date(&date("20220406")).is_some());
+        assert!(manifest.get_date(&date("20220407")).is_none());
+
+        std::fs::remove_dir_all(&transaction_dir_path).unwrap();
+        std::fs::remove_dir_all(&output_dir_path).unwrap();
+    }
+}
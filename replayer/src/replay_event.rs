@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use replay_engine::decoded_instructions::DecodedWhirlpoolInstruction;
+
+use crate::errors::ReplayError;
+use crate::schema::Transaction;
+use crate::{ReplayInstructionResult, ReplayUntil, Slot, WhirlpoolReplayer};
+
+pub enum ReplayEvent {
+    // the slot has been applied to the engine (no transaction has been replayed yet)
+    SlotStart(Slot),
+    TransactionStart(Slot, Arc<Transaction>),
+    Instruction(ReplayedInstruction),
+    // the program has been updated (the new program data is available via get_program_data)
+    ProgramDeploy(ProgramDeployment),
+    // all transactions of the day have been replayed
+    DayEnd(chrono::NaiveDate),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ProgramDeployment {
+    pub slot: Slot,
+    pub signature: String,
+    // sha256 (base58) of the new program data
+    pub program_data_hash: String,
+    // None if the program data is not registered in ProgramVersionRegistry
+    pub version: Option<String>,
+}
+
+pub struct ReplayedInstruction {
+    pub slot: Slot,
+    pub transaction: Arc<Transaction>,
+    pub name: String,
+    pub instruction: DecodedWhirlpoolInstruction,
+    pub result: ReplayInstructionResult,
+}
+
+// ReplayEvents drives the replayer step by step.
+//
+// Iterator can't lend the replayer while iterating, so use the following pattern
+// to access the accounts after each event:
+//
+//   let mut events = replayer.events(ReplayUntil::End);
+//   while let Some(event) = events.next() {
+//     let accounts = events.get_replayer().get_accounts();
+//   }
+pub struct ReplayEvents<'a> {
+    replayer: &'a mut WhirlpoolReplayer,
+    cond: ReplayUntil,
+}
+
+impl<'a> ReplayEvents<'a> {
+    pub(crate) fn new(replayer: &'a mut WhirlpoolReplayer, cond: ReplayUntil) -> ReplayEvents<'a> {
+        return ReplayEvents { replayer, cond };
+    }
+
+    pub fn get_replayer(&self) -> &WhirlpoolReplayer {
+        return self.replayer;
+    }
+}
+
+impl<'a> Iterator for ReplayEvents<'a> {
+    type Item = Result<ReplayEvent, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        return self.replayer.next_event(&self.cond).transpose();
+    }
+}
@@ -0,0 +1,66 @@
+use replay_engine::decoded_instructions::DecodedWhirlpoolInstruction;
+
+use crate::replay_event::ProgramDeployment;
+use crate::schema::Transaction;
+use crate::{AccountMap, DayCallback, InstructionCallback, ReplayInstructionResult, Slot, SlotCallback};
+
+// ReplayObserver receives replay events on a caller's struct, so it can keep state across events.
+// All hooks do nothing by default.
+pub trait ReplayObserver {
+    fn on_slot(&mut self, _slot: &Slot) {}
+
+    fn on_transaction(&mut self, _slot: &Slot, _transaction: &Transaction) {}
+
+    fn on_instruction(
+        &mut self,
+        _slot: &Slot,
+        _transaction: &Transaction,
+        _name: &String,
+        _instruction: &DecodedWhirlpoolInstruction,
+        _accounts: &AccountMap,
+        _result: &ReplayInstructionResult,
+    ) {
+    }
+
+    fn on_program_deploy(&mut self, _deployment: &ProgramDeployment, _program_data: &Vec<u8>) {}
+
+    fn on_day_end(&mut self, _date: &chrono::NaiveDate, _slot: &Slot, _program_data: &Vec<u8>, _accounts: &AccountMap) {}
+
+    // called once when replay stops (until condition reached or no more transactions)
+    fn on_finish(&mut self, _slot: &Slot, _program_data: &Vec<u8>, _accounts: &AccountMap) {}
+}
+
+// adapter for the function pointer callbacks of replay()
+pub(crate) struct CallbackObserver {
+    pub slot_callback: Option<SlotCallback>,
+    pub instruction_callback: Option<InstructionCallback>,
+    pub day_callback: Option<DayCallback>,
+}
+
+impl ReplayObserver for CallbackObserver {
+    fn on_slot(&mut self, slot: &Slot) {
+        if let Some(callback) = self.slot_callback {
+            callback(slot);
+        }
+    }
+
+    fn on_instruction(
+        &mut self,
+        slot: &Slot,
+        transaction: &Transaction,
+        name: &String,
+        instruction: &DecodedWhirlpoolInstruction,
+        accounts: &AccountMap,
+        result: &ReplayInstructionResult,
+    ) {
+        if let Some(callback) = self.instruction_callback {
+            callback(slot, transaction, name, instruction, accounts, result);
+        }
+    }
+
+    fn on_day_end(&mut self, date: &chrono::NaiveDate, slot: &Slot, program_data: &Vec<u8>, accounts: &AccountMap) {
+        if let Some(callback) = self.day_callback {
+            callback(date, slot, program_data, accounts);
+        }
+    }
+}
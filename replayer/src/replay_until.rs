@@ -0,0 +1,253 @@
+use std::fmt;
+use std::sync::Arc;
+
+use replay_engine::decoded_instructions::DecodedWhirlpoolInstruction;
+
+use crate::schema::Transaction;
+use crate::Slot;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ReplayUntil {
+    End,
+    // replay all transactions in slots <= the given value
+    Slot(u64),
+    BlockHeight(u64),
+    BlockTime(i64),
+    // stop before (Exclusive) or after (Inclusive) the transaction with the signature
+    Signature(String, ReplayUntilBound),
+    // stop before (Exclusive) or after (Inclusive) the transaction at (slot, index).
+    // transaction files contain only whirlpool transactions, so the position doesn't need to exist.
+    TransactionIndex(u64, u32, ReplayUntilBound),
+    // stop before (Exclusive) or after (Inclusive) the first instruction that matches
+    Predicate(ReplayUntilPredicate, ReplayUntilBound),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ReplayUntilBound {
+    Inclusive,
+    Exclusive,
+}
+
+pub type ReplayUntilPredicateFn =
+    dyn Fn(&Slot, &Transaction, &String, &DecodedWhirlpoolInstruction) -> bool + Send + Sync;
+
+#[derive(Clone)]
+pub struct ReplayUntilPredicate(Arc<ReplayUntilPredicateFn>);
+
+impl ReplayUntilPredicate {
+    pub fn new<F>(predicate: F) -> ReplayUntilPredicate
+    where
+        F: Fn(&Slot, &Transaction, &String, &DecodedWhirlpoolInstruction) -> bool + Send + Sync + 'static,
+    {
+        return ReplayUntilPredicate(Arc::new(predicate));
+    }
+
+    pub fn matches(
+        &self,
+        slot: &Slot,
+        transaction: &Transaction,
+        name: &String,
+        instruction: &DecodedWhirlpoolInstruction,
+    ) -> bool {
+        return (self.0)(slot, transaction, name, instruction);
+    }
+}
+
+impl fmt::Debug for ReplayUntilPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str("ReplayUntilPredicate(..)");
+    }
+}
+
+// closures are not comparable, so two predicates are equal only if they share the same closure
+impl PartialEq for ReplayUntilPredicate {
+    fn eq(&self, other: &ReplayUntilPredicate) -> bool {
+        return Arc::as_ptr(&self.0) as *const () == Arc::as_ptr(&other.0) as *const ();
+    }
+}
+
+impl Eq for ReplayUntilPredicate {}
+
+// checked before the slot is started
+pub(crate) fn should_stop_before_slot(cond: &ReplayUntil, slot: &Slot) -> bool {
+    match cond {
+        ReplayUntil::Slot(until_slot) => slot.slot > *until_slot,
+        ReplayUntil::BlockHeight(until_block_height) => slot.block_height > *until_block_height,
+        ReplayUntil::BlockTime(until_block_time) => slot.block_time > *until_block_time,
+        ReplayUntil::TransactionIndex(until_slot, _, _) => slot.slot > *until_slot,
+        _ => false,
+    }
+}
+
+// checked before the first instruction of the transaction is replayed
+pub(crate) fn should_stop_before_transaction(cond: &ReplayUntil, slot: &Slot, transaction: &Transaction) -> bool {
+    match cond {
+        ReplayUntil::Signature(signature, ReplayUntilBound::Exclusive) => transaction.signature == *signature,
+        ReplayUntil::TransactionIndex(until_slot, until_index, bound) => {
+            let position = (slot.slot, transaction.index);
+            let until = (*until_slot, *until_index);
+            match bound {
+                ReplayUntilBound::Inclusive => position > until,
+                ReplayUntilBound::Exclusive => position >= until,
+            }
+        }
+        _ => false,
+    }
+}
+
+// checked after the last instruction of the transaction has been replayed
+pub(crate) fn should_stop_after_transaction(cond: &ReplayUntil, transaction: &Transaction) -> bool {
+    match cond {
+        ReplayUntil::Signature(signature, ReplayUntilBound::Inclusive) => transaction.signature == *signature,
+        _ => false,
+    }
+}
+
+// checked before the instruction is replayed.
+// returns Some(bound) if the instruction matches the predicate.
+pub(crate) fn match_instruction(
+    cond: &ReplayUntil,
+    slot: &Slot,
+    transaction: &Transaction,
+    name: &String,
+    instruction: &DecodedWhirlpoolInstruction,
+) -> Option<ReplayUntilBound> {
+    match cond {
+        ReplayUntil::Predicate(predicate, bound) => {
+            if predicate.matches(slot, transaction, name, instruction) {
+                Some(*bound)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use replay_engine::decoded_instructions::DecodedSetFeeRate;
+
+    fn build_slot(slot: u64) -> Slot {
+        return Slot {
+            slot,
+            block_height: slot - 100,
+            block_time: slot as i64 * 2,
+        };
+    }
+
+    fn build_transaction(index: u32, signature: &str) -> Transaction {
+        return Transaction {
+            index,
+            signature: signature.to_string(),
+            payer: "payer".to_string(),
+            balances: Vec::new(),
+            instructions: Vec::new(),
+        };
+    }
+
+    fn build_set_fee_rate(fee_rate: u16) -> DecodedWhirlpoolInstruction {
+        return DecodedWhirlpoolInstruction::SetFeeRate(DecodedSetFeeRate {
+            data_fee_rate: fee_rate,
+            key_whirlpools_config: "config".to_string(),
+            key_whirlpool: "whirlpool".to_string(),
+            key_fee_authority: "authority".to_string(),
+        });
+    }
+
+    #[test]
+    fn test_should_stop_before_slot() {
+        let slot = build_slot(1000);
+
+        assert!(!should_stop_before_slot(&ReplayUntil::End, &slot));
+        assert!(!should_stop_before_slot(&ReplayUntil::Slot(1000), &slot));
+        assert!(should_stop_before_slot(&ReplayUntil::Slot(999), &slot));
+        assert!(!should_stop_before_slot(&ReplayUntil::BlockHeight(900), &slot));
+        assert!(should_stop_before_slot(&ReplayUntil::BlockHeight(899), &slot));
+        assert!(!should_stop_before_slot(&ReplayUntil::BlockTime(2000), &slot));
+        assert!(should_stop_before_slot(&ReplayUntil::BlockTime(1999), &slot));
+        assert!(should_stop_before_slot(
+            &ReplayUntil::TransactionIndex(999, 5, ReplayUntilBound::Inclusive),
+            &slot
+        ));
+        assert!(!should_stop_before_slot(
+            &ReplayUntil::TransactionIndex(1000, 0, ReplayUntilBound::Exclusive),
+            &slot
+        ));
+        assert!(!should_stop_before_slot(
+            &ReplayUntil::Signature("sig".to_string(), ReplayUntilBound::Exclusive),
+            &slot
+        ));
+    }
+
+    #[test]
+    fn test_should_stop_at_signature() {
+        let slot = build_slot(1000);
+        let transaction = build_transaction(3, "sig");
+        let other_transaction = build_transaction(4, "other");
+
+        let exclusive = ReplayUntil::Signature("sig".to_string(), ReplayUntilBound::Exclusive);
+        assert!(should_stop_before_transaction(&exclusive, &slot, &transaction));
+        assert!(!should_stop_before_transaction(&exclusive, &slot, &other_transaction));
+        assert!(!should_stop_after_transaction(&exclusive, &transaction));
+
+        let inclusive = ReplayUntil::Signature("sig".to_string(), ReplayUntilBound::Inclusive);
+        assert!(!should_stop_before_transaction(&inclusive, &slot, &transaction));
+        assert!(should_stop_after_transaction(&inclusive, &transaction));
+        assert!(!should_stop_after_transaction(&inclusive, &other_transaction));
+    }
+
+    #[test]
+    fn test_should_stop_at_transaction_index() {
+        let inclusive = ReplayUntil::TransactionIndex(1000, 3, ReplayUntilBound::Inclusive);
+        let exclusive = ReplayUntil::TransactionIndex(1000, 3, ReplayUntilBound::Exclusive);
+
+        // (slot, index) is compared, the position doesn't need to exist
+        let cases = [
+            (999, 10, false, false),
+            (1000, 2, false, false),
+            (1000, 3, false, true),
+            (1000, 4, true, true),
+            (1001, 0, true, true),
+        ];
+        for (slot, index, inclusive_stop, exclusive_stop) in cases {
+            let transaction = build_transaction(index, "sig");
+            assert_eq!(should_stop_before_transaction(&inclusive, &build_slot(slot), &transaction), inclusive_stop);
+            assert_eq!(should_stop_before_transaction(&exclusive, &build_slot(slot), &transaction), exclusive_stop);
+        }
+    }
+
+    #[test]
+    fn test_match_instruction() {
+        let slot = build_slot(1000);
+        let transaction = build_transaction(3, "sig");
+        let name = "setFeeRate".to_string();
+        let predicate = ReplayUntilPredicate::new(|_, _, name, instruction| {
+            return name == "setFeeRate"
+                && matches!(instruction, DecodedWhirlpoolInstruction::SetFeeRate(params) if params.data_fee_rate > 3000);
+        });
+
+        let cond = ReplayUntil::Predicate(predicate.clone(), ReplayUntilBound::Exclusive);
+        assert_eq!(
+            match_instruction(&cond, &slot, &transaction, &name, &build_set_fee_rate(3001)),
+            Some(ReplayUntilBound::Exclusive)
+        );
+        assert_eq!(match_instruction(&cond, &slot, &transaction, &name, &build_set_fee_rate(3000)), None);
+
+        // predicate is not evaluated for the other conditions
+        let cond = ReplayUntil::Signature("sig".to_string(), ReplayUntilBound::Exclusive);
+        assert_eq!(match_instruction(&cond, &slot, &transaction, &name, &build_set_fee_rate(3001)), None);
+
+        // predicates are equal only if they share the same closure
+        let other = ReplayUntilPredicate::new(|_, _, _, _| true);
+        assert_eq!(
+            ReplayUntil::Predicate(predicate.clone(), ReplayUntilBound::Inclusive),
+            ReplayUntil::Predicate(predicate.clone(), ReplayUntilBound::Inclusive)
+        );
+        assert_ne!(
+            ReplayUntil::Predicate(predicate, ReplayUntilBound::Inclusive),
+            ReplayUntil::Predicate(other, ReplayUntilBound::Inclusive)
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use serde_derive::{Deserialize, Serialize};
 use serde_json::Value;
-use replay_engine::decoded_instructions::{deserialize_u64, deserialize_base64, serialize_base64};
+use replay_engine::decoded_instructions::{deserialize_u64, serialize_u64, deserialize_base64, serialize_base64, deserialize_option_base64, serialize_option_base64};
 
 pub use replay_engine::decoded_instructions::{DecodedInstruction, DecodedProgramDeployInstruction, DecodedWhirlpoolInstruction};
 
@@ -19,9 +19,14 @@ A whirlpool state file (whirlpool-state-yyyymmdd.json.gz) is GZIP compressed JSO
     { pubkey: String(base58 encoding), data: String(base64 encoding) },
     ...
   ],
-  programData: String(base64 encoding)
+  programData: String(base64 encoding),
+  position: { transactionIndex: u32, instructionIndex: u32 } (optional)
 }
 
+position is present only if the state was saved in the middle of the slot.
+transactions before transactionIndex in the slot, and instructions before instructionIndex in the transaction
+at transactionIndex have been applied. replay resumes from there instead of the next slot.
+
 */
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -33,6 +38,15 @@ pub struct WhirlpoolState {
   pub accounts: Vec<WhirlpoolStateAccount>,
   #[serde(deserialize_with = "deserialize_base64", serialize_with = "serialize_base64")]
   pub program_data: Vec<u8>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub position: Option<WhirlpoolStatePosition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct WhirlpoolStatePosition {
+  pub transaction_index: u32,
+  pub instruction_index: u32,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -45,6 +59,47 @@ pub struct WhirlpoolStateAccount {
 
 /*
 
+Whirlpool State Delta File JSON Schema
+
+A whirlpool state delta file (whirlpool-state-delta-yyyymmdd.json.gz) is GZIP compressed JSON file.
+It holds only the difference from the state of the previous day (base state):
+
+{
+  baseSlot: u64(slot of the base state),
+  slot: u64,
+  blockHeight: u64,
+  blockTime: i64,
+  accounts: [
+    { pubkey: String(base58 encoding), data: String(base64 encoding) },  (created or changed accounts)
+    ...
+  ],
+  closedAccounts: [
+    String(base58 encoding),
+    ...
+  ],
+  programData: String(base64 encoding) | null(unchanged),
+  position: { transactionIndex: u32, instructionIndex: u32 } (optional, see the state file)
+}
+
+*/
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WhirlpoolStateDelta {
+  pub base_slot: u64,
+  pub slot: u64,
+  pub block_height: u64,
+  pub block_time: i64,
+  pub accounts: Vec<WhirlpoolStateAccount>,
+  pub closed_accounts: Vec<String>,
+  #[serde(default, deserialize_with = "deserialize_option_base64", serialize_with = "serialize_option_base64")]
+  pub program_data: Option<Vec<u8>>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub position: Option<WhirlpoolStatePosition>,
+}
+
+/*
+
 Whirlpool Transaction File JSON Lines Format
 
 A whirlpool transaction file (whirlpool-transaction-yyyymmdd.json.gz) is GZIP compressed text file.
@@ -99,9 +154,9 @@ pub struct Transaction {
 #[serde(rename_all = "camelCase")]
 pub struct TransactionBalance {
   pub account: String,
-  #[serde(deserialize_with = "deserialize_u64")]
+  #[serde(deserialize_with = "deserialize_u64", serialize_with = "serialize_u64")]
   pub pre: u64,
-  #[serde(deserialize_with = "deserialize_u64")]
+  #[serde(deserialize_with = "deserialize_u64", serialize_with = "serialize_u64")]
   pub post: u64,
 }
 
@@ -110,4 +165,8 @@ pub struct TransactionBalance {
 pub struct TransactionInstruction {
   pub name: String,
   pub payload: Value,
+  // decoded payload (filled by the ingestion pipeline, None if not decoded yet).
+  // the database source fills only this (payload is null).
+  #[serde(skip)]
+  pub decoded: Option<DecodedInstruction>,
 }
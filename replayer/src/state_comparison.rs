@@ -0,0 +1,211 @@
+use replay_engine::account_diff::{diff_accounts, AccountChangeKind, AccountDiff};
+use replay_engine::account_kind::{classify_account, AccountKind};
+
+use crate::errors::ReplayError;
+use crate::io;
+use crate::schema::WhirlpoolState;
+use crate::util;
+use crate::Slot;
+
+// order of the kinds in the report
+const ACCOUNT_KINDS: [AccountKind; 7] = [
+    AccountKind::WhirlpoolsConfig,
+    AccountKind::FeeTier,
+    AccountKind::Whirlpool,
+    AccountKind::TickArray,
+    AccountKind::Position,
+    AccountKind::PositionBundle,
+    AccountKind::Unknown,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountKindComparison {
+    pub kind: AccountKind,
+    // accounts with the same data
+    pub matched: usize,
+    // accounts only in the reference state
+    pub missing: Vec<String>,
+    // accounts only in the replayed state
+    pub extra: Vec<String>,
+    // field level differences (pre: reference, post: replayed)
+    pub differing: Vec<AccountDiff>,
+}
+
+impl AccountKindComparison {
+    pub fn is_identical(&self) -> bool {
+        return self.missing.is_empty() && self.extra.is_empty() && self.differing.is_empty();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateComparison {
+    pub slot: u64,
+    // only the kinds found in either state
+    pub kinds: Vec<AccountKindComparison>,
+}
+
+impl StateComparison {
+    pub fn is_identical(&self) -> bool {
+        return self.kinds.iter().all(|kind| kind.is_identical());
+    }
+}
+
+// compare the accounts of a replayed state with a reference state at the same slot.
+// program data is not compared because account snapshots don't have it.
+pub fn compare_states(replayed: &WhirlpoolState, reference: &WhirlpoolState) -> Result<StateComparison, ReplayError> {
+    if replayed.slot != reference.slot {
+        return Err(ReplayError::StateSlotMismatch {
+            replayed: replayed.slot,
+            reference: reference.slot,
+        });
+    }
+
+    let replayed_accounts = util::convert_accounts_to_account_map(&replayed.accounts);
+    let reference_accounts = util::convert_accounts_to_account_map(&reference.accounts);
+
+    let mut kinds: Vec<AccountKindComparison> = ACCOUNT_KINDS
+        .iter()
+        .map(|kind| AccountKindComparison {
+            kind: *kind,
+            matched: 0,
+            missing: Vec::new(),
+            extra: Vec::new(),
+            differing: Vec::new(),
+        })
+        .collect();
+    let index_of = |kind: AccountKind| ACCOUNT_KINDS.iter().position(|k| *k == kind).unwrap();
+
+    for (pubkey, data) in replayed_accounts.iter() {
+        if reference_accounts.get(pubkey) == Some(data) {
+            kinds[index_of(classify_account(data))].matched += 1;
+        }
+    }
+
+    // sorted by pubkey
+    for diff in diff_accounts(&reference_accounts, &replayed_accounts) {
+        let comparison = &mut kinds[index_of(diff.kind)];
+        match diff.change {
+            AccountChangeKind::Closed => comparison.missing.push(diff.pubkey),
+            AccountChangeKind::Created => comparison.extra.push(diff.pubkey),
+            AccountChangeKind::Updated => comparison.differing.push(diff),
+        }
+    }
+
+    kinds.retain(|kind| kind.matched > 0 || !kind.is_identical());
+    return Ok(StateComparison {
+        slot: replayed.slot,
+        kinds,
+    });
+}
+
+// load a reference state from a state file or an account snapshot (whirlpool-snapshot-{slot}.csv.gz).
+// the state loaded from a snapshot has no block height, block time and program data.
+pub fn load_reference_state(file_path: &String) -> Result<WhirlpoolState, ReplayError> {
+    if !file_path.ends_with(".csv.gz") {
+        return io::load_from_local_whirlpool_state_file(file_path);
+    }
+
+    let slot = io::get_snapshot_slot_from_file_path(file_path).ok_or_else(|| ReplayError::InvalidSnapshotFile {
+        path: file_path.clone(),
+        line: 0,
+        reason: "slot is not found in the file name (whirlpool-snapshot-{slot}.csv.gz)".to_string(),
+    })?;
+    let slot = Slot {
+        slot,
+        block_height: 0,
+        block_time: 0,
+    };
+
+    let file = io::open_local_file(file_path)?;
+    let (state, _summary) = io::read_snapshot_csv(file, file_path, Vec::new(), &slot)?;
+    return Ok(state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::WhirlpoolStateAccount;
+
+    const SAMPLE_STATE_FILE: &str =
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../data/sample_local_storage/2022/0406/whirlpool-state-20220406.json.gz");
+    // liquidity (u128) of Position
+    const POSITION_LIQUIDITY_OFFSET: usize = 72;
+
+    fn load_sample_state() -> WhirlpoolState {
+        return io::load_from_local_whirlpool_state_file(&SAMPLE_STATE_FILE.to_string()).unwrap();
+    }
+
+    fn find_account_index(state: &WhirlpoolState, kind: AccountKind) -> usize {
+        return state.accounts.iter().position(|account| classify_account(&account.data) == kind).unwrap();
+    }
+
+    #[test]
+    fn test_compare_states() {
+        let reference = load_sample_state();
+        let mut replayed = load_sample_state();
+        // program data is not compared
+        replayed.program_data = vec![0xff];
+
+        let comparison = compare_states(&replayed, &reference).unwrap();
+        assert!(comparison.is_identical());
+        let matched: usize = comparison.kinds.iter().map(|kind| kind.matched).sum();
+        assert_eq!(matched, reference.accounts.len());
+
+        // differing position, missing tick array and extra unknown account
+        let position_index = find_account_index(&replayed, AccountKind::Position);
+        replayed.accounts[position_index].data[POSITION_LIQUIDITY_OFFSET] ^= 1;
+        let position = replayed.accounts[position_index].pubkey.clone();
+        let tick_array_index = find_account_index(&replayed, AccountKind::TickArray);
+        let tick_array = replayed.accounts.remove(tick_array_index).pubkey;
+        replayed.accounts.push(WhirlpoolStateAccount {
+            pubkey: "extra".to_string(),
+            data: vec![0u8; 16],
+        });
+
+        let comparison = compare_states(&replayed, &reference).unwrap();
+        assert_eq!(comparison.slot, reference.slot);
+        assert!(!comparison.is_identical());
+
+        let get_kind = |kind: AccountKind| comparison.kinds.iter().find(|comparison| comparison.kind == kind).unwrap();
+
+        let positions = get_kind(AccountKind::Position);
+        assert!(positions.missing.is_empty() && positions.extra.is_empty());
+        assert_eq!(positions.differing.len(), 1);
+        // pre is the reference, post is the replayed
+        let diff = &positions.differing[0];
+        assert_eq!(diff.pubkey, position);
+        let liquidity = diff.fields.iter().find(|field| field.field == "liquidity").unwrap();
+        assert_ne!(liquidity.pre, liquidity.post);
+
+        let tick_arrays = get_kind(AccountKind::TickArray);
+        assert_eq!(tick_arrays.missing, vec![tick_array]);
+        assert!(tick_arrays.extra.is_empty() && tick_arrays.differing.is_empty());
+
+        let unknowns = get_kind(AccountKind::Unknown);
+        assert_eq!(unknowns.extra, vec!["extra".to_string()]);
+
+        // kinds not affected are identical
+        assert!(get_kind(AccountKind::Whirlpool).is_identical());
+        let total = comparison.kinds.iter().map(|kind| kind.matched).sum::<usize>() + 2;
+        assert_eq!(total, reference.accounts.len());
+    }
+
+    #[test]
+    fn test_compare_states_at_different_slots() {
+        let reference = load_sample_state();
+        let mut replayed = load_sample_state();
+        replayed.slot += 1;
+        match compare_states(&replayed, &reference) {
+            Err(ReplayError::StateSlotMismatch { replayed, reference }) => assert_eq!(replayed, reference + 1),
+            result => panic!("unexpected result: {:?}", result.map(|comparison| comparison.slot)),
+        }
+    }
+
+    #[test]
+    fn test_load_reference_state_without_slot() {
+        match load_reference_state(&"whirlpool-snapshot.csv.gz".to_string()) {
+            Err(ReplayError::InvalidSnapshotFile { line, .. }) => assert_eq!(line, 0),
+            result => panic!("unexpected result: {:?}", result.map(|state| state.slot)),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::errors::ReplayError;
+use crate::schema::{WhirlpoolState, WhirlpoolStateAccount, WhirlpoolStateDelta};
+
+// A delta holds only the accounts created, changed or closed since the base state (the state of the previous day),
+// so a chain of daily deltas on a full state is much smaller than the full state of every day.
+//
+//   state(D) = apply(state(D-1), delta(D))
+
+// delta from base to state
+pub fn build_state_delta(base: &WhirlpoolState, state: &WhirlpoolState) -> WhirlpoolStateDelta {
+    let base_accounts: HashMap<&String, &Vec<u8>> =
+        base.accounts.iter().map(|account| (&account.pubkey, &account.data)).collect();
+    let pubkeys: HashSet<&String> = state.accounts.iter().map(|account| &account.pubkey).collect();
+
+    // created or changed (in the order of state)
+    let accounts = state
+        .accounts
+        .iter()
+        .filter(|account| base_accounts.get(&account.pubkey) != Some(&&account.data))
+        .map(|account| WhirlpoolStateAccount {
+            pubkey: account.pubkey.clone(),
+            data: account.data.clone(),
+        })
+        .collect();
+
+    // closed (in the order of base)
+    let closed_accounts = base
+        .accounts
+        .iter()
+        .filter(|account| !pubkeys.contains(&account.pubkey))
+        .map(|account| account.pubkey.clone())
+        .collect();
+
+    let program_data = if base.program_data == state.program_data {
+        None
+    } else {
+        Some(state.program_data.clone())
+    };
+
+    return WhirlpoolStateDelta {
+        base_slot: base.slot,
+        slot: state.slot,
+        block_height: state.block_height,
+        block_time: state.block_time,
+        accounts,
+        closed_accounts,
+        program_data,
+        position: state.position,
+    };
+}
+
+// apply delta to base.
+// the order of the accounts in base is kept, and created accounts are appended.
+pub fn apply_state_delta(base: WhirlpoolState, delta: &WhirlpoolStateDelta) -> Result<WhirlpoolState, ReplayError> {
+    if base.slot != delta.base_slot {
+        return Err(ReplayError::StateDeltaBaseMismatch {
+            base_slot: base.slot,
+            delta_base_slot: delta.base_slot,
+        });
+    }
+
+    let closed: HashSet<&String> = delta.closed_accounts.iter().collect();
+    let mut accounts: Vec<WhirlpoolStateAccount> = base
+        .accounts
+        .into_iter()
+        .filter(|account| !closed.contains(&account.pubkey))
+        .collect();
+
+    let mut indexes: HashMap<String, usize> = accounts
+        .iter()
+        .enumerate()
+        .map(|(index, account)| (account.pubkey.clone(), index))
+        .collect();
+    for account in delta.accounts.iter() {
+        match indexes.get(&account.pubkey) {
+            Some(index) => accounts[*index].data = account.data.clone(),
+            None => {
+                indexes.insert(account.pubkey.clone(), accounts.len());
+                accounts.push(WhirlpoolStateAccount {
+                    pubkey: account.pubkey.clone(),
+                    data: account.data.clone(),
+                });
+            }
+        }
+    }
+
+    return Ok(WhirlpoolState {
+        slot: delta.slot,
+        block_height: delta.block_height,
+        block_time: delta.block_time,
+        accounts,
+        program_data: delta.program_data.clone().unwrap_or(base.program_data),
+        position: delta.position,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::WhirlpoolStatePosition;
+
+    fn account(pubkey: &str, data: &[u8]) -> WhirlpoolStateAccount {
+        return WhirlpoolStateAccount {
+            pubkey: pubkey.to_string(),
+            data: data.to_vec(),
+        };
+    }
+
+    fn state(slot: u64, accounts: Vec<WhirlpoolStateAccount>, program_data: &[u8]) -> WhirlpoolState {
+        return WhirlpoolState {
+            slot,
+            block_height: slot - 10,
+            block_time: slot as i64 * 2,
+            accounts,
+            program_data: program_data.to_vec(),
+            position: None,
+        };
+    }
+
+    #[test]
+    fn test_state_delta_round_trip() {
+        let base = state(100, vec![account("a", &[1]), account("b", &[2]), account("c", &[3])], &[0xaa]);
+        let next = state(200, vec![account("d", &[4]), account("a", &[1]), account("c", &[30])], &[0xbb]);
+
+        let delta = build_state_delta(&base, &next);
+        assert_eq!(delta.base_slot, 100);
+        assert_eq!(delta.slot, 200);
+        // created and changed, unchanged "a" is not stored
+        assert_eq!(delta.accounts, vec![account("d", &[4]), account("c", &[30])]);
+        assert_eq!(delta.closed_accounts, vec!["b".to_string()]);
+        assert_eq!(delta.program_data, Some(vec![0xbb]));
+
+        // base order is kept and created accounts are appended
+        let applied = apply_state_delta(base, &delta).unwrap();
+        assert_eq!(applied, state(200, vec![account("a", &[1]), account("c", &[30]), account("d", &[4])], &[0xbb]));
+    }
+
+    #[test]
+    fn test_state_delta_without_changes() {
+        let base = state(100, vec![account("a", &[1])], &[0xaa]);
+        let mut next = state(200, vec![account("a", &[1])], &[0xaa]);
+        next.position = Some(WhirlpoolStatePosition {
+            transaction_index: 3,
+            instruction_index: 1,
+        });
+
+        let delta = build_state_delta(&base, &next);
+        assert!(delta.accounts.is_empty());
+        assert!(delta.closed_accounts.is_empty());
+        assert_eq!(delta.program_data, None);
+
+        // unchanged program data is taken from the base
+        assert_eq!(apply_state_delta(base, &delta).unwrap(), next);
+    }
+
+    #[test]
+    fn test_state_delta_base_mismatch() {
+        let base = state(100, vec![account("a", &[1])], &[0xaa]);
+        let next = state(200, vec![account("a", &[2])], &[0xaa]);
+        let delta = build_state_delta(&base, &next);
+
+        let other_base = state(150, vec![account("a", &[1])], &[0xaa]);
+        match apply_state_delta(other_base, &delta) {
+            Err(ReplayError::StateDeltaBaseMismatch { base_slot, delta_base_slot }) => {
+                assert_eq!((base_slot, delta_base_slot), (150, 100));
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use chrono::NaiveDate;
+
+use crate::errors::ReplayError;
+use crate::schema::WhirlpoolTransaction;
+use crate::WhirlpoolTransactionIterator;
+
+pub type WhirlpoolTransactionLoader =
+    Box<dyn FnMut(&NaiveDate) -> Result<WhirlpoolTransactionIterator, ReplayError> + Send>;
+
+pub enum TransactionStreamItem {
+    Transaction(WhirlpoolTransaction),
+    // all transactions of the day have been streamed
+    EndOfDay(NaiveDate),
+}
+
+// DailyTransactionStream chains the transaction files of consecutive days.
+// The file of the next day is opened lazily after the previous day has been exhausted.
+pub struct DailyTransactionStream {
+    loader: WhirlpoolTransactionLoader,
+    current: Option<(NaiveDate, WhirlpoolTransactionIterator)>,
+    end_date: Option<NaiveDate>,
+}
+
+impl DailyTransactionStream {
+    // the file of the start date is opened immediately to surface errors early.
+    // if end_date is None, the stream ends at the first day whose file is not found.
+    pub fn new(
+        mut loader: WhirlpoolTransactionLoader,
+        start_date: NaiveDate,
+        end_date: Option<NaiveDate>,
+    ) -> Result<DailyTransactionStream, ReplayError> {
+        let transaction_iter = loader(&start_date)?;
+        return Ok(DailyTransactionStream {
+            loader,
+            current: Some((start_date, transaction_iter)),
+            end_date,
+        });
+    }
+
+    // the date of the transaction file being streamed
+    pub fn get_current_date(&self) -> Option<NaiveDate> {
+        return self.current.as_ref().map(|(date, _)| *date);
+    }
+
+    pub fn next(&mut self) -> Result<Option<TransactionStreamItem>, ReplayError> {
+        let (date, transaction_iter) = match self.current.as_mut() {
+            Some(current) => current,
+            None => return Ok(None),
+        };
+
+        if let Some(whirlpool_transaction) = transaction_iter.next().transpose()? {
+            return Ok(Some(TransactionStreamItem::Transaction(whirlpool_transaction)));
+        }
+
+        let finished_date = *date;
+        self.current = self.open_next_day(&finished_date)?;
+        return Ok(Some(TransactionStreamItem::EndOfDay(finished_date)));
+    }
+
+    fn open_next_day(
+        &mut self,
+        finished_date: &NaiveDate,
+    ) -> Result<Option<(NaiveDate, WhirlpoolTransactionIterator)>, ReplayError> {
+        let next_date = match finished_date.succ_opt() {
+            Some(next_date) => next_date,
+            None => return Ok(None),
+        };
+
+        match self.end_date {
+            Some(end_date) if next_date > end_date => return Ok(None),
+            Some(_) => {
+                let transaction_iter = (self.loader)(&next_date)?;
+                return Ok(Some((next_date, transaction_iter)));
+            }
+            None => match (self.loader)(&next_date) {
+                Ok(transaction_iter) => return Ok(Some((next_date, transaction_iter))),
+                // open end: the latest available day has been reached
+                Err(err) if err.is_not_found() => return Ok(None),
+                Err(err) => return Err(err),
+            },
+        }
+    }
+}
@@ -0,0 +1,477 @@
+use std::collections::{BTreeSet, HashMap};
+
+use solana_transaction_status::{TransactionTokenBalance, TransactionWithStatusMeta};
+
+use replay_engine::decoded_instructions::DecodedWhirlpoolInstruction;
+
+use crate::schema::Transaction;
+use crate::{ReplayInstructionResult, Slot};
+
+const SPL_TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const SPL_TOKEN_INSTRUCTION_TRANSFER: u8 = 3;
+const SPL_TOKEN_INSTRUCTION_TRANSFER_CHECKED: u8 = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationMismatch {
+    pub slot: u64,
+    pub signature: String,
+    pub kind: VerificationMismatchKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationMismatchKind {
+    // token transfers executed by the replayed instruction differ from transfer_amount_*
+    TransferAmount {
+        name: String,
+        recorded: Vec<u64>,
+        replayed: Vec<u64>,
+    },
+    // net balance change of a token vault in the transaction differs from the recorded pre/post
+    BalanceChange {
+        account: String,
+        recorded: i128,
+        replayed: i128,
+    },
+}
+
+// Verifier compares the token movements in the bank with the recorded transaction.
+//
+// - per instruction: inner token transfers of the whirlpool instruction vs transfer_amount_* fields
+// - per transaction: sum of the balance changes of replayed instructions vs recorded balances (pre/post)
+//
+// Replay initializes token accounts with the minimum required amount for each instruction,
+// so absolute balances are not comparable, but the changes are.
+//
+// Only token vaults of the whirlpools of the replayed instructions are compared.
+// Recorded balances are the balances of vaults, and other token accounts in the transaction
+// can be moved by other programs (e.g. wrap/unwrap SOL, other legs of a route) that are not replayed.
+pub struct Verifier {
+    balance_changes: HashMap<String, i128>,
+    balance_changes_incomplete: bool,
+    // false if the recorded balances are not available
+    verify_balance_changes: bool,
+    mismatches: Vec<VerificationMismatch>,
+}
+
+impl Default for Verifier {
+    fn default() -> Self {
+        return Verifier {
+            balance_changes: HashMap::new(),
+            balance_changes_incomplete: false,
+            verify_balance_changes: true,
+            mismatches: Vec::new(),
+        };
+    }
+}
+
+impl Verifier {
+    pub fn new() -> Verifier {
+        return Verifier::default();
+    }
+
+    // only transfer amounts are verified
+    pub fn disable_balance_change_verification(&mut self) {
+        self.verify_balance_changes = false;
+    }
+
+    pub fn get_mismatches(&self) -> &[VerificationMismatch] {
+        return &self.mismatches;
+    }
+
+    pub fn take_mismatches(&mut self) -> Vec<VerificationMismatch> {
+        return std::mem::take(&mut self.mismatches);
+    }
+
+    pub fn verify_instruction(
+        &mut self,
+        slot: &Slot,
+        transaction: &Transaction,
+        name: &String,
+        instruction: &DecodedWhirlpoolInstruction,
+        result: &ReplayInstructionResult,
+    ) {
+        let tx_with_meta = match &result.transaction_status.tx_with_meta {
+            TransactionWithStatusMeta::Complete(tx_with_meta) => tx_with_meta,
+            TransactionWithStatusMeta::MissingMetadata(_) => return,
+        };
+
+        // failed instruction is handled by FailurePolicy, but it is also reflected to balance changes
+        if tx_with_meta.meta.status.is_ok() {
+            let recorded = get_recorded_transfer_amounts(instruction);
+            let replayed = get_replayed_transfer_amounts(tx_with_meta);
+            if recorded != replayed {
+                self.mismatches.push(VerificationMismatch {
+                    slot: slot.slot,
+                    signature: transaction.signature.clone(),
+                    kind: VerificationMismatchKind::TransferAmount {
+                        name: name.clone(),
+                        recorded,
+                        replayed,
+                    },
+                });
+            }
+        }
+
+        if !self.verify_balance_changes {
+            return;
+        }
+
+        // vaults are owned by the whirlpool
+        let whirlpools = get_whirlpools(instruction);
+        let account_keys = tx_with_meta.account_keys();
+        let pre_amounts = get_token_amounts(&tx_with_meta.meta.pre_token_balances);
+        let post_amounts = get_token_amounts(&tx_with_meta.meta.post_token_balances);
+
+        // an account only in pre has been closed, and an account only in post has been created
+        let account_indexes: BTreeSet<u8> = pre_amounts.keys().chain(post_amounts.keys()).copied().collect();
+        for account_index in account_indexes {
+            let (pre_owner, pre_amount) = pre_amounts.get(&account_index).map_or((None, 0), |(owner, amount)| (Some(owner), *amount));
+            let (post_owner, post_amount) = post_amounts.get(&account_index).map_or((None, 0), |(owner, amount)| (Some(owner), *amount));
+            let is_vault = pre_owner.or(post_owner).map_or(false, |owner| whirlpools.contains(owner));
+            if !is_vault {
+                continue;
+            }
+
+            if let Some(account) = account_keys.get(account_index as usize) {
+                *self
+                    .balance_changes
+                    .entry(account.to_string())
+                    .or_insert(0) += post_amount as i128 - pre_amount as i128;
+            }
+        }
+    }
+
+    // some instructions in the current transaction were not replayed as recorded (e.g. by pool filter),
+    // so its balance changes cannot be compared with the recorded ones
+    pub fn mark_balance_changes_incomplete(&mut self) {
+        self.balance_changes_incomplete = true;
+    }
+
+    // should be called after all instructions in the transaction have been replayed
+    pub fn verify_transaction(&mut self, slot: &Slot, transaction: &Transaction) {
+        let balance_changes = std::mem::take(&mut self.balance_changes);
+        if std::mem::take(&mut self.balance_changes_incomplete) || !self.verify_balance_changes {
+            return;
+        }
+
+        // only the vaults touched by the replayed instructions are compared.
+        // a vault without recorded balance has not been changed in the transaction.
+        let recorded_balance_changes: HashMap<&String, i128> = transaction
+            .balances
+            .iter()
+            .map(|balance| (&balance.account, balance.post as i128 - balance.pre as i128))
+            .collect();
+
+        let mut accounts: Vec<&String> = balance_changes.keys().collect();
+        accounts.sort();
+        for account in accounts {
+            let recorded = *recorded_balance_changes.get(account).unwrap_or(&0);
+            let replayed = balance_changes[account];
+            if recorded != replayed {
+                self.mismatches.push(VerificationMismatch {
+                    slot: slot.slot,
+                    signature: transaction.signature.clone(),
+                    kind: VerificationMismatchKind::BalanceChange {
+                        account: account.clone(),
+                        recorded,
+                        replayed,
+                    },
+                });
+            }
+        }
+    }
+}
+
+pub fn get_recorded_transfer_amounts(instruction: &DecodedWhirlpoolInstruction) -> Vec<u64> {
+    match instruction {
+        DecodedWhirlpoolInstruction::Swap(ix) => vec![ix.transfer_amount_0, ix.transfer_amount_1],
+        DecodedWhirlpoolInstruction::TwoHopSwap(ix) => vec![
+            ix.transfer_amount_0,
+            ix.transfer_amount_1,
+            ix.transfer_amount_2,
+            ix.transfer_amount_3,
+        ],
+        DecodedWhirlpoolInstruction::IncreaseLiquidity(ix) => vec![ix.transfer_amount_0, ix.transfer_amount_1],
+        DecodedWhirlpoolInstruction::DecreaseLiquidity(ix) => vec![ix.transfer_amount_0, ix.transfer_amount_1],
+        DecodedWhirlpoolInstruction::CollectFees(ix) => vec![ix.transfer_amount_0, ix.transfer_amount_1],
+        DecodedWhirlpoolInstruction::CollectProtocolFees(ix) => vec![ix.transfer_amount_0, ix.transfer_amount_1],
+        DecodedWhirlpoolInstruction::CollectReward(ix) => vec![ix.transfer_amount_0],
+        _ => vec![],
+    }
+}
+
+// whirlpools whose vaults are moved by the instruction
+fn get_whirlpools(instruction: &DecodedWhirlpoolInstruction) -> Vec<String> {
+    match instruction {
+        DecodedWhirlpoolInstruction::Swap(ix) => vec![ix.key_whirlpool.clone()],
+        DecodedWhirlpoolInstruction::TwoHopSwap(ix) => vec![ix.key_whirlpool_one.clone(), ix.key_whirlpool_two.clone()],
+        DecodedWhirlpoolInstruction::IncreaseLiquidity(ix) => vec![ix.key_whirlpool.clone()],
+        DecodedWhirlpoolInstruction::DecreaseLiquidity(ix) => vec![ix.key_whirlpool.clone()],
+        DecodedWhirlpoolInstruction::CollectFees(ix) => vec![ix.key_whirlpool.clone()],
+        DecodedWhirlpoolInstruction::CollectProtocolFees(ix) => vec![ix.key_whirlpool.clone()],
+        DecodedWhirlpoolInstruction::CollectReward(ix) => vec![ix.key_whirlpool.clone()],
+        _ => vec![],
+    }
+}
+
+fn get_replayed_transfer_amounts(
+    tx_with_meta: &solana_transaction_status::VersionedTransactionWithStatusMeta,
+) -> Vec<u64> {
+    let account_keys = tx_with_meta.account_keys();
+
+    let mut amounts = Vec::new();
+    for inner_instructions in tx_with_meta.meta.inner_instructions.iter().flatten() {
+        // the replayed transaction has the whirlpool instruction at index 0
+        if inner_instructions.index != 0 {
+            continue;
+        }
+
+        for inner_instruction in inner_instructions.instructions.iter() {
+            let instruction = &inner_instruction.instruction;
+            let is_token_program = account_keys
+                .get(instruction.program_id_index as usize)
+                .map_or(false, |program_id| program_id.to_string() == SPL_TOKEN_PROGRAM_ID);
+            if !is_token_program || instruction.data.len() < 9 {
+                continue;
+            }
+
+            match instruction.data[0] {
+                SPL_TOKEN_INSTRUCTION_TRANSFER | SPL_TOKEN_INSTRUCTION_TRANSFER_CHECKED => {
+                    let mut amount = [0u8; 8];
+                    amount.copy_from_slice(&instruction.data[1..9]);
+                    amounts.push(u64::from_le_bytes(amount));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    return amounts;
+}
+
+// account index -> (owner, amount)
+fn get_token_amounts(token_balances: &Option<Vec<TransactionTokenBalance>>) -> HashMap<u8, (String, u64)> {
+    let mut amounts = HashMap::new();
+    for token_balance in token_balances.iter().flatten() {
+        if let Ok(amount) = token_balance.ui_token_amount.amount.parse::<u64>() {
+            amounts.insert(token_balance.account_index, (token_balance.owner.clone(), amount));
+        }
+    }
+    return amounts;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use replay_engine::decoded_instructions::{from_json, DecodedInstruction};
+    use replay_engine::replay_instruction::WritableAccountSnapshot;
+    use solana_sdk::message::{Message, VersionedMessage};
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::transaction::VersionedTransaction;
+    use solana_transaction_status::{
+        ConfirmedTransactionWithStatusMeta, TransactionStatusMeta, VersionedTransactionWithStatusMeta,
+    };
+
+    use crate::schema::TransactionBalance;
+
+    const SLOT: Slot = Slot {
+        slot: 1,
+        block_height: 1,
+        block_time: 1,
+    };
+
+    fn swap(whirlpool: &Pubkey) -> DecodedWhirlpoolInstruction {
+        let json = format!(
+            r#"{{"dataAmount":"100","dataOtherAmountThreshold":"0","dataSqrtPriceLimit":"0","dataAmountSpecifiedIsInput":1,"dataAToB":1,
+            "keyTokenProgram":"{token_program}","keyTokenAuthority":"a","keyWhirlpool":"{whirlpool}","keyTokenOwnerAccountA":"b","keyVaultA":"c",
+            "keyTokenOwnerAccountB":"d","keyVaultB":"e","keyTickArray0":"f","keyTickArray1":"g","keyTickArray2":"h","keyOracle":"i",
+            "transferAmount0":"100","transferAmount1":"90"}}"#,
+            token_program = SPL_TOKEN_PROGRAM_ID,
+            whirlpool = whirlpool,
+        );
+        return match from_json(&"swap".to_string(), &json).unwrap() {
+            DecodedInstruction::WhirlpoolInstruction(ix) => ix,
+            _ => unreachable!(),
+        };
+    }
+
+    fn token_balance(account_index: u8, owner: &Pubkey, amount: u64) -> TransactionTokenBalance {
+        return TransactionTokenBalance {
+            account_index,
+            mint: Pubkey::new_unique().to_string(),
+            // UiTokenAmount (solana-account-decoder is not a direct dependency)
+            ui_token_amount: serde_json::from_value(serde_json::json!({
+                "uiAmount": null,
+                "decimals": 0,
+                "amount": amount.to_string(),
+                "uiAmountString": amount.to_string(),
+            }))
+            .unwrap(),
+            owner: owner.to_string(),
+            program_id: SPL_TOKEN_PROGRAM_ID.to_string(),
+        };
+    }
+
+    fn replay_result(
+        account_keys: Vec<Pubkey>,
+        pre_token_balances: Vec<TransactionTokenBalance>,
+        post_token_balances: Vec<TransactionTokenBalance>,
+    ) -> ReplayInstructionResult {
+        let message = Message {
+            account_keys,
+            ..Message::default()
+        };
+        let meta = TransactionStatusMeta {
+            pre_token_balances: Some(pre_token_balances),
+            post_token_balances: Some(post_token_balances),
+            ..TransactionStatusMeta::default()
+        };
+        return ReplayInstructionResult {
+            transaction_status: ConfirmedTransactionWithStatusMeta {
+                slot: SLOT.slot,
+                tx_with_meta: TransactionWithStatusMeta::Complete(VersionedTransactionWithStatusMeta {
+                    transaction: VersionedTransaction {
+                        signatures: vec![],
+                        message: VersionedMessage::Legacy(message),
+                    },
+                    meta,
+                }),
+                block_time: Some(SLOT.block_time),
+            },
+            snapshot: WritableAccountSnapshot {
+                pre_snapshot: Default::default(),
+                post_snapshot: Default::default(),
+            },
+        };
+    }
+
+    fn transaction(balances: Vec<(&Pubkey, u64, u64)>) -> Transaction {
+        return Transaction {
+            index: 0,
+            signature: "signature".to_string(),
+            payer: "payer".to_string(),
+            balances: balances
+                .into_iter()
+                .map(|(account, pre, post)| TransactionBalance {
+                    account: account.to_string(),
+                    pre,
+                    post,
+                })
+                .collect(),
+            instructions: vec![],
+        };
+    }
+
+    fn balance_change_mismatches(verifier: &Verifier) -> Vec<VerificationMismatchKind> {
+        return verifier
+            .get_mismatches()
+            .iter()
+            .filter(|mismatch| matches!(mismatch.kind, VerificationMismatchKind::BalanceChange { .. }))
+            .map(|mismatch| mismatch.kind.clone())
+            .collect();
+    }
+
+    #[test]
+    fn test_verify_transaction_compares_only_vaults() {
+        let whirlpool = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let vault_a = Pubkey::new_unique();
+        let vault_b = Pubkey::new_unique();
+        let created_vault = Pubkey::new_unique();
+        let closed_user_account = Pubkey::new_unique();
+        let untouched_vault = Pubkey::new_unique();
+
+        let account_keys = vec![Pubkey::new_unique(), vault_a, vault_b, created_vault, closed_user_account];
+        let result = replay_result(
+            account_keys,
+            vec![
+                token_balance(1, &whirlpool, 1000),
+                token_balance(2, &whirlpool, 500),
+                token_balance(4, &user, 70),
+            ],
+            vec![
+                token_balance(1, &whirlpool, 1100),
+                token_balance(2, &whirlpool, 410),
+                token_balance(3, &whirlpool, 10),
+            ],
+        );
+
+        // balances of the user account (e.g. unwrapped SOL) and the untouched vault are not compared
+        let recorded = transaction(vec![
+            (&vault_a, 5000, 5100),
+            (&vault_b, 7000, 6910),
+            (&created_vault, 0, 10),
+            (&untouched_vault, 3, 4),
+        ]);
+
+        let mut verifier = Verifier::new();
+        verifier.verify_instruction(&SLOT, &recorded, &"swap".to_string(), &swap(&whirlpool), &result);
+        verifier.verify_transaction(&SLOT, &recorded);
+        assert!(balance_change_mismatches(&verifier).is_empty());
+
+        // the created vault has no recorded balance (-> unchanged)
+        let recorded = transaction(vec![(&vault_a, 5000, 5100), (&vault_b, 7000, 6911)]);
+        let mut verifier = Verifier::new();
+        verifier.verify_instruction(&SLOT, &recorded, &"swap".to_string(), &swap(&whirlpool), &result);
+        verifier.verify_transaction(&SLOT, &recorded);
+        let mut expected = vec![
+            VerificationMismatchKind::BalanceChange {
+                account: vault_b.to_string(),
+                recorded: -89,
+                replayed: -90,
+            },
+            VerificationMismatchKind::BalanceChange {
+                account: created_vault.to_string(),
+                recorded: 0,
+                replayed: 10,
+            },
+        ];
+        expected.sort_by_key(|kind| match kind {
+            VerificationMismatchKind::BalanceChange { account, .. } => account.clone(),
+            _ => unreachable!(),
+        });
+        assert_eq!(balance_change_mismatches(&verifier), expected);
+    }
+
+    #[test]
+    fn test_verify_transaction_skips_incomplete_transaction() {
+        let whirlpool = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let result = replay_result(
+            vec![Pubkey::new_unique(), vault],
+            vec![token_balance(1, &whirlpool, 1000)],
+            vec![token_balance(1, &whirlpool, 1100)],
+        );
+        let recorded = transaction(vec![(&vault, 0, 1)]);
+
+        let mut verifier = Verifier::new();
+        verifier.verify_instruction(&SLOT, &recorded, &"swap".to_string(), &swap(&whirlpool), &result);
+        verifier.mark_balance_changes_incomplete();
+        verifier.verify_transaction(&SLOT, &recorded);
+        assert!(balance_change_mismatches(&verifier).is_empty());
+
+        // the flag is reset for the next transaction
+        verifier.verify_instruction(&SLOT, &recorded, &"swap".to_string(), &swap(&whirlpool), &result);
+        verifier.verify_transaction(&SLOT, &recorded);
+        assert_eq!(balance_change_mismatches(&verifier).len(), 1);
+    }
+
+    #[test]
+    fn test_verify_transaction_without_balances() {
+        let whirlpool = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let result = replay_result(
+            vec![Pubkey::new_unique(), vault],
+            vec![token_balance(1, &whirlpool, 1000)],
+            vec![token_balance(1, &whirlpool, 1100)],
+        );
+        // e.g. transactions from the database
+        let recorded = transaction(vec![]);
+
+        let mut verifier = Verifier::new();
+        verifier.disable_balance_change_verification();
+        verifier.verify_instruction(&SLOT, &recorded, &"swap".to_string(), &swap(&whirlpool), &result);
+        verifier.verify_transaction(&SLOT, &recorded);
+        assert!(balance_change_mismatches(&verifier).is_empty());
+    }
+}